@@ -0,0 +1,37 @@
+//! Database module, shared by the server and the admin CLI
+
+use color_eyre::Result;
+use eyre::ErrReport;
+use sqlx::migrate::Migrator;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::config::DatabaseConfig;
+
+/// Migrations of the `migrations` directory, embedded at compile time
+pub static MIGRATOR: Migrator = sqlx::migrate!("./../../migrations");
+
+/// Builds the connection pool, connecting eagerly unless `database.lazy` is set
+pub async fn configure_db_pool(config: &DatabaseConfig) -> Result<PgPool> {
+    let seconds = |s: u64| if s == 0 { None } else { Some(Duration::from_secs(s)) };
+
+    let connect_options =
+        PgConnectOptions::from_str(&config.url)?.statement_cache_capacity(config.statement_cache_capacity);
+    let pool_options = PgPoolOptions::new()
+        .min_connections(config.min_connections)
+        .max_connections(config.max_connections)
+        .connect_timeout(Duration::from_secs(config.acquire_timeout))
+        .idle_timeout(seconds(config.idle_timeout))
+        .max_lifetime(seconds(config.max_lifetime));
+
+    if config.lazy {
+        Ok(pool_options.connect_lazy_with(connect_options))
+    } else {
+        pool_options
+            .connect_with(connect_options)
+            .await
+            .map_err(|e| -> ErrReport { e.into() })
+    }
+}
@@ -4,6 +4,8 @@ use actix_http::ResponseBuilder;
 use actix_web::{error::ResponseError, http::header, http::StatusCode, HttpResponse};
 use northwind_core::errors::{AppError, AppErrorMessage};
 
+use crate::middlewares::request_id;
+
 #[derive(Debug)]
 pub struct ApiError(AppError);
 
@@ -26,6 +28,13 @@ impl ResponseError for ApiError {
             AppError::BadRequest { .. } => StatusCode::BAD_REQUEST,
             AppError::NotFound { .. } => StatusCode::NOT_FOUND,
             AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::Conflict { .. } => StatusCode::CONFLICT,
+            AppError::UnprocessableEntity { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::PreconditionFailed { .. } => StatusCode::PRECONDITION_FAILED,
+            AppError::PreconditionRequired { .. } => StatusCode::PRECONDITION_REQUIRED,
+            AppError::UnsupportedMediaType { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
         }
     }
 
@@ -35,16 +44,16 @@ impl ResponseError for ApiError {
             .json(AppErrorMessage {
                 code: self.status_code().as_u16(),
                 message: self.to_string(),
+                request_id: request_id::current().map(|current| current.id),
             })
     }
 }
 
 impl From<sqlx::Error> for ApiError {
-    fn from(error: sqlx::Error) -> Self {
-        match error {
-            _ => AppError::InternalError {
-                message: "Database Error".to_owned(),
-            }.into(),
+    fn from(_error: sqlx::Error) -> Self {
+        AppError::InternalError {
+            message: "Database Error".to_owned(),
         }
+        .into()
     }
 }
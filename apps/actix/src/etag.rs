@@ -0,0 +1,105 @@
+//! ETag module
+//!
+//! Versioned resources are returned with a strong `ETag` made of their version. Changes require
+//! the `If-Match` header (428 Precondition Required without it, 412 Precondition Failed if the
+//! resource has been modified since), and reads honor `If-None-Match` with 304 Not Modified.
+
+use actix_web::dev::Payload;
+use actix_web::http::header::{self, EntityTag, Header};
+use actix_web::{FromRequest, HttpRequest, HttpResponse};
+use futures::future::{ready, Ready};
+use northwind_core::concurrency::ExpectedVersion;
+use northwind_core::errors::AppError;
+use serde::Serialize;
+
+use crate::errors::ApiError;
+
+/// Returns the ETag of a resource version
+pub fn entity_tag(version: i32) -> EntityTag {
+    EntityTag::strong(version.to_string())
+}
+
+/// Versions a change may apply to, from the required `If-Match` header
+pub struct IfMatch(pub ExpectedVersion);
+
+impl FromRequest for IfMatch {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(parse_if_match(req).map(IfMatch))
+    }
+}
+
+fn parse_if_match(req: &HttpRequest) -> Result<ExpectedVersion, ApiError> {
+    if !req.headers().contains_key(header::IF_MATCH) {
+        return Err(AppError::PreconditionRequired {
+            message: String::from("If-Match header is required"),
+        }
+        .into());
+    }
+
+    match header::IfMatch::parse(req) {
+        Ok(header::IfMatch::Any) => Ok(ExpectedVersion::Any),
+        // Weak ETags never match, If-Match using the strong comparison
+        Ok(header::IfMatch::Items(tags)) => Ok(ExpectedVersion::OneOf(
+            tags.iter()
+                .filter(|tag| !tag.weak)
+                .filter_map(|tag| tag.tag().parse().ok())
+                .collect(),
+        )),
+        Err(_) => Err(AppError::BadRequest {
+            message: String::from("Invalid If-Match header"),
+        }
+        .into()),
+    }
+}
+
+/// Returns a resource with its ETag
+pub fn json_with_etag<T: Serialize>(version: i32, body: &T) -> HttpResponse {
+    HttpResponse::Ok().set(header::ETag(entity_tag(version))).json(body)
+}
+
+/// Returns a resource with its ETag, or 304 Not Modified if the client already has this version
+pub fn versioned_json<T: Serialize>(req: &HttpRequest, version: i32, body: &T) -> HttpResponse {
+    let etag = entity_tag(version);
+    let not_modified = match header::IfNoneMatch::parse(req) {
+        Ok(header::IfNoneMatch::Any) => true,
+        Ok(header::IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        Err(_) => false,
+    };
+
+    if not_modified {
+        HttpResponse::NotModified().set(header::ETag(etag)).finish()
+    } else {
+        json_with_etag(version, body)
+    }
+}
+
+#[test]
+fn test_conditional_requests() {
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+
+    let if_match = |value: Option<&str>| {
+        let req = match value {
+            Some(value) => TestRequest::default().header(header::IF_MATCH, value),
+            None => TestRequest::default(),
+        };
+        parse_if_match(&req.to_http_request()).map_err(|e| actix_web::ResponseError::status_code(&e))
+    };
+    assert_eq!(Err(StatusCode::PRECONDITION_REQUIRED), if_match(None));
+    assert_eq!(Ok(ExpectedVersion::Any), if_match(Some("*")));
+    assert_eq!(Ok(ExpectedVersion::OneOf(vec![2, 3])), if_match(Some(r#""2", W/"4", "3""#)));
+
+    let get = |value: &str| {
+        let req = TestRequest::default()
+            .header(header::IF_NONE_MATCH, value)
+            .to_http_request();
+        let res = versioned_json(&req, 3, &"user");
+        (res.status(), res.headers().get(header::ETAG).cloned())
+    };
+    assert_eq!((StatusCode::NOT_MODIFIED, Some(header::HeaderValue::from_static("\"3\""))), get(r#"W/"3""#));
+    assert_eq!(StatusCode::OK, get(r#""2""#).0);
+}
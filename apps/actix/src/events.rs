@@ -0,0 +1,284 @@
+//! Domain events module
+//!
+//! Postgres triggers send the domain events on the `northwind_events` channel once their
+//! transaction commits. Each instance listens to the channel and broadcasts the events to its
+//! streams, so that clients receive every event whichever instance they are connected to.
+//!
+//! The outbox records the same changes, but each of its events is relayed by a single
+//! instance, possibly long after the change when a sink fails: the streams could not be fed
+//! from it without every instance reading the whole outbox, so they keep their own channel.
+
+use actix_web::web::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, LocalBoxStream, StreamExt};
+use northwind_core::errors::AppError;
+use northwind_core::telemetry::query_span;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::Instrument;
+
+use crate::metrics::Metrics;
+
+/// Postgres channel of the domain events
+pub const CHANNEL: &str = "northwind_events";
+
+/// Types of the events sent by the triggers
+pub const EVENT_TYPES: [&str; 7] = [
+    "user.registered",
+    "user.updated",
+    "user.deleted",
+    "user.restored",
+    "order.placed",
+    "order.shipped",
+    "product.stock_low",
+];
+
+/// Delay before listening again after the connection to Postgres is lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Domain event, e.g. `user.registered` or `order.shipped`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub data: Value,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// Types of the events sent to a stream, all when empty
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventFilter {
+    /// Exact types (`order.shipped`) or aggregates (`user`)
+    types: Vec<String>,
+    /// Aggregates never matched, whatever the types
+    excluded: Vec<String>,
+}
+
+/// Whether an event type is a type or of an aggregate
+fn covers(type_or_aggregate: &str, event_type: &str) -> bool {
+    event_type == type_or_aggregate
+        || event_type
+            .strip_prefix(type_or_aggregate)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+impl EventFilter {
+    /// Parses a comma separated list of types or aggregates, `user.*` standing for `user`
+    pub fn parse(types: &str) -> Result<Self, AppError> {
+        let types = types
+            .split(',')
+            .map(|t| t.trim().trim_end_matches(".*"))
+            .filter(|t| !t.is_empty())
+            .map(|t| {
+                let known = EVENT_TYPES
+                    .iter()
+                    .any(|known| *known == t || known.split('.').next() == Some(t));
+                if known {
+                    Ok(t.to_owned())
+                } else {
+                    Err(AppError::BadRequest {
+                        message: format!("Unknown event type `{}`", t),
+                    })
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            types,
+            excluded: Vec::new(),
+        })
+    }
+
+    /// Whether events of an aggregate have been asked for by name
+    pub fn names(&self, aggregate: &str) -> bool {
+        self.types.iter().any(|t| covers(aggregate, t))
+    }
+
+    /// Never matches the events of an aggregate
+    pub fn exclude(mut self, aggregate: &str) -> Self {
+        self.excluded.push(aggregate.to_owned());
+        self
+    }
+
+    pub fn matches(&self, event_type: &str) -> bool {
+        !self.excluded.iter().any(|aggregate| covers(aggregate, event_type))
+            && (self.types.is_empty() || self.types.iter().any(|t| covers(t, event_type)))
+    }
+}
+
+/// Message broadcasted to the streams
+#[derive(Debug, Clone)]
+enum Message {
+    Event(Arc<Event>),
+    /// Keeps idle connections open through proxies, and lets closed ones be noticed
+    KeepAlive,
+}
+
+/// Broadcasts the events received by an instance to its streams
+#[derive(Clone)]
+pub struct EventBroadcaster {
+    sender: broadcast::Sender<Message>,
+}
+
+impl EventBroadcaster {
+    /// Streams lagging more than `capacity` messages behind miss the oldest ones
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: Event) {
+        // Fails only when no stream is open
+        let _ = self.sender.send(Message::Event(Arc::new(event)));
+    }
+
+    /// Server-sent events stream of the events matching a filter
+    pub fn subscribe(&self, filter: EventFilter) -> LocalBoxStream<'static, Result<Bytes, actix_web::Error>> {
+        let receiver = self.sender.subscribe();
+        let connected = stream::once(async { Ok(Bytes::from_static(b": connected\n\n")) });
+
+        let events = stream::unfold((receiver, filter), |(mut receiver, filter)| async move {
+            loop {
+                let frame = match receiver.recv().await {
+                    Ok(Message::Event(event)) if filter.matches(&event.event_type) => sse_frame(&event),
+                    Ok(Message::Event(_)) => continue,
+                    Ok(Message::KeepAlive) => String::from(": keep-alive\n\n"),
+                    Err(broadcast::error::RecvError::Lagged(count)) => format!(": {} events missed\n\n", count),
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                };
+                return Some((Ok(Bytes::from(frame)), (receiver, filter)));
+            }
+        });
+
+        connected.chain(events).boxed_local()
+    }
+
+    /// Periodically sends a comment to the streams
+    pub fn spawn_keep_alive(&self, interval: Duration) {
+        let sender = self.sender.clone();
+
+        actix_web::rt::spawn(async move {
+            loop {
+                actix_web::rt::time::delay_for(interval).await;
+                let _ = sender.send(Message::KeepAlive);
+            }
+        });
+    }
+}
+
+/// Formats an event as a server-sent event named after its type
+fn sse_frame(event: &Event) -> String {
+    // Compact JSON holds no line break
+    let data = serde_json::to_string(event).unwrap_or_default();
+    format!("event: {}\ndata: {}\n\n", event.event_type, data)
+}
+
+/// Listens to the domain events sent by Postgres and broadcasts them, listening again whenever
+/// the connection is lost
+///
+/// Placed orders are also counted in the metrics, along with their revenue: every instance
+/// receives every event, so each one reports the orders of all of them.
+pub fn spawn_listener(pool: PgPool, broadcaster: EventBroadcaster, metrics: Metrics) {
+    actix_web::rt::spawn(async move {
+        loop {
+            if let Err(e) = listen(&pool, &broadcaster, &metrics).await {
+                error!("Listening to the domain events failed: {}", e);
+            }
+
+            actix_web::rt::time::delay_for(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+async fn listen(pool: &PgPool, broadcaster: &EventBroadcaster, metrics: &Metrics) -> Result<(), sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(CHANNEL).await?;
+    info!("Listening to the domain events");
+
+    loop {
+        let notification = listener.recv().await?;
+        match serde_json::from_str::<Event>(notification.payload()) {
+            Ok(event) => {
+                if event.event_type == "order.placed" {
+                    record_order(pool, metrics, &event).await;
+                }
+                broadcaster.publish(event);
+            }
+            Err(e) => warn!("Invalid domain event `{}`: {}", notification.payload(), e),
+        }
+    }
+}
+
+/// Counts a placed order, its lines being committed along with it when the event is received
+async fn record_order(pool: &PgPool, metrics: &Metrics, event: &Event) {
+    let order_id = match event.data["order_id"].as_i64().and_then(|id| i16::try_from(id).ok()) {
+        Some(order_id) => order_id,
+        None => {
+            warn!("Order event without a valid order ID: {}", event.data);
+            return;
+        }
+    };
+
+    let revenue = sqlx::query_scalar!(
+        r#"
+            SELECT COALESCE(SUM(unit_price * quantity * (1 - discount)), 0)::float8 AS "revenue!"
+            FROM order_details
+            WHERE order_id = $1
+        "#,
+        order_id
+    )
+    .fetch_one(pool)
+    .instrument(query_span("SELECT", "order_details"))
+    .await;
+
+    match revenue {
+        Ok(revenue) => metrics.order_placed(revenue),
+        Err(e) => warn!("Revenue of order {} not recorded: {}", order_id, e),
+    }
+}
+
+#[test]
+fn test_event_stream() {
+    assert!(EventFilter::parse("users").is_err());
+    assert!(EventFilter::parse("order.cancelled").is_err());
+    let filter = EventFilter::parse("user.*, order.shipped").unwrap();
+    assert!(filter.matches("user.registered"));
+    assert!(filter.matches("order.shipped"));
+    assert!(!filter.matches("order.placed"));
+    assert!(EventFilter::parse("").unwrap().matches("product.stock_low"));
+    assert!(filter.names("user"));
+    assert!(EventFilter::parse("user.deleted").unwrap().names("user"));
+    assert!(!EventFilter::parse("").unwrap().names("user"));
+    let filter = EventFilter::parse("").unwrap().exclude("user");
+    assert!(!filter.matches("user.registered"));
+    assert!(filter.matches("order.placed"));
+
+    let event = |event_type: &str| Event {
+        event_type: event_type.to_owned(),
+        data: serde_json::json!({ "order_id": 10248 }),
+        occurred_at: "2026-10-19T08:00:00Z".parse().unwrap(),
+    };
+
+    futures::executor::block_on(async {
+        let broadcaster = EventBroadcaster::new(8);
+        let mut events = broadcaster.subscribe(EventFilter::parse("order").unwrap());
+        assert_eq!(": connected\n\n", events.next().await.unwrap().unwrap());
+
+        broadcaster.publish(event("user.registered"));
+        broadcaster.publish(event("order.shipped"));
+        assert_eq!(
+            "event: order.shipped\ndata: {\"type\":\"order.shipped\",\"data\":{\"order_id\":10248},\
+             \"occurred_at\":\"2026-10-19T08:00:00Z\"}\n\n",
+            events.next().await.unwrap().unwrap()
+        );
+
+        drop(broadcaster);
+        assert!(events.next().await.is_none());
+    });
+}
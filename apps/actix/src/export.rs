@@ -0,0 +1,518 @@
+//! Export module
+//!
+//! List and report endpoints answer with JSON, CSV or XLSX, chosen with the `format` query
+//! parameter (`json`, `csv` or `xlsx`) or else with the `Accept` header. Rows come from a
+//! database stream: CSV and JSON are sent as they are read, while a workbook is only readable
+//! once complete, so it is written row by row to a temporary file and sent at the end.
+
+use actix_web::dev::Payload;
+use actix_web::http::header::{self, Accept, Header};
+use actix_web::web::Bytes;
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use futures::future::{ready, Ready};
+use futures::stream::{self, BoxStream, LocalBoxStream, StreamExt};
+use northwind_core::errors::AppError;
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use uuid::Uuid;
+
+use crate::errors::ApiError;
+
+pub const CSV_CONTENT_TYPE: &str = "text/csv";
+pub const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Response format of a list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Xlsx,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct FormatQuery {
+    format: Option<String>,
+}
+
+impl FromRequest for ExportFormat {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(negotiate(req))
+    }
+}
+
+/// Reads the format from the `format` query parameter, else from the `Accept` header, JSON
+/// being the default
+fn negotiate(req: &HttpRequest) -> Result<ExportFormat, ApiError> {
+    let query = web::Query::<FormatQuery>::from_query(req.query_string())
+        .map_err(|e| -> ApiError { AppError::BadRequest { message: e.to_string() }.into() })?;
+
+    match query.format.as_deref() {
+        Some("json") => return Ok(ExportFormat::Json),
+        Some("csv") => return Ok(ExportFormat::Csv),
+        Some("xlsx") => return Ok(ExportFormat::Xlsx),
+        Some(format) => {
+            return Err(AppError::BadRequest {
+                message: format!("Unknown format {}, expected json, csv or xlsx", format),
+            }
+            .into())
+        }
+        None => {}
+    }
+
+    let mut accepted = match Accept::parse(req) {
+        Ok(accept) => accept.0,
+        Err(_) => return Ok(ExportFormat::Json),
+    };
+    accepted.sort_by_key(|item| std::cmp::Reverse(item.quality));
+
+    Ok(accepted
+        .iter()
+        .find_map(|item| match item.item.essence_str() {
+            CSV_CONTENT_TYPE => Some(ExportFormat::Csv),
+            XLSX_CONTENT_TYPE => Some(ExportFormat::Xlsx),
+            "application/json" | "application/*" | "*/*" => Some(ExportFormat::Json),
+            _ => None,
+        })
+        .unwrap_or(ExportFormat::Json))
+}
+
+/// First characters making a spreadsheet read a CSV cell as a formula
+const FORMULA_PREFIXES: &[char] = &['=', '+', '-', '@', '\t', '\r'];
+
+/// Value of a spreadsheet cell
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Text(String),
+    Integer(i64),
+    Number(f64),
+    Date(NaiveDate),
+    /// UTC date and time
+    DateTime(NaiveDateTime),
+}
+
+impl Cell {
+    fn to_csv(&self) -> String {
+        match self {
+            Cell::Empty => String::new(),
+            // Spreadsheets evaluate the text starting like a formula, unless it is quoted
+            Cell::Text(text) if text.starts_with(FORMULA_PREFIXES) => format!("'{}", text),
+            Cell::Text(text) => text.clone(),
+            Cell::Integer(number) => number.to_string(),
+            Cell::Number(number) => number.to_string(),
+            Cell::Date(date) => date.format(DATE_FORMAT).to_string(),
+            Cell::DateTime(date_time) => date_time.format(DATE_TIME_FORMAT).to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_csv())
+    }
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Cell::Text(value)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(value: &str) -> Self {
+        Cell::Text(value.to_owned())
+    }
+}
+
+impl From<Uuid> for Cell {
+    fn from(value: Uuid) -> Self {
+        Cell::Text(value.to_string())
+    }
+}
+
+impl From<serde_json::Value> for Cell {
+    fn from(value: serde_json::Value) -> Self {
+        Cell::Text(value.to_string())
+    }
+}
+
+impl From<i16> for Cell {
+    fn from(value: i16) -> Self {
+        Cell::Integer(value.into())
+    }
+}
+
+impl From<i64> for Cell {
+    fn from(value: i64) -> Self {
+        Cell::Integer(value)
+    }
+}
+
+impl From<f64> for Cell {
+    fn from(value: f64) -> Self {
+        Cell::Number(value)
+    }
+}
+
+impl From<NaiveDate> for Cell {
+    fn from(value: NaiveDate) -> Self {
+        Cell::Date(value)
+    }
+}
+
+impl From<NaiveDateTime> for Cell {
+    fn from(value: NaiveDateTime) -> Self {
+        Cell::DateTime(value)
+    }
+}
+
+impl<T: Into<Cell>> From<Option<T>> for Cell {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Cell::Empty, Into::into)
+    }
+}
+
+/// Row of an exported list
+pub trait Tabular: Serialize {
+    /// Column names, in the order of the cells
+    fn columns() -> &'static [&'static str];
+    fn cells(&self) -> Vec<Cell>;
+}
+
+/// Answers with the rows of a list in the requested format, as the `name-<date>` file for CSV
+/// and XLSX
+pub async fn respond<T>(
+    format: ExportFormat,
+    name: &str,
+    rows: BoxStream<'static, Result<T, AppError>>,
+) -> Result<HttpResponse, ApiError>
+where
+    T: Tabular + 'static,
+{
+    let filename = format!("{}-{}.{}", name, Utc::now().format(DATE_FORMAT), format.extension());
+    let disposition = format!("attachment; filename=\"{}\"", filename);
+
+    match format {
+        ExportFormat::Json => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .streaming(json_stream(rows))),
+        ExportFormat::Csv => Ok(HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .header(header::CONTENT_DISPOSITION, disposition)
+            .streaming(csv_stream(rows))),
+        ExportFormat::Xlsx => {
+            let workbook = xlsx_workbook(name, rows).await?;
+            Ok(HttpResponse::Ok()
+                .content_type(XLSX_CONTENT_TYPE)
+                .header(header::CONTENT_DISPOSITION, disposition)
+                .body(workbook))
+        }
+    }
+}
+
+/// Fails a response already started, which aborts its connection
+fn stream_error(error: AppError) -> actix_web::Error {
+    error!("Export failed: {}", error);
+    ApiError::from(error).into()
+}
+
+/// Streams the rows as a JSON array
+fn json_stream<T>(
+    rows: BoxStream<'static, Result<T, AppError>>,
+) -> LocalBoxStream<'static, Result<Bytes, actix_web::Error>>
+where
+    T: Tabular + 'static,
+{
+    let rows = rows.enumerate().map(|(index, row)| {
+        let row = row.map_err(stream_error)?;
+        let mut chunk = if index == 0 { Vec::new() } else { vec![b','] };
+        serde_json::to_writer(&mut chunk, &row)
+            .map_err(|e| stream_error(AppError::InternalError { message: e.to_string() }))?;
+        Ok(Bytes::from(chunk))
+    });
+
+    stream::once(ready(Ok(Bytes::from_static(b"["))))
+        .chain(rows)
+        .chain(stream::once(ready(Ok(Bytes::from_static(b"]")))))
+        .boxed_local()
+}
+
+/// Streams the rows as CSV, after a line of column names
+fn csv_stream<T>(
+    rows: BoxStream<'static, Result<T, AppError>>,
+) -> LocalBoxStream<'static, Result<Bytes, actix_web::Error>>
+where
+    T: Tabular + 'static,
+{
+    let header = csv_record(T::columns().iter().map(|column| String::from(*column)).collect());
+    let rows = rows.map(|row| {
+        let row = row.map_err(stream_error)?;
+        csv_record(row.cells().iter().map(Cell::to_csv).collect())
+    });
+
+    stream::once(ready(header)).chain(rows).boxed_local()
+}
+
+fn csv_record(record: Vec<String>) -> Result<Bytes, actix_web::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(&record)
+        .map_err(|e| stream_error(AppError::InternalError { message: e.to_string() }))?;
+    writer
+        .into_inner()
+        .map(Bytes::from)
+        .map_err(|e| stream_error(AppError::InternalError { message: e.to_string() }))
+}
+
+/// Builds a workbook of one worksheet, holding one row in memory at a time
+async fn xlsx_workbook<T>(name: &str, mut rows: BoxStream<'static, Result<T, AppError>>) -> Result<Vec<u8>, ApiError>
+where
+    T: Tabular + 'static,
+{
+    let header = Format::new().set_bold();
+    let date = Format::new().set_num_format("yyyy-mm-dd");
+    let date_time = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+    let number = Format::new().set_num_format("#,##0.00");
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet_with_constant_memory();
+    worksheet.set_name(name).map_err(xlsx_error)?;
+    worksheet.set_freeze_panes(1, 0).map_err(xlsx_error)?;
+    for (col, column) in T::columns().iter().enumerate() {
+        worksheet
+            .set_column_width(col as u16, column.len().max(12) as f64 + 2.0)
+            .map_err(xlsx_error)?;
+        worksheet
+            .write_string_with_format(0, col as u16, *column, &header)
+            .map_err(xlsx_error)?;
+    }
+
+    let mut row_num = 0;
+    while let Some(row) = rows.next().await {
+        let row = row?;
+        row_num += 1;
+        for (col, cell) in row.cells().into_iter().enumerate() {
+            let col = col as u16;
+            match cell {
+                Cell::Empty => continue,
+                Cell::Text(text) => worksheet.write_string(row_num, col, text),
+                Cell::Integer(value) => worksheet.write_number(row_num, col, value as f64),
+                Cell::Number(value) => worksheet.write_number_with_format(row_num, col, value, &number),
+                Cell::Date(value) => worksheet.write_number_with_format(
+                    row_num,
+                    col,
+                    excel_serial(value.and_time(NaiveTime::MIN)),
+                    &date,
+                ),
+                Cell::DateTime(value) => {
+                    worksheet.write_number_with_format(row_num, col, excel_serial(value), &date_time)
+                }
+            }
+            .map_err(xlsx_error)?;
+        }
+    }
+
+    // Zipping the workbook takes a while for large exports
+    web::block(move || workbook.save_to_buffer())
+        .await
+        .map_err(|e| -> ApiError {
+            AppError::InternalError {
+                message: format!("Workbook creation failed: {}", e),
+            }
+            .into()
+        })
+}
+
+fn xlsx_error(error: XlsxError) -> ApiError {
+    match error {
+        XlsxError::RowColumnLimitError => AppError::UnprocessableEntity {
+            message: String::from("Too many rows for a worksheet, export them as CSV"),
+        },
+        e => AppError::InternalError {
+            message: format!("Workbook creation failed: {}", e),
+        },
+    }
+    .into()
+}
+
+/// Excel date, i.e. the number of days since 1899-12-30
+fn excel_serial(date_time: NaiveDateTime) -> f64 {
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30).unwrap().and_time(NaiveTime::MIN);
+    (date_time - epoch).num_milliseconds() as f64 / 86_400_000.0
+}
+
+#[test]
+fn test_negotiate() {
+    use actix_web::test::TestRequest;
+
+    let format = |req: TestRequest| negotiate(&req.to_http_request()).ok();
+
+    assert_eq!(Some(ExportFormat::Json), format(TestRequest::get().uri("/v1/users")));
+    assert_eq!(
+        Some(ExportFormat::Csv),
+        format(TestRequest::get().uri("/v1/users?format=csv"))
+    );
+    assert_eq!(
+        Some(ExportFormat::Xlsx),
+        format(TestRequest::get().uri("/v1/audit?limit=5&format=xlsx"))
+    );
+    assert_eq!(None, format(TestRequest::get().uri("/v1/users?format=pdf")));
+    assert_eq!(
+        Some(ExportFormat::Csv),
+        format(TestRequest::get().header(header::ACCEPT, "text/csv"))
+    );
+    // The query parameter wins, then the most preferred type
+    assert_eq!(
+        Some(ExportFormat::Json),
+        format(
+            TestRequest::get()
+                .uri("/v1/users?format=json")
+                .header(header::ACCEPT, "text/csv")
+        )
+    );
+    assert_eq!(
+        Some(ExportFormat::Xlsx),
+        format(TestRequest::get().header(header::ACCEPT, format!("text/csv;q=0.5, {}", XLSX_CONTENT_TYPE)))
+    );
+    assert_eq!(
+        Some(ExportFormat::Json),
+        format(TestRequest::get().header(header::ACCEPT, "text/html, */*;q=0.8"))
+    );
+}
+
+#[test]
+fn test_export() {
+    #[derive(Serialize)]
+    struct Row {
+        name: String,
+        quantity: i64,
+        shipped_at: Option<NaiveDateTime>,
+    }
+
+    impl Tabular for Row {
+        fn columns() -> &'static [&'static str] {
+            &["Name", "Quantity", "Shipped at"]
+        }
+
+        fn cells(&self) -> Vec<Cell> {
+            vec![self.name.as_str().into(), self.quantity.into(), self.shipped_at.into()]
+        }
+    }
+
+    fn rows() -> BoxStream<'static, Result<Row, AppError>> {
+        stream::iter(vec![
+            Ok(Row {
+                name: String::from("Chai, 10 boxes"),
+                quantity: 12,
+                shipped_at: Some(
+                    NaiveDate::from_ymd_opt(1998, 5, 6)
+                        .unwrap()
+                        .and_hms_opt(14, 30, 0)
+                        .unwrap(),
+                ),
+            }),
+            Ok(Row {
+                name: String::from("Tofu \"extra firm\""),
+                quantity: 3,
+                shipped_at: None,
+            }),
+        ])
+        .boxed()
+    }
+
+    async fn body(response: &mut HttpResponse) -> Bytes {
+        actix_web::test::load_stream(response.take_body()).await.unwrap()
+    }
+
+    actix_web::rt::System::new("test").block_on(async move {
+        let mut csv = respond(ExportFormat::Csv, "orders", rows()).await.unwrap();
+        let disposition = csv
+            .headers()
+            .get(header::CONTENT_DISPOSITION)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(disposition.starts_with("attachment; filename=\"orders-"));
+        assert!(disposition.ends_with(".csv\""));
+        assert_eq!(
+            "Name,Quantity,Shipped at\n\"Chai, 10 boxes\",12,1998-05-06 14:30:00\n\"Tofu \"\"extra firm\"\"\",3,\n",
+            body(&mut csv).await
+        );
+
+        let mut json = respond(ExportFormat::Json, "orders", rows()).await.unwrap();
+        assert_eq!(
+            serde_json::json!([
+                { "name": "Chai, 10 boxes", "quantity": 12, "shipped_at": "1998-05-06T14:30:00" },
+                { "name": "Tofu \"extra firm\"", "quantity": 3, "shipped_at": null },
+            ]),
+            serde_json::from_slice::<serde_json::Value>(&body(&mut json).await).unwrap()
+        );
+
+        let mut xlsx = respond(ExportFormat::Xlsx, "orders", rows()).await.unwrap();
+        assert_eq!(XLSX_CONTENT_TYPE, xlsx.headers().get(header::CONTENT_TYPE).unwrap());
+        assert!(body(&mut xlsx).await.starts_with(b"PK"));
+
+        let failing = stream::iter(vec![Err(AppError::InternalError {
+            message: String::from("Connection lost"),
+        })])
+        .boxed();
+        assert!(respond::<Row>(ExportFormat::Xlsx, "orders", failing).await.is_err());
+    });
+}
+
+#[test]
+fn test_csv_formula() {
+    assert_eq!(
+        "'=HYPERLINK(\"http://example.com\")",
+        Cell::from("=HYPERLINK(\"http://example.com\")").to_csv()
+    );
+    assert_eq!("'+33 1 23 45 67 89", Cell::from("+33 1 23 45 67 89").to_csv());
+    assert_eq!("'-2+3", Cell::from("-2+3").to_csv());
+    assert_eq!("'@SUM(A1)", Cell::from("@SUM(A1)").to_csv());
+    assert_eq!("'\tcmd", Cell::from("\tcmd").to_csv());
+    assert_eq!("'\rcmd", Cell::from("\rcmd").to_csv());
+    assert_eq!("Chai = tea", Cell::from("Chai = tea").to_csv());
+    assert_eq!("-3", Cell::Integer(-3).to_csv());
+    assert_eq!("-0.5", Cell::Number(-0.5).to_csv());
+}
+
+#[test]
+fn test_excel_serial() {
+    assert_eq!(
+        35_921.0,
+        excel_serial(
+            NaiveDate::from_ymd_opt(1998, 5, 6)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        )
+    );
+    assert_eq!(
+        35_921.75,
+        excel_serial(
+            NaiveDate::from_ymd_opt(1998, 5, 6)
+                .unwrap()
+                .and_hms_opt(18, 0, 0)
+                .unwrap()
+        )
+    );
+}
@@ -0,0 +1,461 @@
+//! GraphQL data loaders module
+//!
+//! Relations are loaded in batches: the keys requested while resolving a level of a query are
+//! loaded together with a single query per relation, instead of a query per parent object.
+
+use async_graphql::dataloader::Loader;
+use async_trait::async_trait;
+use northwind_core::errors::AppError;
+use northwind_traders::domain::catalog::{Category, Product, Supplier};
+use northwind_traders::domain::catalog_repository::CatalogRepository;
+use northwind_traders::domain::customer::{Customer, CustomerDemographic};
+use northwind_traders::domain::customer_repository::CustomerRepository;
+use northwind_traders::domain::employee::{Employee, Region, Territory};
+use northwind_traders::domain::employee_repository::EmployeeRepository;
+use northwind_traders::domain::order::{Order, OrderDetail, Shipper};
+use northwind_traders::domain::order_repository::OrderRepository;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+
+/// Errors are shared by all the keys of a batch
+pub type LoaderError = Arc<AppError>;
+
+/// Indexes rows by their key
+fn index<K: Hash + Eq, V>(rows: Vec<V>, key: impl Fn(&V) -> K) -> HashMap<K, V> {
+    rows.into_iter().map(|row| (key(&row), row)).collect()
+}
+
+/// Groups rows by the key of their parent
+fn group<K: Hash + Eq, V>(rows: impl IntoIterator<Item = (K, V)>) -> HashMap<K, Vec<V>> {
+    let mut groups: HashMap<K, Vec<V>> = HashMap::new();
+    for (key, row) in rows {
+        groups.entry(key).or_default().push(row);
+    }
+    groups
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CategoryId(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SupplierId(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProductId(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProductsOfCategory(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProductsOfSupplier(pub i16);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CustomerId(pub String);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DemographicsOfCustomer(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OrderId(pub i16);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OrdersOfCustomer(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OrdersOfEmployee(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OrdersOfShipper(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DetailsOfOrder(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DetailsOfProduct(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShipperId(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EmployeeId(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReportsOfEmployee(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TerritoriesOfEmployee(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TerritoriesOfRegion(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RegionId(pub i16);
+
+pub struct CatalogLoader {
+    pub repo: Arc<dyn CatalogRepository>,
+}
+
+#[async_trait]
+impl Loader<CategoryId> for CatalogLoader {
+    type Value = Category;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[CategoryId]) -> Result<HashMap<CategoryId, Category>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_categories_by_ids(&ids).await?;
+        Ok(index(rows, |row| CategoryId(row.category_id)))
+    }
+}
+
+#[async_trait]
+impl Loader<SupplierId> for CatalogLoader {
+    type Value = Supplier;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[SupplierId]) -> Result<HashMap<SupplierId, Supplier>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_suppliers_by_ids(&ids).await?;
+        Ok(index(rows, |row| SupplierId(row.supplier_id)))
+    }
+}
+
+#[async_trait]
+impl Loader<ProductId> for CatalogLoader {
+    type Value = Product;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[ProductId]) -> Result<HashMap<ProductId, Product>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_products_by_ids(&ids).await?;
+        Ok(index(rows, |row| ProductId(row.product_id)))
+    }
+}
+
+#[async_trait]
+impl Loader<ProductsOfCategory> for CatalogLoader {
+    type Value = Vec<Product>;
+    type Error = LoaderError;
+
+    async fn load(
+        &self,
+        keys: &[ProductsOfCategory],
+    ) -> Result<HashMap<ProductsOfCategory, Vec<Product>>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_products_by_category_ids(&ids).await?;
+        Ok(group(rows.into_iter().filter_map(|row| {
+            row.category_id.map(|id| (ProductsOfCategory(id), row))
+        })))
+    }
+}
+
+#[async_trait]
+impl Loader<ProductsOfSupplier> for CatalogLoader {
+    type Value = Vec<Product>;
+    type Error = LoaderError;
+
+    async fn load(
+        &self,
+        keys: &[ProductsOfSupplier],
+    ) -> Result<HashMap<ProductsOfSupplier, Vec<Product>>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_products_by_supplier_ids(&ids).await?;
+        Ok(group(rows.into_iter().filter_map(|row| {
+            row.supplier_id.map(|id| (ProductsOfSupplier(id), row))
+        })))
+    }
+}
+
+pub struct CustomerLoader {
+    pub repo: Arc<dyn CustomerRepository>,
+}
+
+#[async_trait]
+impl Loader<CustomerId> for CustomerLoader {
+    type Value = Customer;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[CustomerId]) -> Result<HashMap<CustomerId, Customer>, LoaderError> {
+        let ids: Vec<String> = keys.iter().map(|key| key.0.clone()).collect();
+        let rows = self.repo.get_customers_by_ids(&ids).await?;
+        Ok(index(rows, |row| CustomerId(row.customer_id.clone())))
+    }
+}
+
+#[async_trait]
+impl Loader<DemographicsOfCustomer> for CustomerLoader {
+    type Value = Vec<CustomerDemographic>;
+    type Error = LoaderError;
+
+    async fn load(
+        &self,
+        keys: &[DemographicsOfCustomer],
+    ) -> Result<HashMap<DemographicsOfCustomer, Vec<CustomerDemographic>>, LoaderError> {
+        let ids: Vec<String> = keys.iter().map(|key| key.0.clone()).collect();
+        let rows = self.repo.get_demographics_by_customer_ids(&ids).await?;
+        Ok(group(
+            rows.into_iter().map(|(id, row)| (DemographicsOfCustomer(id), row)),
+        ))
+    }
+}
+
+pub struct OrderLoader {
+    pub repo: Arc<dyn OrderRepository>,
+}
+
+#[async_trait]
+impl Loader<OrderId> for OrderLoader {
+    type Value = Order;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[OrderId]) -> Result<HashMap<OrderId, Order>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_orders_by_ids(&ids).await?;
+        Ok(index(rows, |row| OrderId(row.order_id)))
+    }
+}
+
+#[async_trait]
+impl Loader<OrdersOfCustomer> for OrderLoader {
+    type Value = Vec<Order>;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[OrdersOfCustomer]) -> Result<HashMap<OrdersOfCustomer, Vec<Order>>, LoaderError> {
+        let ids: Vec<String> = keys.iter().map(|key| key.0.clone()).collect();
+        let rows = self.repo.get_orders_by_customer_ids(&ids).await?;
+        Ok(group(rows.into_iter().filter_map(|row| {
+            row.customer_id.clone().map(|id| (OrdersOfCustomer(id), row))
+        })))
+    }
+}
+
+#[async_trait]
+impl Loader<OrdersOfEmployee> for OrderLoader {
+    type Value = Vec<Order>;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[OrdersOfEmployee]) -> Result<HashMap<OrdersOfEmployee, Vec<Order>>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_orders_by_employee_ids(&ids).await?;
+        Ok(group(rows.into_iter().filter_map(|row| {
+            row.employee_id.map(|id| (OrdersOfEmployee(id), row))
+        })))
+    }
+}
+
+#[async_trait]
+impl Loader<OrdersOfShipper> for OrderLoader {
+    type Value = Vec<Order>;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[OrdersOfShipper]) -> Result<HashMap<OrdersOfShipper, Vec<Order>>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_orders_by_shipper_ids(&ids).await?;
+        Ok(group(
+            rows.into_iter()
+                .filter_map(|row| row.ship_via.map(|id| (OrdersOfShipper(id), row))),
+        ))
+    }
+}
+
+#[async_trait]
+impl Loader<DetailsOfOrder> for OrderLoader {
+    type Value = Vec<OrderDetail>;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[DetailsOfOrder]) -> Result<HashMap<DetailsOfOrder, Vec<OrderDetail>>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_details_by_order_ids(&ids).await?;
+        Ok(group(rows.into_iter().map(|row| (DetailsOfOrder(row.order_id), row))))
+    }
+}
+
+#[async_trait]
+impl Loader<DetailsOfProduct> for OrderLoader {
+    type Value = Vec<OrderDetail>;
+    type Error = LoaderError;
+
+    async fn load(
+        &self,
+        keys: &[DetailsOfProduct],
+    ) -> Result<HashMap<DetailsOfProduct, Vec<OrderDetail>>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_details_by_product_ids(&ids).await?;
+        Ok(group(
+            rows.into_iter().map(|row| (DetailsOfProduct(row.product_id), row)),
+        ))
+    }
+}
+
+#[async_trait]
+impl Loader<ShipperId> for OrderLoader {
+    type Value = Shipper;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[ShipperId]) -> Result<HashMap<ShipperId, Shipper>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_shippers_by_ids(&ids).await?;
+        Ok(index(rows, |row| ShipperId(row.shipper_id)))
+    }
+}
+
+pub struct EmployeeLoader {
+    pub repo: Arc<dyn EmployeeRepository>,
+}
+
+#[async_trait]
+impl Loader<EmployeeId> for EmployeeLoader {
+    type Value = Employee;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[EmployeeId]) -> Result<HashMap<EmployeeId, Employee>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_employees_by_ids(&ids).await?;
+        Ok(index(rows, |row| EmployeeId(row.employee_id)))
+    }
+}
+
+#[async_trait]
+impl Loader<ReportsOfEmployee> for EmployeeLoader {
+    type Value = Vec<Employee>;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[ReportsOfEmployee]) -> Result<HashMap<ReportsOfEmployee, Vec<Employee>>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_employees_by_manager_ids(&ids).await?;
+        Ok(group(rows.into_iter().filter_map(|row| {
+            row.reports_to.map(|id| (ReportsOfEmployee(id), row))
+        })))
+    }
+}
+
+#[async_trait]
+impl Loader<TerritoriesOfEmployee> for EmployeeLoader {
+    type Value = Vec<Territory>;
+    type Error = LoaderError;
+
+    async fn load(
+        &self,
+        keys: &[TerritoriesOfEmployee],
+    ) -> Result<HashMap<TerritoriesOfEmployee, Vec<Territory>>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_territories_by_employee_ids(&ids).await?;
+        Ok(group(
+            rows.into_iter().map(|(id, row)| (TerritoriesOfEmployee(id), row)),
+        ))
+    }
+}
+
+#[async_trait]
+impl Loader<TerritoriesOfRegion> for EmployeeLoader {
+    type Value = Vec<Territory>;
+    type Error = LoaderError;
+
+    async fn load(
+        &self,
+        keys: &[TerritoriesOfRegion],
+    ) -> Result<HashMap<TerritoriesOfRegion, Vec<Territory>>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_territories_by_region_ids(&ids).await?;
+        Ok(group(
+            rows.into_iter().map(|row| (TerritoriesOfRegion(row.region_id), row)),
+        ))
+    }
+}
+
+#[async_trait]
+impl Loader<RegionId> for EmployeeLoader {
+    type Value = Region;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[RegionId]) -> Result<HashMap<RegionId, Region>, LoaderError> {
+        let ids: Vec<i16> = keys.iter().map(|key| key.0).collect();
+        let rows = self.repo.get_regions_by_ids(&ids).await?;
+        Ok(index(rows, |row| RegionId(row.region_id)))
+    }
+}
+
+#[test]
+fn test_loaders_batch_keys() {
+    use async_graphql::dataloader::DataLoader;
+    use northwind_core::audit::AuditContext;
+    use northwind_core::import::BatchError;
+    use northwind_traders::domain::Page;
+    use std::sync::Mutex;
+
+    /// Catalog of 3 products in 2 categories, recording the IDs of each query, the other
+    /// queries returning nothing
+    #[derive(Default)]
+    struct Catalog {
+        queries: Mutex<Vec<Vec<i16>>>,
+    }
+
+    #[async_trait]
+    impl CatalogRepository for Catalog {
+        async fn get_categories(&self, _page: Page) -> Result<Vec<Category>, AppError> {
+            Ok(Default::default())
+        }
+        async fn get_categories_by_ids(&self, _ids: &[i16]) -> Result<Vec<Category>, AppError> {
+            Ok(Default::default())
+        }
+        async fn get_suppliers(&self, _page: Page) -> Result<Vec<Supplier>, AppError> {
+            Ok(Default::default())
+        }
+        async fn get_suppliers_by_ids(&self, _ids: &[i16]) -> Result<Vec<Supplier>, AppError> {
+            Ok(Default::default())
+        }
+        async fn get_products(&self, _page: Page) -> Result<Vec<Product>, AppError> {
+            Ok(Default::default())
+        }
+        async fn get_products_by_ids(&self, _ids: &[i16]) -> Result<Vec<Product>, AppError> {
+            Ok(Default::default())
+        }
+        async fn get_products_by_category_ids(&self, category_ids: &[i16]) -> Result<Vec<Product>, AppError> {
+            let mut ids = category_ids.to_vec();
+            ids.sort_unstable();
+            self.queries.lock().unwrap().push(ids);
+
+            let product = |product_id, category_id| Product {
+                product_id,
+                product_name: format!("Product {}", product_id),
+                supplier_id: None,
+                category_id: Some(category_id),
+                quantity_per_unit: None,
+                unit_price: None,
+                units_in_stock: None,
+                units_on_order: None,
+                reorder_level: None,
+                discontinued: 0,
+            };
+            Ok(vec![product(1, 1), product(2, 2), product(3, 1)]
+                .into_iter()
+                .filter(|p| p.category_id.is_some_and(|id| category_ids.contains(&id)))
+                .collect())
+        }
+        async fn get_products_by_supplier_ids(&self, _supplier_ids: &[i16]) -> Result<Vec<Product>, AppError> {
+            Ok(Default::default())
+        }
+        async fn import_products(&self, _products: &[Product], _audit: &AuditContext) -> Result<u64, BatchError> {
+            Ok(Default::default())
+        }
+    }
+
+    let repo = Arc::new(Catalog::default());
+    let loader = DataLoader::new(CatalogLoader { repo: repo.clone() });
+
+    actix_web::rt::System::new("test").block_on(async move {
+        // Keys requested together are loaded by a single query
+        let (first, second, third) = futures::join!(
+            loader.load_one(ProductsOfCategory(1)),
+            loader.load_one(ProductsOfCategory(2)),
+            loader.load_one(ProductsOfCategory(3)),
+        );
+        let ids = |products: Option<Vec<Product>>| -> Vec<i16> {
+            products.unwrap_or_default().iter().map(|p| p.product_id).collect()
+        };
+        assert_eq!(vec![1, 3], ids(first.unwrap()));
+        assert_eq!(vec![2], ids(second.unwrap()));
+        assert!(third.unwrap().is_none());
+        assert_eq!(vec![vec![1, 2, 3]], *repo.queries.lock().unwrap());
+    });
+}
@@ -0,0 +1,55 @@
+//! GraphQL schema module
+//!
+//! Users and the Northwind tables are exposed read-only on `/graphql`, behind the `Authentication`
+//! middleware. The authenticated user is added to the data of each request.
+
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{EmptyMutation, EmptySubscription, Schema};
+use northwind_traders::domain::catalog_repository::CatalogRepository;
+use northwind_traders::domain::customer_repository::CustomerRepository;
+use northwind_traders::domain::employee_repository::EmployeeRepository;
+use northwind_traders::domain::order_repository::OrderRepository;
+use northwind_user::domain::user_repository::UserRepository;
+use std::sync::Arc;
+
+use crate::config::GraphqlConfig;
+
+pub mod loaders;
+pub mod query;
+pub mod types;
+
+pub type NorthwindSchema = Schema<query::QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Repositories the schema reads from
+#[derive(Clone)]
+pub struct Repositories {
+    pub users: Arc<dyn UserRepository>,
+    pub catalog: Arc<dyn CatalogRepository>,
+    pub customers: Arc<dyn CustomerRepository>,
+    pub orders: Arc<dyn OrderRepository>,
+    pub employees: Arc<dyn EmployeeRepository>,
+}
+
+pub fn schema(config: &GraphqlConfig, repositories: Repositories) -> NorthwindSchema {
+    Schema::build(query::QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(config.max_depth)
+        .limit_complexity(config.max_complexity)
+        .data(DataLoader::new(loaders::CatalogLoader {
+            repo: repositories.catalog.clone(),
+        }))
+        .data(DataLoader::new(loaders::CustomerLoader {
+            repo: repositories.customers.clone(),
+        }))
+        .data(DataLoader::new(loaders::OrderLoader {
+            repo: repositories.orders.clone(),
+        }))
+        .data(DataLoader::new(loaders::EmployeeLoader {
+            repo: repositories.employees.clone(),
+        }))
+        .data(repositories.users)
+        .data(repositories.catalog)
+        .data(repositories.customers)
+        .data(repositories.orders)
+        .data(repositories.employees)
+        .finish()
+}
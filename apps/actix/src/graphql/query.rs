@@ -0,0 +1,155 @@
+//! GraphQL query root module
+
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{Context, Object, Result};
+use northwind_core::errors::AppError;
+use northwind_traders::domain::catalog_repository::CatalogRepository;
+use northwind_traders::domain::customer_repository::CustomerRepository;
+use northwind_traders::domain::employee_repository::EmployeeRepository;
+use northwind_traders::domain::order_repository::OrderRepository;
+use northwind_traders::domain::Page;
+use northwind_user::domain::user_repository::UserRepository;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::graphql::loaders::*;
+use crate::graphql::types::*;
+use crate::middlewares::auth::AuthenticatedUser;
+
+pub struct QueryRoot;
+
+/// Builds a page from the `limit` and `offset` arguments of a list
+fn page(limit: Option<i32>, offset: Option<i32>) -> Page {
+    Page::new(limit.map(i64::from), offset.map(i64::from))
+}
+
+#[Object]
+impl QueryRoot {
+    /// Authenticated user
+    async fn me(&self, ctx: &Context<'_>) -> Result<Option<User>> {
+        let user = ctx.data_opt::<AuthenticatedUser>().ok_or(AppError::Unauthorized)?;
+        let repo = ctx.data_unchecked::<Arc<dyn UserRepository>>();
+        Ok(repo.get_by_id(user.id).await?.map(User))
+    }
+
+    async fn users(&self, ctx: &Context<'_>) -> Result<Vec<User>> {
+        let repo = ctx.data_unchecked::<Arc<dyn UserRepository>>();
+        Ok(repo.get_all().await?.into_iter().map(User).collect())
+    }
+
+    async fn user(&self, ctx: &Context<'_>, id: Uuid) -> Result<Option<User>> {
+        let repo = ctx.data_unchecked::<Arc<dyn UserRepository>>();
+        Ok(repo.get_by_id(id).await?.map(User))
+    }
+
+    async fn categories(&self, ctx: &Context<'_>, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<Category>> {
+        let repo = ctx.data_unchecked::<Arc<dyn CatalogRepository>>();
+        let categories = repo.get_categories(page(limit, offset)).await?;
+        Ok(categories.into_iter().map(Category).collect())
+    }
+
+    async fn category(&self, ctx: &Context<'_>, id: i16) -> Result<Option<Category>> {
+        let loader = ctx.data_unchecked::<DataLoader<CatalogLoader>>();
+        Ok(loader.load_one(CategoryId(id)).await?.map(Category))
+    }
+
+    async fn suppliers(&self, ctx: &Context<'_>, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<Supplier>> {
+        let repo = ctx.data_unchecked::<Arc<dyn CatalogRepository>>();
+        let suppliers = repo.get_suppliers(page(limit, offset)).await?;
+        Ok(suppliers.into_iter().map(Supplier).collect())
+    }
+
+    async fn supplier(&self, ctx: &Context<'_>, id: i16) -> Result<Option<Supplier>> {
+        let loader = ctx.data_unchecked::<DataLoader<CatalogLoader>>();
+        Ok(loader.load_one(SupplierId(id)).await?.map(Supplier))
+    }
+
+    async fn products(&self, ctx: &Context<'_>, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<Product>> {
+        let repo = ctx.data_unchecked::<Arc<dyn CatalogRepository>>();
+        let products = repo.get_products(page(limit, offset)).await?;
+        Ok(products.into_iter().map(Product).collect())
+    }
+
+    async fn product(&self, ctx: &Context<'_>, id: i16) -> Result<Option<Product>> {
+        let loader = ctx.data_unchecked::<DataLoader<CatalogLoader>>();
+        Ok(loader.load_one(ProductId(id)).await?.map(Product))
+    }
+
+    async fn customers(&self, ctx: &Context<'_>, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<Customer>> {
+        let repo = ctx.data_unchecked::<Arc<dyn CustomerRepository>>();
+        let customers = repo.get_customers(page(limit, offset)).await?;
+        Ok(customers.into_iter().map(Customer).collect())
+    }
+
+    async fn customer(&self, ctx: &Context<'_>, id: String) -> Result<Option<Customer>> {
+        let loader = ctx.data_unchecked::<DataLoader<CustomerLoader>>();
+        Ok(loader.load_one(CustomerId(id)).await?.map(Customer))
+    }
+
+    async fn customer_demographics(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> Result<Vec<CustomerDemographic>> {
+        let repo = ctx.data_unchecked::<Arc<dyn CustomerRepository>>();
+        let demographics = repo.get_demographics(page(limit, offset)).await?;
+        Ok(demographics.into_iter().map(CustomerDemographic).collect())
+    }
+
+    async fn orders(&self, ctx: &Context<'_>, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<Order>> {
+        let repo = ctx.data_unchecked::<Arc<dyn OrderRepository>>();
+        let orders = repo.get_orders(page(limit, offset)).await?;
+        Ok(orders.into_iter().map(Order).collect())
+    }
+
+    async fn order(&self, ctx: &Context<'_>, id: i16) -> Result<Option<Order>> {
+        let loader = ctx.data_unchecked::<DataLoader<OrderLoader>>();
+        Ok(loader.load_one(OrderId(id)).await?.map(Order))
+    }
+
+    async fn shippers(&self, ctx: &Context<'_>, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<Shipper>> {
+        let repo = ctx.data_unchecked::<Arc<dyn OrderRepository>>();
+        let shippers = repo.get_shippers(page(limit, offset)).await?;
+        Ok(shippers.into_iter().map(Shipper).collect())
+    }
+
+    async fn shipper(&self, ctx: &Context<'_>, id: i16) -> Result<Option<Shipper>> {
+        let loader = ctx.data_unchecked::<DataLoader<OrderLoader>>();
+        Ok(loader.load_one(ShipperId(id)).await?.map(Shipper))
+    }
+
+    async fn employees(&self, ctx: &Context<'_>, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<Employee>> {
+        let repo = ctx.data_unchecked::<Arc<dyn EmployeeRepository>>();
+        let employees = repo.get_employees(page(limit, offset)).await?;
+        Ok(employees.into_iter().map(Employee).collect())
+    }
+
+    async fn employee(&self, ctx: &Context<'_>, id: i16) -> Result<Option<Employee>> {
+        let loader = ctx.data_unchecked::<DataLoader<EmployeeLoader>>();
+        Ok(loader.load_one(EmployeeId(id)).await?.map(Employee))
+    }
+
+    async fn territories(&self, ctx: &Context<'_>, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<Territory>> {
+        let repo = ctx.data_unchecked::<Arc<dyn EmployeeRepository>>();
+        let territories = repo.get_territories(page(limit, offset)).await?;
+        Ok(territories.into_iter().map(Territory).collect())
+    }
+
+    async fn regions(&self, ctx: &Context<'_>, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<Region>> {
+        let repo = ctx.data_unchecked::<Arc<dyn EmployeeRepository>>();
+        let regions = repo.get_regions(page(limit, offset)).await?;
+        Ok(regions.into_iter().map(Region).collect())
+    }
+
+    async fn region(&self, ctx: &Context<'_>, id: i16) -> Result<Option<Region>> {
+        let loader = ctx.data_unchecked::<DataLoader<EmployeeLoader>>();
+        Ok(loader.load_one(RegionId(id)).await?.map(Region))
+    }
+
+    async fn us_states(&self, ctx: &Context<'_>, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<UsState>> {
+        let repo = ctx.data_unchecked::<Arc<dyn EmployeeRepository>>();
+        let states = repo.get_us_states(page(limit, offset)).await?;
+        Ok(states.into_iter().map(UsState).collect())
+    }
+}
@@ -0,0 +1,549 @@
+//! GraphQL object types module
+//!
+//! Objects wrap the domain models, their relations being loaded through the data loaders.
+
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{Context, Object, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use northwind_traders::domain::{catalog, customer, employee, order};
+use uuid::Uuid;
+
+use crate::graphql::loaders::*;
+
+fn catalog<'a>(ctx: &'a Context<'_>) -> &'a DataLoader<CatalogLoader> {
+    ctx.data_unchecked()
+}
+
+fn customers<'a>(ctx: &'a Context<'_>) -> &'a DataLoader<CustomerLoader> {
+    ctx.data_unchecked()
+}
+
+fn orders<'a>(ctx: &'a Context<'_>) -> &'a DataLoader<OrderLoader> {
+    ctx.data_unchecked()
+}
+
+fn employees<'a>(ctx: &'a Context<'_>) -> &'a DataLoader<EmployeeLoader> {
+    ctx.data_unchecked()
+}
+
+pub struct User(pub northwind_user::domain::user::User);
+
+#[Object]
+impl User {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn lastname(&self) -> &str {
+        &self.0.lastname
+    }
+
+    async fn firstname(&self) -> &str {
+        &self.0.firstname
+    }
+
+    async fn email(&self) -> &str {
+        &self.0.email
+    }
+
+    async fn role(&self) -> &str {
+        &self.0.role
+    }
+
+    async fn created_at(&self) -> NaiveDateTime {
+        self.0.created_at
+    }
+
+    async fn updated_at(&self) -> NaiveDateTime {
+        self.0.updated_at
+    }
+}
+
+pub struct Category(pub catalog::Category);
+
+#[Object]
+impl Category {
+    async fn category_id(&self) -> i16 {
+        self.0.category_id
+    }
+
+    async fn category_name(&self) -> &str {
+        &self.0.category_name
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn products(&self, ctx: &Context<'_>) -> Result<Vec<Product>> {
+        let products = catalog(ctx).load_one(ProductsOfCategory(self.0.category_id)).await?;
+        Ok(products.unwrap_or_default().into_iter().map(Product).collect())
+    }
+}
+
+pub struct Supplier(pub catalog::Supplier);
+
+#[Object]
+impl Supplier {
+    async fn supplier_id(&self) -> i16 {
+        self.0.supplier_id
+    }
+
+    async fn company_name(&self) -> &str {
+        &self.0.company_name
+    }
+
+    async fn contact_name(&self) -> Option<&str> {
+        self.0.contact_name.as_deref()
+    }
+
+    async fn contact_title(&self) -> Option<&str> {
+        self.0.contact_title.as_deref()
+    }
+
+    async fn address(&self) -> Option<&str> {
+        self.0.address.as_deref()
+    }
+
+    async fn city(&self) -> Option<&str> {
+        self.0.city.as_deref()
+    }
+
+    async fn region(&self) -> Option<&str> {
+        self.0.region.as_deref()
+    }
+
+    async fn postal_code(&self) -> Option<&str> {
+        self.0.postal_code.as_deref()
+    }
+
+    async fn country(&self) -> Option<&str> {
+        self.0.country.as_deref()
+    }
+
+    async fn phone(&self) -> Option<&str> {
+        self.0.phone.as_deref()
+    }
+
+    async fn fax(&self) -> Option<&str> {
+        self.0.fax.as_deref()
+    }
+
+    async fn homepage(&self) -> Option<&str> {
+        self.0.homepage.as_deref()
+    }
+
+    async fn products(&self, ctx: &Context<'_>) -> Result<Vec<Product>> {
+        let products = catalog(ctx).load_one(ProductsOfSupplier(self.0.supplier_id)).await?;
+        Ok(products.unwrap_or_default().into_iter().map(Product).collect())
+    }
+}
+
+pub struct Product(pub catalog::Product);
+
+#[Object]
+impl Product {
+    async fn product_id(&self) -> i16 {
+        self.0.product_id
+    }
+
+    async fn product_name(&self) -> &str {
+        &self.0.product_name
+    }
+
+    async fn quantity_per_unit(&self) -> Option<&str> {
+        self.0.quantity_per_unit.as_deref()
+    }
+
+    async fn unit_price(&self) -> Option<f32> {
+        self.0.unit_price
+    }
+
+    async fn units_in_stock(&self) -> Option<i16> {
+        self.0.units_in_stock
+    }
+
+    async fn units_on_order(&self) -> Option<i16> {
+        self.0.units_on_order
+    }
+
+    async fn reorder_level(&self) -> Option<i16> {
+        self.0.reorder_level
+    }
+
+    async fn discontinued(&self) -> bool {
+        self.0.discontinued != 0
+    }
+
+    async fn category(&self, ctx: &Context<'_>) -> Result<Option<Category>> {
+        match self.0.category_id {
+            Some(id) => Ok(catalog(ctx).load_one(CategoryId(id)).await?.map(Category)),
+            None => Ok(None),
+        }
+    }
+
+    async fn supplier(&self, ctx: &Context<'_>) -> Result<Option<Supplier>> {
+        match self.0.supplier_id {
+            Some(id) => Ok(catalog(ctx).load_one(SupplierId(id)).await?.map(Supplier)),
+            None => Ok(None),
+        }
+    }
+
+    async fn order_details(&self, ctx: &Context<'_>) -> Result<Vec<OrderDetail>> {
+        let details = orders(ctx).load_one(DetailsOfProduct(self.0.product_id)).await?;
+        Ok(details.unwrap_or_default().into_iter().map(OrderDetail).collect())
+    }
+}
+
+pub struct Customer(pub customer::Customer);
+
+#[Object]
+impl Customer {
+    async fn customer_id(&self) -> &str {
+        &self.0.customer_id
+    }
+
+    async fn company_name(&self) -> &str {
+        &self.0.company_name
+    }
+
+    async fn contact_name(&self) -> Option<&str> {
+        self.0.contact_name.as_deref()
+    }
+
+    async fn contact_title(&self) -> Option<&str> {
+        self.0.contact_title.as_deref()
+    }
+
+    async fn address(&self) -> Option<&str> {
+        self.0.address.as_deref()
+    }
+
+    async fn city(&self) -> Option<&str> {
+        self.0.city.as_deref()
+    }
+
+    async fn region(&self) -> Option<&str> {
+        self.0.region.as_deref()
+    }
+
+    async fn postal_code(&self) -> Option<&str> {
+        self.0.postal_code.as_deref()
+    }
+
+    async fn country(&self) -> Option<&str> {
+        self.0.country.as_deref()
+    }
+
+    async fn phone(&self) -> Option<&str> {
+        self.0.phone.as_deref()
+    }
+
+    async fn fax(&self) -> Option<&str> {
+        self.0.fax.as_deref()
+    }
+
+    async fn demographics(&self, ctx: &Context<'_>) -> Result<Vec<CustomerDemographic>> {
+        let demographics = customers(ctx)
+            .load_one(DemographicsOfCustomer(self.0.customer_id.clone()))
+            .await?;
+        Ok(demographics
+            .unwrap_or_default()
+            .into_iter()
+            .map(CustomerDemographic)
+            .collect())
+    }
+
+    async fn orders(&self, ctx: &Context<'_>) -> Result<Vec<Order>> {
+        let orders = orders(ctx)
+            .load_one(OrdersOfCustomer(self.0.customer_id.clone()))
+            .await?;
+        Ok(orders.unwrap_or_default().into_iter().map(Order).collect())
+    }
+}
+
+pub struct CustomerDemographic(pub customer::CustomerDemographic);
+
+#[Object]
+impl CustomerDemographic {
+    async fn customer_type_id(&self) -> &str {
+        &self.0.customer_type_id
+    }
+
+    async fn customer_desc(&self) -> Option<&str> {
+        self.0.customer_desc.as_deref()
+    }
+}
+
+pub struct Order(pub order::Order);
+
+#[Object]
+impl Order {
+    async fn order_id(&self) -> i16 {
+        self.0.order_id
+    }
+
+    async fn order_date(&self) -> Option<NaiveDate> {
+        self.0.order_date
+    }
+
+    async fn required_date(&self) -> Option<NaiveDate> {
+        self.0.required_date
+    }
+
+    async fn shipped_date(&self) -> Option<NaiveDate> {
+        self.0.shipped_date
+    }
+
+    async fn freight(&self) -> Option<f32> {
+        self.0.freight
+    }
+
+    async fn ship_name(&self) -> Option<&str> {
+        self.0.ship_name.as_deref()
+    }
+
+    async fn ship_address(&self) -> Option<&str> {
+        self.0.ship_address.as_deref()
+    }
+
+    async fn ship_city(&self) -> Option<&str> {
+        self.0.ship_city.as_deref()
+    }
+
+    async fn ship_region(&self) -> Option<&str> {
+        self.0.ship_region.as_deref()
+    }
+
+    async fn ship_postal_code(&self) -> Option<&str> {
+        self.0.ship_postal_code.as_deref()
+    }
+
+    async fn ship_country(&self) -> Option<&str> {
+        self.0.ship_country.as_deref()
+    }
+
+    async fn customer(&self, ctx: &Context<'_>) -> Result<Option<Customer>> {
+        match &self.0.customer_id {
+            Some(id) => Ok(customers(ctx).load_one(CustomerId(id.clone())).await?.map(Customer)),
+            None => Ok(None),
+        }
+    }
+
+    async fn employee(&self, ctx: &Context<'_>) -> Result<Option<Employee>> {
+        match self.0.employee_id {
+            Some(id) => Ok(employees(ctx).load_one(EmployeeId(id)).await?.map(Employee)),
+            None => Ok(None),
+        }
+    }
+
+    async fn shipper(&self, ctx: &Context<'_>) -> Result<Option<Shipper>> {
+        match self.0.ship_via {
+            Some(id) => Ok(orders(ctx).load_one(ShipperId(id)).await?.map(Shipper)),
+            None => Ok(None),
+        }
+    }
+
+    async fn details(&self, ctx: &Context<'_>) -> Result<Vec<OrderDetail>> {
+        let details = orders(ctx).load_one(DetailsOfOrder(self.0.order_id)).await?;
+        Ok(details.unwrap_or_default().into_iter().map(OrderDetail).collect())
+    }
+}
+
+pub struct OrderDetail(pub order::OrderDetail);
+
+#[Object]
+impl OrderDetail {
+    async fn unit_price(&self) -> f32 {
+        self.0.unit_price
+    }
+
+    async fn quantity(&self) -> i16 {
+        self.0.quantity
+    }
+
+    async fn discount(&self) -> f32 {
+        self.0.discount
+    }
+
+    async fn order(&self, ctx: &Context<'_>) -> Result<Option<Order>> {
+        Ok(orders(ctx).load_one(OrderId(self.0.order_id)).await?.map(Order))
+    }
+
+    async fn product(&self, ctx: &Context<'_>) -> Result<Option<Product>> {
+        Ok(catalog(ctx).load_one(ProductId(self.0.product_id)).await?.map(Product))
+    }
+}
+
+pub struct Shipper(pub order::Shipper);
+
+#[Object]
+impl Shipper {
+    async fn shipper_id(&self) -> i16 {
+        self.0.shipper_id
+    }
+
+    async fn company_name(&self) -> &str {
+        &self.0.company_name
+    }
+
+    async fn phone(&self) -> Option<&str> {
+        self.0.phone.as_deref()
+    }
+
+    async fn orders(&self, ctx: &Context<'_>) -> Result<Vec<Order>> {
+        let orders = orders(ctx).load_one(OrdersOfShipper(self.0.shipper_id)).await?;
+        Ok(orders.unwrap_or_default().into_iter().map(Order).collect())
+    }
+}
+
+pub struct Employee(pub employee::Employee);
+
+#[Object]
+impl Employee {
+    async fn employee_id(&self) -> i16 {
+        self.0.employee_id
+    }
+
+    async fn last_name(&self) -> &str {
+        &self.0.last_name
+    }
+
+    async fn first_name(&self) -> &str {
+        &self.0.first_name
+    }
+
+    async fn title(&self) -> Option<&str> {
+        self.0.title.as_deref()
+    }
+
+    async fn title_of_courtesy(&self) -> Option<&str> {
+        self.0.title_of_courtesy.as_deref()
+    }
+
+    async fn birth_date(&self) -> Option<NaiveDate> {
+        self.0.birth_date
+    }
+
+    async fn hire_date(&self) -> Option<NaiveDate> {
+        self.0.hire_date
+    }
+
+    async fn address(&self) -> Option<&str> {
+        self.0.address.as_deref()
+    }
+
+    async fn city(&self) -> Option<&str> {
+        self.0.city.as_deref()
+    }
+
+    async fn region(&self) -> Option<&str> {
+        self.0.region.as_deref()
+    }
+
+    async fn postal_code(&self) -> Option<&str> {
+        self.0.postal_code.as_deref()
+    }
+
+    async fn country(&self) -> Option<&str> {
+        self.0.country.as_deref()
+    }
+
+    async fn home_phone(&self) -> Option<&str> {
+        self.0.home_phone.as_deref()
+    }
+
+    async fn extension(&self) -> Option<&str> {
+        self.0.extension.as_deref()
+    }
+
+    async fn notes(&self) -> Option<&str> {
+        self.0.notes.as_deref()
+    }
+
+    async fn photo_path(&self) -> Option<&str> {
+        self.0.photo_path.as_deref()
+    }
+
+    /// Manager of the employee
+    async fn reports_to(&self, ctx: &Context<'_>) -> Result<Option<Employee>> {
+        match self.0.reports_to {
+            Some(id) => Ok(employees(ctx).load_one(EmployeeId(id)).await?.map(Employee)),
+            None => Ok(None),
+        }
+    }
+
+    /// Employees reporting to the employee
+    async fn reports(&self, ctx: &Context<'_>) -> Result<Vec<Employee>> {
+        let reports = employees(ctx).load_one(ReportsOfEmployee(self.0.employee_id)).await?;
+        Ok(reports.unwrap_or_default().into_iter().map(Employee).collect())
+    }
+
+    async fn territories(&self, ctx: &Context<'_>) -> Result<Vec<Territory>> {
+        let territories = employees(ctx)
+            .load_one(TerritoriesOfEmployee(self.0.employee_id))
+            .await?;
+        Ok(territories.unwrap_or_default().into_iter().map(Territory).collect())
+    }
+
+    async fn orders(&self, ctx: &Context<'_>) -> Result<Vec<Order>> {
+        let orders = orders(ctx).load_one(OrdersOfEmployee(self.0.employee_id)).await?;
+        Ok(orders.unwrap_or_default().into_iter().map(Order).collect())
+    }
+}
+
+pub struct Territory(pub employee::Territory);
+
+#[Object]
+impl Territory {
+    async fn territory_id(&self) -> &str {
+        &self.0.territory_id
+    }
+
+    async fn territory_description(&self) -> &str {
+        &self.0.territory_description
+    }
+
+    async fn region(&self, ctx: &Context<'_>) -> Result<Option<Region>> {
+        Ok(employees(ctx).load_one(RegionId(self.0.region_id)).await?.map(Region))
+    }
+}
+
+pub struct Region(pub employee::Region);
+
+#[Object]
+impl Region {
+    async fn region_id(&self) -> i16 {
+        self.0.region_id
+    }
+
+    async fn region_description(&self) -> &str {
+        &self.0.region_description
+    }
+
+    async fn territories(&self, ctx: &Context<'_>) -> Result<Vec<Territory>> {
+        let territories = employees(ctx).load_one(TerritoriesOfRegion(self.0.region_id)).await?;
+        Ok(territories.unwrap_or_default().into_iter().map(Territory).collect())
+    }
+}
+
+pub struct UsState(pub employee::UsState);
+
+#[Object]
+impl UsState {
+    async fn state_id(&self) -> i16 {
+        self.0.state_id
+    }
+
+    async fn state_name(&self) -> Option<&str> {
+        self.0.state_name.as_deref()
+    }
+
+    async fn state_abbr(&self) -> Option<&str> {
+        self.0.state_abbr.as_deref()
+    }
+
+    async fn state_region(&self) -> Option<&str> {
+        self.0.state_region.as_deref()
+    }
+}
@@ -0,0 +1,112 @@
+//! gRPC server module
+//!
+//! The gRPC services listen on `grpc.address`, apart from the HTTP API. Like the telemetry
+//! exporter, they run on their own Tokio runtime, actix-web running on an older one.
+//! `grpc.health.v1.Health` reports the status of each service.
+//!
+//! When `server.tls` is enabled, the services are served over TLS with the certificate of the
+//! HTTP server, read at startup. Logins and registrations are rate limited like over HTTP.
+
+use color_eyre::Result;
+use eyre::WrapErr;
+use northwind_core::errors::AppError;
+use std::net::SocketAddr;
+use tonic::transport::{Identity, Server, ServerTlsConfig};
+use tonic::Status;
+use tonic_health::ServingStatus;
+
+pub mod rate_limit;
+pub mod users;
+
+/// Messages and services generated from `proto/user.proto`
+pub mod proto {
+    tonic::include_proto!("northwind.user.v1");
+}
+
+use crate::config::TlsConfig;
+use proto::user_service_server::UserServiceServer;
+use rate_limit::RateLimitLayer;
+
+/// gRPC server, stopped when dropped
+pub struct GrpcServer {
+    pub local_addr: SocketAddr,
+    _runtime: tokio::runtime::Runtime,
+}
+
+/// Binds the gRPC server to an address and starts serving the services, over TLS if enabled
+pub fn serve(
+    address: &str,
+    tls: &TlsConfig,
+    rate_limit: RateLimitLayer,
+    users: users::UserGrpcService,
+) -> Result<GrpcServer> {
+    let mut server = Server::builder().layer(rate_limit);
+    if tls.enabled {
+        let cert = std::fs::read(&tls.cert_path).with_context(|| format!("reading {}", tls.cert_path))?;
+        let key = std::fs::read(&tls.key_path).with_context(|| format!("reading {}", tls.key_path))?;
+        server = server
+            .tls_config(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
+            .context("configuring the gRPC server TLS")?;
+    } else if !address.starts_with("127.") && !address.starts_with("localhost:") && !address.starts_with("[::1]:") {
+        warn!("gRPC server listening on {} without TLS", address);
+    }
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_name("grpc")
+        .enable_all()
+        .build()
+        .context("starting the gRPC runtime")?;
+
+    // Bound here to fail at startup if the address is not available
+    let listener =
+        std::net::TcpListener::bind(address).with_context(|| format!("binding the gRPC server to {}", address))?;
+    listener.set_nonblocking(true)?;
+    let local_addr = listener.local_addr()?;
+    let listener = {
+        let _guard = runtime.enter();
+        tokio::net::TcpListener::from_std(listener)?
+    };
+
+    runtime.spawn(async move {
+        let (mut reporter, health) = tonic_health::server::health_reporter();
+        reporter
+            .set_serving::<UserServiceServer<users::UserGrpcService>>()
+            .await;
+        reporter.set_service_status("", ServingStatus::Serving).await;
+
+        let result = server
+            .add_service(health)
+            .add_service(UserServiceServer::new(users))
+            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+            .await;
+        if let Err(e) = result {
+            error!("gRPC server failed: {}", e);
+        }
+    });
+    info!("gRPC server listening on {}", local_addr);
+
+    Ok(GrpcServer {
+        local_addr,
+        _runtime: runtime,
+    })
+}
+
+/// Maps an application error to the gRPC status of the same meaning
+pub fn status(error: AppError) -> Status {
+    let message = error.name();
+    match error {
+        AppError::InternalError { .. } => Status::internal(message),
+        AppError::BadRequest { .. } | AppError::UnprocessableEntity { .. } | AppError::UnsupportedMediaType { .. } => {
+            Status::invalid_argument(message)
+        }
+        AppError::NotFound { .. } => Status::not_found(message),
+        AppError::Unauthorized => Status::unauthenticated(message),
+        AppError::Forbidden => Status::permission_denied(message),
+        AppError::Conflict { .. } => Status::already_exists(message),
+        // Concurrent modification, to be retried from a fresh read
+        AppError::PreconditionFailed { .. } => Status::aborted(message),
+        AppError::PreconditionRequired { .. } => Status::failed_precondition(message),
+        AppError::TooManyRequests => Status::resource_exhausted(message),
+    }
+}
@@ -0,0 +1,127 @@
+//! gRPC rate limiting module
+//!
+//! Calls of the methods of `METHOD_POLICIES` count against the `rate_limit.policies` of the
+//! same name, under the client IP key of the HTTP requests: logins and registrations have the
+//! same quotas whichever API they go through. tonic interceptors being synchronous, the
+//! counters store is queried by a layer in front of the services.
+
+use chrono::Utc;
+use futures::future::BoxFuture;
+use northwind_core::errors::AppError;
+use northwind_core::rate_limit::{Quota, RateLimitStore};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::codegen::http::{Request, Response};
+use tonic::transport::server::{TcpConnectInfo, TlsConnectInfo};
+use tonic::transport::Body;
+use tower::{Layer, Service};
+
+use crate::config::RateLimitConfig;
+use crate::grpc::status;
+
+/// Rate limited methods, with the name of their policy
+const METHOD_POLICIES: [(&str, &str); 2] = [
+    ("/northwind.user.v1.UserService/Login", "login"),
+    ("/northwind.user.v1.UserService/Register", "register"),
+];
+
+/// Layer limiting the calls of the rate limited methods
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    store: Arc<dyn RateLimitStore>,
+    /// Policy name and quota, by method path
+    policies: Arc<HashMap<&'static str, (String, Quota)>>,
+}
+
+impl RateLimitLayer {
+    pub fn new(config: &RateLimitConfig, store: Arc<dyn RateLimitStore>) -> Self {
+        let policies = if config.enabled {
+            METHOD_POLICIES
+                .iter()
+                .filter_map(|(method, name)| {
+                    let policy = config.policies.iter().find(|policy| policy.name == *name)?;
+                    let quota = Quota {
+                        limit: policy.limit,
+                        period: policy.period as i64 * 1000,
+                    };
+                    Some((*method, (policy.name.clone(), quota)))
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        Self {
+            store,
+            policies: Arc::new(policies),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: RateLimitLayer,
+}
+
+/// Peer address of a call, over TLS or not
+fn remote_addr(req: &Request<Body>) -> Option<SocketAddr> {
+    req.extensions()
+        .get::<TcpConnectInfo>()
+        .and_then(|info| info.remote_addr())
+        .or_else(|| {
+            req.extensions()
+                .get::<TlsConnectInfo<TcpConnectInfo>>()
+                .and_then(|info| info.get_ref().remote_addr())
+        })
+}
+
+impl<S> Service<Request<Body>> for RateLimitService<S>
+where
+    S: Service<Request<Body>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        // The service polled ready handles the call, its clone the next ones
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let policy = self.limiter.policies.get(req.uri().path()).cloned();
+        let store = self.limiter.store.clone();
+
+        Box::pin(async move {
+            if let Some((name, quota)) = policy {
+                let ip = remote_addr(&req).map(|addr| addr.ip().to_string()).unwrap_or_default();
+                let key = format!("{}:ip:{}", name, ip);
+                match store.acquire(&key, quota, Utc::now().timestamp_millis()).await {
+                    Ok(decision) if !decision.allowed => return Ok(status(AppError::TooManyRequests).to_http()),
+                    Ok(_) => (),
+                    // Calls are let through if the counters store fails, like HTTP requests
+                    Err(e) => error!("Rate limiting of {} failed: {}", key, e),
+                }
+            }
+            inner.call(req).await
+        })
+    }
+}
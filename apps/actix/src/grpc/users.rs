@@ -0,0 +1,568 @@
+//! gRPC users service module
+
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use northwind_core::audit::AuditContext;
+use northwind_core::concurrency::ExpectedVersion;
+use northwind_core::errors::{invalid_fields, AppError};
+use northwind_user::domain::jwt_processor::JwtProcessor;
+use northwind_user::domain::user::{UpdateUserModel, User, UserCreation};
+use northwind_user::domain::user_repository::UserRepository;
+use northwind_user::AppState;
+use std::sync::Arc;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::grpc::proto::{self, user_service_server::UserService};
+use crate::grpc::status;
+use crate::metrics::{jwt_failure_reason, Metrics};
+use crate::middlewares::auth::AuthenticatedUser;
+use crate::middlewares::request_id::RequestIdService;
+
+/// `UserService` backed by the repository and JWT processor of the HTTP API
+pub struct UserGrpcService {
+    pub user_repo: Arc<dyn UserRepository>,
+    pub jwt_processor: Arc<dyn JwtProcessor>,
+    pub app_state: AppState,
+    pub metrics: Metrics,
+    /// Reads the ID sent by the caller in the metadata, like the HTTP header
+    pub request_id: RequestIdService,
+}
+
+impl UserGrpcService {
+    /// Authenticates a call from its `authorization: Bearer <token>` metadata, like the
+    /// `Authentication` middleware
+    async fn authenticate(&self, metadata: &MetadataMap) -> Result<AuthenticatedUser, Status> {
+        let unauthenticated = || status(AppError::Unauthorized);
+        let token = metadata
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer"))
+            .map(|token| token.trim().to_owned());
+        let token = match token {
+            Some(token) => token,
+            None => {
+                self.metrics.jwt_validation_failed("missing");
+                return Err(unauthenticated());
+            }
+        };
+
+        let user_id = match self.jwt_processor.parse_any(token, &self.app_state.jwt_keys()) {
+            Ok(claims) => claims.user_id,
+            Err(e) => {
+                self.metrics.jwt_validation_failed(jwt_failure_reason(e.as_ref()));
+                return Err(unauthenticated());
+            }
+        };
+        tracing::Span::current().record("user_id", tracing::field::display(user_id));
+
+        // Check if user is still valid
+        match self.user_repo.get_by_id(user_id).await.map_err(status)? {
+            Some(user) => Ok(AuthenticatedUser {
+                id: user.id,
+                role: user.role,
+            }),
+            None => {
+                self.metrics.jwt_validation_failed("unknown_user");
+                Err(unauthenticated())
+            }
+        }
+    }
+
+    /// Returns the audit context of a call, keeping the request ID sent by the caller if valid
+    fn audit<T>(&self, request: &Request<T>, actor_id: Option<Uuid>) -> AuditContext {
+        let metadata = |key: &str| request.metadata().get(key).and_then(|value| value.to_str().ok());
+
+        AuditContext {
+            actor_id,
+            ip: request.remote_addr().map(|addr| addr.ip().to_string()),
+            user_agent: metadata("user-agent").map(String::from),
+            request_id: Some(self.request_id.resolve(metadata(self.request_id.header().as_str()))),
+            traceparent: crate::telemetry::current_traceparent(),
+        }
+    }
+}
+
+fn parse_id(id: &str) -> Result<Uuid, AppError> {
+    Uuid::parse_str(id).map_err(|_| AppError::BadRequest {
+        message: String::from("Invalid user ID"),
+    })
+}
+
+/// Version a user must have to be changed, required like the `If-Match` header
+fn expected_version(version: i32) -> Result<ExpectedVersion, AppError> {
+    if version > 0 {
+        Ok(ExpectedVersion::OneOf(vec![version]))
+    } else {
+        Err(AppError::PreconditionRequired {
+            message: String::from("The version of the user is required"),
+        })
+    }
+}
+
+fn timestamp(date: NaiveDateTime) -> prost_types::Timestamp {
+    let date = Utc.from_utc_datetime(&date);
+    prost_types::Timestamp {
+        seconds: date.timestamp(),
+        nanos: date.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn not_found() -> Status {
+    status(AppError::NotFound {
+        message: String::from("No user found"),
+    })
+}
+
+impl From<User> for proto::User {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id.to_string(),
+            lastname: user.lastname,
+            firstname: user.firstname,
+            email: user.email,
+            role: user.role,
+            created_at: Some(timestamp(user.created_at)),
+            updated_at: Some(timestamp(user.updated_at)),
+            version: user.version,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl UserService for UserGrpcService {
+    #[tracing::instrument(name = "UserService/Login", skip(self, request))]
+    async fn login(&self, request: Request<proto::LoginRequest>) -> Result<Response<proto::LoginResponse>, Status> {
+        let audit = self.audit(&request, None);
+        let model = request.into_inner();
+        let user = self
+            .user_repo
+            .login(model.email, model.password, &audit)
+            .await
+            .map_err(|e| {
+                self.metrics.login_failed("error");
+                status(e)
+            })?;
+
+        let user = match user {
+            Some(user) => user,
+            None => {
+                self.metrics.login_failed("invalid_credentials");
+                return Err(status(AppError::Unauthorized));
+            }
+        };
+        let token = self
+            .jwt_processor
+            .generate(
+                user.id,
+                user.lastname.clone(),
+                user.firstname.clone(),
+                user.email.clone(),
+                self.app_state.jwt_secret_key.clone(),
+                self.app_state.jwt_lifetime,
+            )
+            .map_err(|_| ());
+        let (token, expires_at) = match token {
+            Ok(token) => token,
+            Err(_) => {
+                self.metrics.login_failed("token_error");
+                return Err(status(AppError::Unauthorized));
+            }
+        };
+        self.metrics.login_succeeded();
+
+        Ok(Response::new(proto::LoginResponse {
+            user: Some(user.into()),
+            token,
+            expires_at: Some(prost_types::Timestamp {
+                seconds: expires_at,
+                nanos: 0,
+            }),
+        }))
+    }
+
+    #[tracing::instrument(name = "UserService/Register", skip(self, request))]
+    async fn register(&self, request: Request<proto::RegisterRequest>) -> Result<Response<proto::User>, Status> {
+        let audit = self.audit(&request, None);
+        let model = request.into_inner();
+        let creation = UserCreation {
+            lastname: model.lastname,
+            firstname: model.firstname,
+            email: model.email,
+            password: model.password,
+        };
+        creation
+            .validate()
+            .map_err(|e| Status::invalid_argument(format!("Invalid user: invalid {}", invalid_fields(&e))))?;
+
+        let mut user = User::new(creation);
+        match self.user_repo.create(&mut user, &audit).await {
+            Ok(_) => {
+                self.metrics.user_registered();
+                Ok(Response::new(user.into()))
+            }
+            Err(e @ AppError::Conflict { .. }) => Err(status(e)),
+            _ => Err(status(AppError::InternalError {
+                message: String::from("Error during user creation"),
+            })),
+        }
+    }
+
+    #[tracing::instrument(name = "UserService/Get", skip(self, request), fields(user_id))]
+    async fn get(&self, request: Request<proto::GetUserRequest>) -> Result<Response<proto::User>, Status> {
+        self.authenticate(request.metadata()).await?;
+        let id = parse_id(&request.get_ref().id).map_err(status)?;
+
+        let user = self.user_repo.get_by_id(id).await.map_err(status)?;
+        user.map(|user| Response::new(user.into())).ok_or_else(not_found)
+    }
+
+    #[tracing::instrument(name = "UserService/List", skip(self, request), fields(user_id))]
+    async fn list(
+        &self,
+        request: Request<proto::ListUsersRequest>,
+    ) -> Result<Response<proto::ListUsersResponse>, Status> {
+        self.authenticate(request.metadata()).await?;
+
+        let users = self.user_repo.get_all().await.map_err(status)?;
+        Ok(Response::new(proto::ListUsersResponse {
+            users: users.into_iter().map(proto::User::from).collect(),
+        }))
+    }
+
+    #[tracing::instrument(name = "UserService/Update", skip(self, request), fields(user_id))]
+    async fn update(&self, request: Request<proto::UpdateUserRequest>) -> Result<Response<proto::User>, Status> {
+        let user = self.authenticate(request.metadata()).await?;
+        let audit = self.audit(&request, Some(user.id));
+        let model = request.into_inner();
+        let id = parse_id(&model.id).map_err(status)?;
+        let expected = expected_version(model.version).map_err(status)?;
+        let update = UpdateUserModel {
+            lastname: model.lastname,
+            firstname: model.firstname,
+        };
+        update
+            .validate()
+            .map_err(|e| Status::invalid_argument(format!("Invalid user: invalid {}", invalid_fields(&e))))?;
+
+        self.user_repo
+            .update(id, update.firstname, update.lastname, &expected, &audit)
+            .await
+            .map_err(status)?;
+
+        let user = self.user_repo.get_by_id(id).await.map_err(status)?;
+        user.map(|user| Response::new(user.into())).ok_or_else(not_found)
+    }
+
+    #[tracing::instrument(name = "UserService/Delete", skip(self, request), fields(user_id))]
+    async fn delete(
+        &self,
+        request: Request<proto::DeleteUserRequest>,
+    ) -> Result<Response<proto::DeleteUserResponse>, Status> {
+        let user = self.authenticate(request.metadata()).await?;
+        let audit = self.audit(&request, Some(user.id));
+        let model = request.into_inner();
+        let id = parse_id(&model.id).map_err(status)?;
+        let expected = expected_version(model.version).map_err(status)?;
+
+        match self.user_repo.delete(id, &expected, &audit).await {
+            Ok(Some(1)) => Ok(Response::new(proto::DeleteUserResponse {})),
+            Ok(_) => Err(not_found()),
+            Err(e @ AppError::PreconditionFailed { .. }) => Err(status(e)),
+            _ => Err(status(AppError::InternalError {
+                message: String::from("Error during user deletion"),
+            })),
+        }
+    }
+}
+
+#[test]
+fn test_user_service() {
+    use async_trait::async_trait;
+    use chrono::NaiveDateTime;
+    use futures::stream::BoxStream;
+    use northwind_core::import::BatchError;
+    use northwind_user::services::jwt_processor_impl::JwtProcessorImpl;
+    use std::sync::Mutex;
+    use tonic::transport::{Certificate, Channel, ClientTlsConfig};
+    use tonic::Code;
+    use tonic_health::proto::health_check_response::ServingStatus;
+    use tonic_health::proto::health_client::HealthClient;
+    use tonic_health::proto::HealthCheckRequest;
+
+    use crate::config::{RateLimitConfig, RateLimitPolicy, RequestIdConfig, TlsConfig};
+    use crate::grpc::proto::user_service_client::UserServiceClient;
+    use crate::grpc::rate_limit::RateLimitLayer;
+    use northwind_core::rate_limit::MemoryRateLimitStore;
+
+    /// Users kept in memory, passwords in clear, the queries not used by the service returning
+    /// nothing
+    #[derive(Default)]
+    struct Users(Mutex<Vec<User>>);
+
+    #[async_trait]
+    impl UserRepository for Users {
+        async fn login(&self, email: String, password: String, _: &AuditContext) -> Result<Option<User>, AppError> {
+            let users = self.0.lock().unwrap();
+            let user = users.iter().find(|u| u.email == email && u.password == password);
+            Ok(user.map(|u| {
+                User::init(
+                    u.id,
+                    u.lastname.clone(),
+                    u.firstname.clone(),
+                    u.email.clone(),
+                    String::new(),
+                    u.role.clone(),
+                    u.created_at,
+                    u.updated_at,
+                    None,
+                    u.version,
+                )
+            }))
+        }
+        async fn create(&self, user: &mut User, _: &AuditContext) -> Result<Option<u64>, AppError> {
+            self.0.lock().unwrap().push(User::init(
+                user.id,
+                user.lastname.clone(),
+                user.firstname.clone(),
+                user.email.clone(),
+                user.password.clone(),
+                user.role.clone(),
+                user.created_at,
+                user.updated_at,
+                None,
+                user.version,
+            ));
+            Ok(Some(1))
+        }
+        async fn import(&self, _: &mut [User], _: &AuditContext) -> Result<u64, BatchError> {
+            Ok(Default::default())
+        }
+        async fn get_all(&self) -> Result<Vec<User>, AppError> {
+            let ids: Vec<Uuid> = self.0.lock().unwrap().iter().map(|u| u.id).collect();
+            let mut users = Vec::new();
+            for id in ids {
+                users.extend(self.get_by_id(id).await?);
+            }
+            Ok(users)
+        }
+        async fn get_by_id(&self, id: Uuid) -> Result<Option<User>, AppError> {
+            let users = self.0.lock().unwrap();
+            Ok(users.iter().find(|u| u.id == id).map(|u| {
+                User::init(
+                    u.id,
+                    u.lastname.clone(),
+                    u.firstname.clone(),
+                    u.email.clone(),
+                    String::new(),
+                    u.role.clone(),
+                    u.created_at,
+                    u.updated_at,
+                    None,
+                    u.version,
+                )
+            }))
+        }
+        async fn get_by_email(&self, _: &str) -> Result<Option<User>, AppError> {
+            Ok(Default::default())
+        }
+        async fn get_by_emails(&self, _: &[String]) -> Result<Vec<User>, AppError> {
+            Ok(Default::default())
+        }
+        async fn delete(
+            &self,
+            id: Uuid,
+            expected: &ExpectedVersion,
+            _: &AuditContext,
+        ) -> Result<Option<u64>, AppError> {
+            let mut users = self.0.lock().unwrap();
+            match users.iter().position(|u| u.id == id) {
+                Some(index) => {
+                    expected.check(users[index].version)?;
+                    users.remove(index);
+                    Ok(Some(1))
+                }
+                None => Ok(Some(0)),
+            }
+        }
+        async fn update(
+            &self,
+            id: Uuid,
+            firstname: String,
+            lastname: String,
+            expected: &ExpectedVersion,
+            _: &AuditContext,
+        ) -> Result<Option<u64>, AppError> {
+            let mut users = self.0.lock().unwrap();
+            match users.iter_mut().find(|u| u.id == id) {
+                Some(user) => {
+                    expected.check(user.version)?;
+                    user.firstname = firstname;
+                    user.lastname = lastname;
+                    user.version += 1;
+                    Ok(Some(1))
+                }
+                None => Ok(Some(0)),
+            }
+        }
+        async fn get_deleted(&self) -> Result<Vec<User>, AppError> {
+            Ok(Default::default())
+        }
+        fn stream_all(&self) -> BoxStream<'static, Result<User, AppError>> {
+            Box::pin(futures::stream::empty())
+        }
+        fn stream_deleted(&self) -> BoxStream<'static, Result<User, AppError>> {
+            Box::pin(futures::stream::empty())
+        }
+        async fn restore(&self, _: Uuid, _: &AuditContext) -> Result<Option<u64>, AppError> {
+            Ok(Default::default())
+        }
+        async fn reset_password(&self, _: Uuid, _: String, _: &AuditContext) -> Result<Option<u64>, AppError> {
+            Ok(Default::default())
+        }
+        async fn purge(&self, _: NaiveDateTime, _: &AuditContext) -> Result<u64, AppError> {
+            Ok(Default::default())
+        }
+    }
+
+    let service = || UserGrpcService {
+        user_repo: Arc::new(Users::default()),
+        jwt_processor: Arc::new(JwtProcessorImpl {}),
+        app_state: AppState {
+            jwt_secret_key: String::from("secret"),
+            jwt_previous_keys: Vec::new(),
+            jwt_lifetime: 1,
+        },
+        metrics: Metrics::new("test").unwrap(),
+        request_id: RequestIdService::new(&RequestIdConfig::default()).unwrap(),
+    };
+
+    // The request ID sent by the caller is kept if valid, like over HTTP
+    let mut request = Request::new(());
+    request
+        .metadata_mut()
+        .insert("x-request-id", "gateway-42".parse().unwrap());
+    assert_eq!(
+        Some("gateway-42"),
+        service().audit(&request, None).request_id.as_deref()
+    );
+    request
+        .metadata_mut()
+        .insert("x-request-id", "<script>".parse().unwrap());
+    let generated = service().audit(&request, None).request_id.unwrap();
+    assert!(Uuid::parse_str(&generated).is_ok());
+    let rate_limit = RateLimitConfig {
+        policies: vec![
+            RateLimitPolicy {
+                name: String::from("login"),
+                limit: 1,
+                ..RateLimitPolicy::default()
+            },
+            RateLimitPolicy {
+                name: String::from("register"),
+                limit: 2,
+                ..RateLimitPolicy::default()
+            },
+        ],
+        ..RateLimitConfig::default()
+    };
+    let rate_limit = RateLimitLayer::new(&rate_limit, Arc::new(MemoryRateLimitStore::default()));
+    let server = crate::grpc::serve("127.0.0.1:0", &TlsConfig::default(), rate_limit.clone(), service()).unwrap();
+    let endpoint = format!("http://{}", server.local_addr);
+
+    // Served over TLS with the certificate of the HTTP server when enabled
+    let (tls, _) = crate::tls::self_signed(&crate::tls::test_dir("grpc-tls"));
+    let tls_server = crate::grpc::serve("127.0.0.1:0", &tls, rate_limit, service()).unwrap();
+    let tls_port = tls_server.local_addr.port();
+    let ca = Certificate::from_pem(std::fs::read(&tls.cert_path).unwrap());
+
+    tokio::runtime::Runtime::new().unwrap().block_on(async move {
+        let mut health = HealthClient::connect(endpoint.clone()).await.unwrap();
+        for service in &["", "northwind.user.v1.UserService"] {
+            let request = HealthCheckRequest {
+                service: service.to_string(),
+            };
+            let response = health.check(request).await.unwrap().into_inner();
+            assert_eq!(ServingStatus::Serving as i32, response.status);
+        }
+
+        let channel = Channel::from_shared(format!("https://localhost:{}", tls_port))
+            .unwrap()
+            .tls_config(ClientTlsConfig::new().ca_certificate(ca).domain_name("localhost"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let request = HealthCheckRequest { service: String::new() };
+        let response = HealthClient::new(channel).check(request).await.unwrap().into_inner();
+        assert_eq!(ServingStatus::Serving as i32, response.status);
+        let mut plaintext = HealthClient::connect(format!("http://localhost:{}", tls_port))
+            .await
+            .unwrap();
+        let request = HealthCheckRequest { service: String::new() };
+        assert!(plaintext.check(request).await.is_err());
+
+        let mut client = UserServiceClient::connect(endpoint).await.unwrap();
+        let error = client.list(proto::ListUsersRequest {}).await.unwrap_err();
+        assert_eq!(Code::Unauthenticated, error.code());
+
+        let register = |email: &str| proto::RegisterRequest {
+            lastname: String::from("Chung"),
+            firstname: String::from("Thang"),
+            email: email.to_owned(),
+            password: String::from("password"),
+        };
+        let error = client.register(register("not an email")).await.unwrap_err();
+        assert_eq!(Code::InvalidArgument, error.code());
+        assert_eq!("Invalid user: invalid email (email)", error.message());
+        let user = client
+            .register(register("Thang@Example.com"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(("thang@example.com", 1), (user.email.as_str(), user.version));
+
+        let login = proto::LoginRequest {
+            email: String::from("thang@example.com"),
+            password: String::from("password"),
+        };
+        let token = client.login(login.clone()).await.unwrap().into_inner().token;
+        // Logins are limited like over HTTP
+        let error = client.login(login).await.unwrap_err();
+        assert_eq!(Code::ResourceExhausted, error.code());
+        fn bearer<T>(token: &str, message: T) -> Request<T> {
+            let mut request = Request::new(message);
+            let value = format!("Bearer {}", token).parse().unwrap();
+            request.metadata_mut().insert("authorization", value);
+            request
+        }
+
+        let users = client.list(bearer(&token, proto::ListUsersRequest {})).await.unwrap();
+        assert_eq!(1, users.into_inner().users.len());
+
+        // Changes require the current version of the user
+        let update = |version| proto::UpdateUserRequest {
+            id: user.id.clone(),
+            lastname: String::from("Chung"),
+            firstname: String::from("T."),
+            version,
+        };
+        let error = client.update(bearer(&token, update(0))).await.unwrap_err();
+        assert_eq!(Code::FailedPrecondition, error.code());
+        let updated = client.update(bearer(&token, update(1))).await.unwrap().into_inner();
+        assert_eq!(("T.", 2), (updated.firstname.as_str(), updated.version));
+        let error = client.update(bearer(&token, update(1))).await.unwrap_err();
+        assert_eq!(Code::Aborted, error.code());
+
+        let delete = proto::DeleteUserRequest {
+            id: user.id.clone(),
+            version: 2,
+        };
+        client.delete(bearer(&token, delete)).await.unwrap();
+        let get = proto::GetUserRequest { id: user.id.clone() };
+        // The token of a deleted user is refused
+        let error = client.get(bearer(&token, get)).await.unwrap_err();
+        assert_eq!(Code::Unauthenticated, error.code());
+    });
+}
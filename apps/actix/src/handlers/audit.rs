@@ -0,0 +1,151 @@
+//! API audit log handlers module
+
+use actix_web::{dev::Payload, web, Error, FromRequest, HttpRequest, HttpResponse, Responder};
+use futures::future::{ok, Ready};
+use std::net::SocketAddr;
+
+use crate::errors::ApiError;
+use crate::export::{self, Cell, ExportFormat, Tabular};
+use crate::middlewares::auth::AuthenticatedUser;
+use crate::middlewares::request_id::RequestIdMessage;
+use northwind_core::audit::{AuditContext, AuditEvent, AuditFilter, AuditRepository};
+
+/// Extractor of the audit context of a request: authenticated user, client IP, user agent and request ID
+pub struct Audit(pub AuditContext);
+
+/// Configuration of the `Audit` extractor
+#[derive(Clone, Default)]
+pub struct AuditConfig {
+    /// Reads the client IP from the forwarded headers, which any client can set, instead of the peer address
+    pub trust_forwarded_for: bool,
+}
+
+impl FromRequest for Audit {
+    type Error = Error;
+    type Future = Ready<Result<Audit, Error>>;
+    type Config = AuditConfig;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let trust_forwarded_for = req
+            .app_data::<AuditConfig>()
+            .map(|config| config.trust_forwarded_for)
+            .unwrap_or_default();
+        let ip = if trust_forwarded_for {
+            // `realip_remote_addr` is the forwarded client address if any, the peer address otherwise
+            req.connection_info().realip_remote_addr().map(|addr| {
+                addr.parse::<SocketAddr>()
+                    .map(|addr| addr.ip().to_string())
+                    .unwrap_or_else(|_| addr.to_owned())
+            })
+        } else {
+            req.peer_addr().map(|addr| addr.ip().to_string())
+        };
+        let user_agent = req
+            .headers()
+            .get(actix_web::http::header::USER_AGENT)
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.to_owned());
+
+        // Read before `id`, which may borrow the extensions mutably to store a generated ID
+        let actor_id = req.extensions().get::<AuthenticatedUser>().map(|user| user.id);
+
+        ok(Audit(AuditContext {
+            actor_id,
+            ip,
+            user_agent,
+            request_id: Some(req.id()),
+            traceparent: crate::telemetry::current_traceparent(),
+        }))
+    }
+}
+
+impl Tabular for AuditEvent {
+    fn columns() -> &'static [&'static str] {
+        &[
+            "ID",
+            "Occurred at",
+            "Actor ID",
+            "Action",
+            "Target type",
+            "Target ID",
+            "Before",
+            "After",
+            "IP",
+            "User agent",
+            "Request ID",
+        ]
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            self.id.into(),
+            self.occurred_at.into(),
+            self.actor_id.into(),
+            self.action.as_str().into(),
+            self.target_type.as_str().into(),
+            self.target_id.as_deref().into(),
+            self.before.clone().into(),
+            self.after.clone().into(),
+            self.ip.as_deref().into(),
+            self.user_agent.as_deref().into(),
+            self.request_id.as_deref().into(),
+        ]
+    }
+}
+
+// Route: GET "/v1/audit"
+#[tracing::instrument(skip(audit_repo, user, filter))]
+pub async fn search(
+    audit_repo: web::Data<dyn AuditRepository>,
+    user: AuthenticatedUser,
+    filter: web::Query<AuditFilter>,
+    format: ExportFormat,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    // Exports hold every matching event
+    if format != ExportFormat::Json {
+        return export::respond(format, "audit", audit_repo.stream(filter.into_inner())).await;
+    }
+
+    let events = audit_repo.search(&filter).await?;
+    Ok(HttpResponse::Ok().json(events))
+}
+
+pub fn init_auth_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("", web::get().to(crate::handlers::audit::search));
+}
+
+#[test]
+fn test_audit_ip() {
+    use actix_web::test::TestRequest;
+
+    let ip = |config: Option<AuditConfig>| {
+        let mut req = TestRequest::default()
+            .peer_addr("192.0.2.10:40000".parse().unwrap())
+            .header("x-forwarded-for", "203.0.113.9");
+        if let Some(config) = config {
+            req = req.app_data(config);
+        }
+        Audit::from_request(&req.to_http_request(), &mut Payload::None)
+            .into_inner()
+            .unwrap()
+            .0
+            .ip
+    };
+
+    // A forwarded address set by the client is ignored unless the proxy is trusted
+    assert_eq!(Some(String::from("192.0.2.10")), ip(None));
+    assert_eq!(
+        Some(String::from("192.0.2.10")),
+        ip(Some(AuditConfig {
+            trust_forwarded_for: false
+        }))
+    );
+    assert_eq!(
+        Some(String::from("203.0.113.9")),
+        ip(Some(AuditConfig {
+            trust_forwarded_for: true
+        }))
+    );
+}
@@ -7,10 +7,12 @@ use color_eyre::Result;
 use serde_json::json;
 use northwind_core::errors::{AppErrorMessage};
 
+use crate::middlewares::request_id::RequestIdMessage;
+
 fn render_error<B>(mut res: dev::ServiceResponse<B>, code: u16, message: String) -> ErrorHandlerResponse<B> {
-    let err = json!(AppErrorMessage { code, message });
+    let request_id = Some(res.request().id());
+    let err = json!(AppErrorMessage { code, message, request_id });
 
-    res.request();
     res.headers_mut().insert(
         http::header::CONTENT_TYPE,
         http::HeaderValue::from_static("application/json"),
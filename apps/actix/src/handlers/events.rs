@@ -0,0 +1,44 @@
+//! Domain events stream handlers module
+
+use actix_web::{http::header, web, HttpResponse};
+use serde::Deserialize;
+
+use crate::errors::ApiError;
+use crate::events::{EventBroadcaster, EventFilter};
+use crate::middlewares::auth::AuthenticatedUser;
+use northwind_core::errors::AppError;
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// Comma separated event types or aggregates, e.g. `user,order.shipped`
+    pub types: Option<String>,
+}
+
+// Route: GET "/v1/events"
+#[tracing::instrument(skip(broadcaster, user))]
+pub async fn stream(
+    broadcaster: web::Data<EventBroadcaster>,
+    user: AuthenticatedUser,
+    query: web::Query<EventsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let mut filter = EventFilter::parse(query.types.as_deref().unwrap_or_default())?;
+
+    // User events carry emails and names, streamed to administrators only
+    if !user.is_admin() {
+        if filter.names("user") {
+            return Err(AppError::Forbidden.into());
+        }
+        filter = filter.exclude("user");
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        // Disables the buffering of proxies such as nginx
+        .header("X-Accel-Buffering", "no")
+        .streaming(broadcaster.subscribe(filter)))
+}
+
+pub fn init_auth_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("", web::get().to(crate::handlers::events::stream));
+}
@@ -0,0 +1,27 @@
+//! GraphQL handlers module
+
+use actix_web::{web, HttpResponse, Responder};
+use async_graphql_actix_web::{Request, Response};
+
+use crate::graphql::NorthwindSchema;
+use crate::middlewares::auth::AuthenticatedUser;
+
+/// GraphiQL playground page, sending its requests to `/graphql`
+const GRAPHIQL: &str = include_str!("../graphql/graphiql.html");
+
+// Route: POST "/graphql"
+#[tracing::instrument(skip(schema, user, request))]
+pub async fn graphql(schema: web::Data<NorthwindSchema>, user: AuthenticatedUser, request: Request) -> Response {
+    schema.execute(request.into_inner().data(user)).await.into()
+}
+
+// Route: GET "/graphiql"
+pub async fn graphiql() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(GRAPHIQL)
+}
+
+pub fn init_auth_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("", web::post().to(crate::handlers::graphql::graphql));
+}
@@ -0,0 +1,245 @@
+//! Bulk import handlers module
+
+use actix_web::{http::header, web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use futures::Future;
+use serde::de::DeserializeOwned;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::errors::ApiError;
+use crate::handlers::audit::Audit;
+use crate::import::{parse, ImportFormat, ImportQuery, ImportReport, Importer, ParsedRows};
+use crate::middlewares::auth::AuthenticatedUser;
+use northwind_core::errors::AppError;
+use northwind_core::import::{BatchError, ImportJob};
+use northwind_traders::domain::catalog::Product;
+use northwind_traders::domain::catalog_repository::CatalogRepository;
+use northwind_traders::domain::customer::Customer;
+use northwind_traders::domain::customer_repository::CustomerRepository;
+use northwind_user::domain::user::{normalize_email, User, UserCreation};
+use northwind_user::domain::user_repository::UserRepository;
+
+/// Parsed file of an import request
+struct ImportFile<'a> {
+    entity: &'a str,
+    format: ImportFormat,
+    body: &'a [u8],
+    dry_run: bool,
+    created_by: Uuid,
+}
+
+impl ImportFile<'_> {
+    /// Deserializes and validates the rows
+    fn parse<T>(&self) -> ParsedRows<T>
+    where
+        T: DeserializeOwned + Validate,
+    {
+        parse(self.format, self.body)
+    }
+
+    /// Starts a job inserting the checked rows with `insert`, unless the file is invalid or the
+    /// import is a dry run
+    async fn start<T, F, Fut>(
+        &self,
+        importer: &Importer,
+        rows: ParsedRows<T>,
+        insert: F,
+    ) -> Result<HttpResponse, ApiError>
+    where
+        T: 'static,
+        F: Fn(Vec<T>) -> Fut + 'static,
+        Fut: Future<Output = Result<u64, BatchError>>,
+    {
+        let report = ImportReport {
+            entity: self.entity.to_owned(),
+            dry_run: self.dry_run,
+            total_rows: rows.rows.len() + rows.errors.len(),
+            valid_rows: rows.rows.len(),
+            errors: rows.errors,
+        };
+        let rows: Vec<T> = rows.rows.into_iter().map(|(_, row)| row).collect();
+
+        if self.dry_run {
+            return Ok(HttpResponse::Ok().json(report));
+        }
+        if !report.errors.is_empty() {
+            return Ok(HttpResponse::UnprocessableEntity().json(report));
+        }
+        if rows.is_empty() {
+            return Err(AppError::BadRequest {
+                message: String::from("The file has no rows"),
+            }
+            .into());
+        }
+
+        let job = ImportJob::new(self.entity, rows.len(), Some(self.created_by));
+        importer.repo.create(&job).await?;
+
+        let (job_id, importer) = (job.id, importer.clone());
+        actix_web::rt::spawn(async move {
+            if let Err(e) = importer.run(job_id, rows, insert).await {
+                error!("Import {} failed: {}", job_id, e);
+            }
+        });
+
+        Ok(HttpResponse::Accepted()
+            .header(header::LOCATION, format!("/v1/import/jobs/{}", job.id))
+            .json(job))
+    }
+}
+
+// Route: POST "/v1/import/{entity}"
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(req, body, user, audit, importer, user_repo, customer_repo, catalog_repo))]
+pub async fn import(
+    req: HttpRequest,
+    web::Path(entity): web::Path<String>,
+    query: web::Query<ImportQuery>,
+    body: web::Bytes,
+    user: AuthenticatedUser,
+    audit: Audit,
+    importer: web::Data<Importer>,
+    user_repo: web::Data<dyn UserRepository>,
+    customer_repo: web::Data<dyn CustomerRepository>,
+    catalog_repo: web::Data<dyn CatalogRepository>,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    let format = ImportFormat::from_content_type(req.content_type()).ok_or_else(|| -> ApiError {
+        AppError::UnsupportedMediaType {
+            message: String::from("Imported files must be text/csv or application/x-ndjson"),
+        }
+        .into()
+    })?;
+    let file = ImportFile {
+        entity: &entity,
+        format,
+        body: &body,
+        dry_run: query.dry_run,
+        created_by: user.id,
+    };
+    let audit = audit.0;
+
+    // Rows are checked against each other and against the database before the job starts, the
+    // batches failing only if the tables change meanwhile
+    match entity.as_str() {
+        "customers" => {
+            let mut rows = file.parse::<Customer>();
+            let ids = rows.values(|customer| Some(customer.customer_id.clone()));
+            let existing: Vec<String> = customer_repo
+                .get_customers_by_ids(&ids)
+                .await?
+                .into_iter()
+                .map(|customer| customer.customer_id)
+                .collect();
+            rows.reject_taken(
+                |customer| customer.customer_id.clone(),
+                &existing,
+                |id| format!("Customer {} already exists", id),
+            );
+
+            let repo = customer_repo.clone();
+            file.start(&importer, rows, move |customers: Vec<Customer>| {
+                let (repo, audit) = (repo.clone(), audit.clone());
+                async move { repo.import(&customers, &audit).await }
+            })
+            .await
+        }
+        "products" => {
+            let mut rows = file.parse::<Product>();
+            let ids = rows.values(|product| Some(product.product_id));
+            let existing: Vec<i16> = catalog_repo
+                .get_products_by_ids(&ids)
+                .await?
+                .into_iter()
+                .map(|product| product.product_id)
+                .collect();
+            rows.reject_taken(
+                |product| product.product_id,
+                &existing,
+                |id| format!("Product {} already exists", id),
+            );
+            let ids = rows.values(|product| product.category_id);
+            let categories: Vec<i16> = catalog_repo
+                .get_categories_by_ids(&ids)
+                .await?
+                .into_iter()
+                .map(|category| category.category_id)
+                .collect();
+            rows.reject_unknown(
+                |product| product.category_id,
+                &categories,
+                |id| format!("Unknown category {}", id),
+            );
+            let ids = rows.values(|product| product.supplier_id);
+            let suppliers: Vec<i16> = catalog_repo
+                .get_suppliers_by_ids(&ids)
+                .await?
+                .into_iter()
+                .map(|supplier| supplier.supplier_id)
+                .collect();
+            rows.reject_unknown(
+                |product| product.supplier_id,
+                &suppliers,
+                |id| format!("Unknown supplier {}", id),
+            );
+
+            let repo = catalog_repo.clone();
+            file.start(&importer, rows, move |products: Vec<Product>| {
+                let (repo, audit) = (repo.clone(), audit.clone());
+                async move { repo.import_products(&products, &audit).await }
+            })
+            .await
+        }
+        "users" => {
+            let mut rows = file.parse::<UserCreation>();
+            let emails = rows.values(|user| Some(normalize_email(&user.email)));
+            let existing: Vec<String> = user_repo
+                .get_by_emails(&emails)
+                .await?
+                .into_iter()
+                .map(|user| user.email)
+                .collect();
+            rows.reject_taken(
+                |user| normalize_email(&user.email),
+                &existing,
+                |_| String::from("A user with this email already exists"),
+            );
+
+            let repo = user_repo.clone();
+            file.start(&importer, rows, move |users: Vec<UserCreation>| {
+                let (repo, audit) = (repo.clone(), audit.clone());
+                let mut users: Vec<User> = users.into_iter().map(User::new).collect();
+                async move { repo.import(&mut users, &audit).await }
+            })
+            .await
+        }
+        _ => Err(AppError::NotFound {
+            message: format!("Unknown entity {}, expected customers, products or users", entity),
+        }
+        .into()),
+    }
+}
+
+// Route: GET "/v1/import/jobs/{id}"
+#[tracing::instrument(skip(importer, user))]
+pub async fn get_job(
+    importer: web::Data<Importer>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<Uuid>,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    match importer.repo.get_by_id(id).await? {
+        Some(job) => Ok(HttpResponse::Ok().json(job)),
+        None => Err(AppError::NotFound {
+            message: String::from("No import job found"),
+        }
+        .into()),
+    }
+}
+
+pub fn init_auth_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/jobs/{id}", web::get().to(crate::handlers::import::get_job))
+        .route("/{entity}", web::post().to(crate::handlers::import::import));
+}
@@ -1,5 +1,11 @@
 //! Handlers module list all application handlers
 
+pub mod audit;
 pub mod errors;
+pub mod events;
+pub mod graphql;
+pub mod import;
+pub mod reports;
 pub mod web;
 pub mod users;
+pub mod webhooks;
@@ -0,0 +1,60 @@
+//! Reports handlers module
+
+use actix_web::{web, Responder};
+
+use crate::errors::ApiError;
+use crate::export::{self, Cell, ExportFormat, Tabular};
+use northwind_traders::domain::report::{OrderSummary, ReportFilter};
+use northwind_traders::domain::report_repository::ReportRepository;
+
+impl Tabular for OrderSummary {
+    fn columns() -> &'static [&'static str] {
+        &[
+            "Order ID",
+            "Order date",
+            "Required date",
+            "Shipped date",
+            "Customer ID",
+            "Company",
+            "Employee",
+            "Ship country",
+            "Lines",
+            "Quantity",
+            "Subtotal",
+            "Freight",
+            "Total",
+        ]
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            self.order_id.into(),
+            self.order_date.into(),
+            self.required_date.into(),
+            self.shipped_date.into(),
+            self.customer_id.as_deref().into(),
+            self.company_name.as_deref().into(),
+            self.employee_name.as_deref().into(),
+            self.ship_country.as_deref().into(),
+            self.lines.into(),
+            self.quantity.into(),
+            self.subtotal.into(),
+            self.freight.into(),
+            self.total.into(),
+        ]
+    }
+}
+
+// Route: GET "/v1/reports/orders"
+#[tracing::instrument(skip(report_repo, filter))]
+pub async fn orders(
+    report_repo: web::Data<dyn ReportRepository>,
+    filter: web::Query<ReportFilter>,
+    format: ExportFormat,
+) -> Result<impl Responder, ApiError> {
+    export::respond(format, "orders", report_repo.order_summaries(filter.into_inner())).await
+}
+
+pub fn init_auth_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/orders", web::get().to(crate::handlers::reports::orders));
+}
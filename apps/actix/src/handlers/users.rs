@@ -1,29 +1,74 @@
 //! API users handlers module
 
 use crate::AppState;
-use actix_web::{http::StatusCode, web, HttpResponse, Responder};
+use actix_web::{http::header, http::StatusCode, web, HttpRequest, HttpResponse, Responder};
 use actix_web_validator::Json;
-use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
 use uuid::Uuid;
 
 use crate::errors::ApiError;
+use crate::etag::{json_with_etag, versioned_json, IfMatch};
+use crate::export::{self, Cell, ExportFormat, Tabular};
+use crate::handlers::audit::Audit;
+use crate::metrics::Metrics;
+use crate::patch::MergePatch;
+use crate::middlewares::auth::AuthenticatedUser;
 use northwind_user::domain::user::{Login, LoginResponse, UpdateUserModel, User, UserCreation};
 use northwind_user::domain::jwt_processor::JwtProcessor;
+use northwind_core::concurrency::ExpectedVersion;
 use northwind_core::errors::AppError;
+use northwind_core::patch;
 use northwind_user::domain::user_repository::UserRepository;
 
+impl Tabular for User {
+    fn columns() -> &'static [&'static str] {
+        &[
+            "ID",
+            "First name",
+            "Last name",
+            "Email",
+            "Role",
+            "Created at",
+            "Updated at",
+            "Deleted at",
+        ]
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            self.id.into(),
+            self.firstname.as_str().into(),
+            self.lastname.as_str().into(),
+            self.email.as_str().into(),
+            self.role.as_str().into(),
+            self.created_at.into(),
+            self.updated_at.into(),
+            self.deleted_at.into(),
+        ]
+    }
+}
+
 // Route: POST "/v1/login"
+#[tracing::instrument(skip(data, jwt_processor, user_repo, metrics, audit, form))]
 pub async fn login(
     data: web::Data<AppState>,
     jwt_processor: web::Data<dyn JwtProcessor>,
     user_repo: web::Data<dyn UserRepository>,
+    metrics: web::Data<Metrics>,
+    audit: Audit,
     form: Json<Login>,
 ) -> Result<impl Responder, ApiError> {
     let model = form.into_inner();
-    let user = user_repo.login(model.email, model.password).await?;
+    let user = user_repo
+        .login(model.email, model.password, &audit.0)
+        .await
+        .inspect_err(|_| metrics.login_failed("error"))?;
 
     match user {
-        None => Err(AppError::Unauthorized {}.into()),
+        None => {
+            metrics.login_failed("invalid_credentials");
+            Err(AppError::Unauthorized {}.into())
+        }
         Some(user) => {
             // generate the token
             // -------------------
@@ -34,16 +79,17 @@ pub async fn login(
                 user.lastname.to_owned(),
                 user.firstname.to_owned(),
                 user.email.to_owned(),
-                String::from(secret.to_owned()),
+                secret.to_owned(),
                 jwt_lifetime,
             );
 
             match token {
                 Ok(token) => {
-                    let expires_at = NaiveDateTime::from_timestamp(token.1, 0);
-                    let expires_at: DateTime<Utc> = DateTime::from_utc(expires_at, Utc);
+                    metrics.login_succeeded();
+                    let expires_at: DateTime<Utc> = Utc.timestamp_opt(token.1, 0).unwrap();
 
-                    Ok(HttpResponse::Ok().json(LoginResponse {
+                    // Tokens are neither cached nor stored for idempotent replays
+                    Ok(HttpResponse::Ok().set_header(header::CACHE_CONTROL, "no-store").json(LoginResponse {
                         id: user.id.to_owned().to_string(),
                         lastname: user.lastname.to_owned(),
                         firstname: user.firstname.to_owned(),
@@ -52,19 +98,32 @@ pub async fn login(
                         expires_at: expires_at.to_rfc3339_opts(SecondsFormat::Secs, true),
                     }))
                 }
-                _ => Err(AppError::Unauthorized {}.into()),
+                _ => {
+                    metrics.login_failed("token_error");
+                    Err(AppError::Unauthorized {}.into())
+                }
             }
         }
     }
 }
 
 // Route: POST "/v1/register"
-pub async fn register(user_repo: web::Data<dyn UserRepository>, form: Json<UserCreation>) -> Result<impl Responder, ApiError> {
+#[tracing::instrument(skip(user_repo, metrics, audit, form))]
+pub async fn register(
+    user_repo: web::Data<dyn UserRepository>,
+    metrics: web::Data<Metrics>,
+    audit: Audit,
+    form: Json<UserCreation>,
+) -> Result<impl Responder, ApiError> {
     let mut user = User::new(form.0);
-    let result = user_repo.create(&mut user).await;
+    let result = user_repo.create(&mut user, &audit.0).await;
 
     match result {
-        Ok(_) => Ok(HttpResponse::Ok().json(user)),
+        Ok(_) => {
+            metrics.user_registered();
+            Ok(json_with_etag(user.version, &user))
+        }
+        Err(e @ AppError::Conflict { .. }) => Err(e.into()),
         _ => Err(AppError::InternalError {
             message: String::from("Error during user creation"),
         }
@@ -73,16 +132,29 @@ pub async fn register(user_repo: web::Data<dyn UserRepository>, form: Json<UserC
 }
 
 // Route: GET "/v1/users"
-pub async fn get_all(user_repo: web::Data<dyn UserRepository>) -> Result<impl Responder, ApiError> {
+#[tracing::instrument(skip(user_repo))]
+pub async fn get_all(
+    user_repo: web::Data<dyn UserRepository>,
+    format: ExportFormat,
+) -> Result<impl Responder, ApiError> {
+    if format != ExportFormat::Json {
+        return export::respond(format, "users", user_repo.stream_all()).await;
+    }
+
     let users = user_repo.get_all().await?;
     Ok(HttpResponse::Ok().json(users))
 }
 
 // Route: GET "/v1/users/{id}"
-pub async fn get_by_id(user_repo: web::Data<dyn UserRepository>, web::Path(id): web::Path<Uuid>) -> Result<impl Responder, ApiError> {
+#[tracing::instrument(skip(req, user_repo))]
+pub async fn get_by_id(
+    req: HttpRequest,
+    user_repo: web::Data<dyn UserRepository>,
+    web::Path(id): web::Path<Uuid>,
+) -> Result<impl Responder, ApiError> {
     let user = user_repo.get_by_id(id).await?;
     match user {
-        Some(user) => Ok(HttpResponse::Ok().json(user)),
+        Some(user) => Ok(versioned_json(&req, user.version, &user)),
         _ => Err(AppError::NotFound {
             message: String::from("No user found"),
         }
@@ -91,19 +163,26 @@ pub async fn get_by_id(user_repo: web::Data<dyn UserRepository>, web::Path(id):
 }
 
 // Route: DELETE "/v1/users/{id}"
-pub async fn delete(user_repo: web::Data<dyn UserRepository>, web::Path(id): web::Path<Uuid>) -> Result<impl Responder, ApiError> {
-    let result = user_repo.delete(id).await;
+#[tracing::instrument(skip(user_repo, audit, if_match))]
+pub async fn delete(
+    user_repo: web::Data<dyn UserRepository>,
+    audit: Audit,
+    if_match: IfMatch,
+    web::Path(id): web::Path<Uuid>,
+) -> Result<impl Responder, ApiError> {
+    let result = user_repo.delete(id, &if_match.0, &audit.0).await;
     match result {
         Ok(result) => {
             if result.unwrap() == 1 {
                 Ok(HttpResponse::Ok().status(StatusCode::NO_CONTENT).finish())
             } else {
-            Err(AppError::InternalError {
-            message: String::from("No user or user already deleted"),
-            }
-            .into())
+                Err(AppError::NotFound {
+                    message: String::from("No user found"),
+                }
+                .into())
             }
         }
+        Err(e @ AppError::PreconditionFailed { .. }) => Err(e.into()),
         _ => Err(AppError::InternalError {
             message: String::from("Error during user deletion"),
         }
@@ -112,17 +191,102 @@ pub async fn delete(user_repo: web::Data<dyn UserRepository>, web::Path(id): web
 }
 
 // Route: PUT "/v1/users/{id}"
+#[tracing::instrument(skip(user_repo, audit, if_match, form))]
 pub async fn update(
     user_repo: web::Data<dyn UserRepository>,
+    audit: Audit,
+    if_match: IfMatch,
     web::Path(id): web::Path<Uuid>,
     form: Json<UpdateUserModel>,
 ) -> Result<impl Responder, ApiError> {
     let model = &form.0;
-    user_repo.update(id.clone(), model.firstname.clone(), model.lastname.clone()).await?;
+    user_repo
+        .update(id, model.firstname.clone(), model.lastname.clone(), &if_match.0, &audit.0)
+        .await?;
+
+    let user = user_repo.get_by_id(id).await?;
+    match user {
+        Some(user) => Ok(json_with_etag(user.version, &user)),
+        _ => Err(AppError::NotFound {
+            message: String::from("No user found"),
+        }
+        .into()),
+    }
+}
+
+// Route: PATCH "/v1/users/{id}"
+#[tracing::instrument(skip(user_repo, audit, if_match, patch))]
+pub async fn patch(
+    user_repo: web::Data<dyn UserRepository>,
+    audit: Audit,
+    if_match: IfMatch,
+    web::Path(id): web::Path<Uuid>,
+    patch: MergePatch,
+) -> Result<impl Responder, ApiError> {
+    let user = user_repo.get_by_id(id).await?.ok_or_else(|| AppError::NotFound {
+        message: String::from("No user found"),
+    })?;
+    if_match.0.check(user.version)?;
+
+    // The patch applies to the version read, which must not change before the update
+    let model = patch::apply(&UpdateUserModel::from(&user), patch.0)?;
+    user_repo
+        .update(
+            id,
+            model.firstname,
+            model.lastname,
+            &ExpectedVersion::OneOf(vec![user.version]),
+            &audit.0,
+        )
+        .await?;
+
+    let user = user_repo.get_by_id(id).await?;
+    match user {
+        Some(user) => Ok(json_with_etag(user.version, &user)),
+        _ => Err(AppError::NotFound {
+            message: String::from("No user found"),
+        }
+        .into()),
+    }
+}
+
+// Route: GET "/v1/users/deleted"
+#[tracing::instrument(skip(user_repo, user))]
+pub async fn get_deleted(
+    user_repo: web::Data<dyn UserRepository>,
+    user: AuthenticatedUser,
+    format: ExportFormat,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    if format != ExportFormat::Json {
+        return export::respond(format, "deleted-users", user_repo.stream_deleted()).await;
+    }
+
+    let users = user_repo.get_deleted().await?;
+    Ok(HttpResponse::Ok().json(users))
+}
+
+// Route: POST "/v1/users/{id}/restore"
+#[tracing::instrument(skip(user_repo, user, audit))]
+pub async fn restore(
+    user_repo: web::Data<dyn UserRepository>,
+    user: AuthenticatedUser,
+    audit: Audit,
+    web::Path(id): web::Path<Uuid>,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    if user_repo.restore(id, &audit.0).await? != Some(1) {
+        return Err(AppError::NotFound {
+            message: String::from("No deleted user found"),
+        }
+        .into());
+    }
 
     let user = user_repo.get_by_id(id).await?;
     match user {
-        Some(user) => Ok(HttpResponse::Ok().json(user)),
+        Some(user) => Ok(json_with_etag(user.version, &user)),
         _ => Err(AppError::NotFound {
             message: String::from("No user found"),
         }
@@ -137,7 +301,10 @@ pub fn init_routes(cfg: &mut web::ServiceConfig) {
 
 pub fn init_auth_routes(cfg: &mut web::ServiceConfig) {
     cfg.route("", web::get().to(crate::handlers::users::get_all))
+        .route("/deleted", web::get().to(crate::handlers::users::get_deleted))
         .route("/{id}", web::get().to(crate::handlers::users::get_by_id))
         .route("/{id}", web::delete().to(crate::handlers::users::delete))
-        .route("/{id}", web::put().to(crate::handlers::users::update));
+        .route("/{id}", web::put().to(crate::handlers::users::update))
+        .route("/{id}", web::patch().to(crate::handlers::users::patch))
+        .route("/{id}/restore", web::post().to(crate::handlers::users::restore));
 }
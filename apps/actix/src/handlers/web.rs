@@ -1,8 +1,10 @@
 //! Web handlers module
 
 use crate::{middlewares::request_id::RequestId};
+use crate::config::{MetricsConfig, ServerConfig};
 use crate::errors::ApiError;
-use actix_web::{HttpResponse, Responder, web};
+use actix_web::{http::header, HttpRequest, HttpResponse, Responder, web};
+use prometheus::{Encoder, Registry, TextEncoder};
 
 // Route: GET "/health-check"
 pub async fn health_check(request_id: RequestId) -> Result<impl Responder, ApiError> {
@@ -10,6 +12,96 @@ pub async fn health_check(request_id: RequestId) -> Result<impl Responder, ApiEr
     Ok(HttpResponse::Ok().finish())
 }
 
+// Route: any route of the plain HTTP port when TLS is enabled
+pub async fn https_redirect(req: HttpRequest, server: web::Data<ServerConfig>) -> HttpResponse {
+    let host = req.connection_info().host().to_owned();
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => name.to_owned(),
+        _ => host,
+    };
+    let port = match server.port {
+        443 => String::new(),
+        port => format!(":{}", port),
+    };
+    let path = req.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+    HttpResponse::PermanentRedirect()
+        .header(header::LOCATION, format!("https://{}{}{}", host, port, path))
+        .finish()
+}
+
+// Route: GET "/metrics" (`metrics.path`), on the API server or on `metrics.address`
+pub async fn metrics(
+    req: HttpRequest,
+    registry: web::Data<Registry>,
+    config: web::Data<MetricsConfig>,
+) -> HttpResponse {
+    if !config.token.is_empty() {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        if !constant_time_eq(token.trim().as_bytes(), config.token.as_bytes()) {
+            return HttpResponse::Unauthorized().finish();
+        }
+    }
+
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    match encoder.encode(&registry.gather(), &mut buffer) {
+        Ok(_) => HttpResponse::Ok()
+            .header(header::CONTENT_TYPE, encoder.format_type())
+            .body(buffer),
+        Err(e) => {
+            error!("Metrics encoding failed: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Compares secrets in a time independent of the position of the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.route("/healthz", web::get().to(health_check));
 }
+
+#[test]
+fn test_https_redirect() {
+    use actix_web::{test, App};
+
+    let redirect = |port: u16, host: &'static str, uri: &'static str| {
+        actix_web::rt::System::new("test").block_on(async move {
+            let server = ServerConfig {
+                port,
+                ..ServerConfig::default()
+            };
+            let mut app =
+                test::init_service(App::new().data(server).default_service(web::route().to(https_redirect))).await;
+
+            let req = test::TestRequest::get()
+                .uri(uri)
+                .header(header::HOST, host)
+                .to_request();
+            let res = test::call_service(&mut app, req).await;
+            assert_eq!(actix_web::http::StatusCode::PERMANENT_REDIRECT, res.status());
+            res.headers()
+                .get(header::LOCATION)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned()
+        })
+    };
+
+    assert_eq!(
+        "https://example.com:8443/v1/users?limit=5",
+        redirect(8443, "example.com:8080", "/v1/users?limit=5")
+    );
+    assert_eq!("https://example.com/", redirect(443, "example.com", "/"));
+    assert_eq!("https://[::1]:8443/healthz", redirect(8443, "[::1]:8080", "/healthz"));
+}
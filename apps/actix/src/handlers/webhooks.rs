@@ -0,0 +1,184 @@
+//! Webhook subscriptions handlers module
+
+use actix_web::{web, HttpResponse, Responder};
+use actix_web_validator::Json;
+use uuid::Uuid;
+
+use crate::errors::ApiError;
+use crate::handlers::audit::Audit;
+use crate::middlewares::auth::AuthenticatedUser;
+use northwind_core::errors::AppError;
+use northwind_webhooks::domain::delivery::DeliveryFilter;
+use northwind_webhooks::domain::subscription::{Subscription, SubscriptionCreated, SubscriptionRequest};
+use northwind_webhooks::domain::webhook_repository::WebhookRepository;
+
+fn subscription_not_found() -> ApiError {
+    AppError::NotFound {
+        message: String::from("No webhook subscription found"),
+    }
+    .into()
+}
+
+// Route: POST "/v1/webhooks"
+#[tracing::instrument(skip(webhook_repo, user, audit, form))]
+pub async fn create(
+    webhook_repo: web::Data<dyn WebhookRepository>,
+    user: AuthenticatedUser,
+    audit: Audit,
+    form: Json<SubscriptionRequest>,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    let subscription = Subscription::new(form.into_inner());
+    webhook_repo.create(&subscription, &audit.0).await?;
+
+    Ok(HttpResponse::Created().json(SubscriptionCreated {
+        secret: subscription.secret.clone(),
+        subscription,
+    }))
+}
+
+// Route: GET "/v1/webhooks"
+#[tracing::instrument(skip(webhook_repo, user))]
+pub async fn get_all(
+    webhook_repo: web::Data<dyn WebhookRepository>,
+    user: AuthenticatedUser,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    let subscriptions = webhook_repo.get_all().await?;
+    Ok(HttpResponse::Ok().json(subscriptions))
+}
+
+// Route: GET "/v1/webhooks/{id}"
+#[tracing::instrument(skip(webhook_repo, user))]
+pub async fn get_by_id(
+    webhook_repo: web::Data<dyn WebhookRepository>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<Uuid>,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    match webhook_repo.get_by_id(id).await? {
+        Some(subscription) => Ok(HttpResponse::Ok().json(subscription)),
+        None => Err(subscription_not_found()),
+    }
+}
+
+// Route: PUT "/v1/webhooks/{id}"
+#[tracing::instrument(skip(webhook_repo, user, audit, form))]
+pub async fn update(
+    webhook_repo: web::Data<dyn WebhookRepository>,
+    user: AuthenticatedUser,
+    audit: Audit,
+    web::Path(id): web::Path<Uuid>,
+    form: Json<SubscriptionRequest>,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    match webhook_repo.update(id, &form, &audit.0).await? {
+        Some(subscription) => Ok(HttpResponse::Ok().json(subscription)),
+        None => Err(subscription_not_found()),
+    }
+}
+
+// Route: DELETE "/v1/webhooks/{id}"
+#[tracing::instrument(skip(webhook_repo, user, audit))]
+pub async fn delete(
+    webhook_repo: web::Data<dyn WebhookRepository>,
+    user: AuthenticatedUser,
+    audit: Audit,
+    web::Path(id): web::Path<Uuid>,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    match webhook_repo.delete(id, &audit.0).await? {
+        0 => Err(subscription_not_found()),
+        _ => Ok(HttpResponse::NoContent().finish()),
+    }
+}
+
+// Route: GET "/v1/webhooks/{id}/deliveries"
+#[tracing::instrument(skip(webhook_repo, user, filter))]
+pub async fn get_deliveries(
+    webhook_repo: web::Data<dyn WebhookRepository>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<Uuid>,
+    filter: web::Query<DeliveryFilter>,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    if webhook_repo.get_by_id(id).await?.is_none() {
+        return Err(subscription_not_found());
+    }
+    let deliveries = webhook_repo.get_deliveries(id, &filter).await?;
+    Ok(HttpResponse::Ok().json(deliveries))
+}
+
+// Route: GET "/v1/webhooks/{id}/deliveries/{delivery_id}"
+#[tracing::instrument(skip(webhook_repo, user))]
+pub async fn get_delivery(
+    webhook_repo: web::Data<dyn WebhookRepository>,
+    user: AuthenticatedUser,
+    web::Path((id, delivery_id)): web::Path<(Uuid, Uuid)>,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    match webhook_repo.get_delivery(id, delivery_id).await? {
+        Some(delivery) => Ok(HttpResponse::Ok().json(delivery)),
+        None => Err(AppError::NotFound {
+            message: String::from("No webhook delivery found"),
+        }
+        .into()),
+    }
+}
+
+// Route: POST "/v1/webhooks/{id}/deliveries/{delivery_id}/redeliver"
+#[tracing::instrument(skip(webhook_repo, user, audit))]
+pub async fn redeliver(
+    webhook_repo: web::Data<dyn WebhookRepository>,
+    user: AuthenticatedUser,
+    audit: Audit,
+    web::Path((id, delivery_id)): web::Path<(Uuid, Uuid)>,
+) -> Result<impl Responder, ApiError> {
+    user.require_admin()?;
+
+    match webhook_repo.get_by_id(id).await? {
+        Some(subscription) if !subscription.enabled => {
+            return Err(AppError::Conflict {
+                message: String::from("The webhook subscription is disabled"),
+            }
+            .into())
+        }
+        Some(_) => {}
+        None => return Err(subscription_not_found()),
+    }
+
+    match webhook_repo.redeliver(id, delivery_id, &audit.0).await? {
+        0 => Err(AppError::NotFound {
+            message: String::from("No webhook delivery found"),
+        }
+        .into()),
+        _ => Ok(HttpResponse::Accepted().finish()),
+    }
+}
+
+pub fn init_auth_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("", web::post().to(crate::handlers::webhooks::create))
+        .route("", web::get().to(crate::handlers::webhooks::get_all))
+        .route("/{id}", web::get().to(crate::handlers::webhooks::get_by_id))
+        .route("/{id}", web::put().to(crate::handlers::webhooks::update))
+        .route("/{id}", web::delete().to(crate::handlers::webhooks::delete))
+        .route(
+            "/{id}/deliveries",
+            web::get().to(crate::handlers::webhooks::get_deliveries),
+        )
+        .route(
+            "/{id}/deliveries/{delivery_id}",
+            web::get().to(crate::handlers::webhooks::get_delivery),
+        )
+        .route(
+            "/{id}/deliveries/{delivery_id}/redeliver",
+            web::post().to(crate::handlers::webhooks::redeliver),
+        );
+}
@@ -0,0 +1,378 @@
+//! Bulk import module
+//!
+//! Imported files are CSV, with a header line naming the fields, or NDJSON, one JSON object
+//! per line. Every row is deserialized and validated before anything is inserted, then checked
+//! against the other rows and the database, so that duplicates and unknown references are
+//! reported at once rather than failing the job after some batches have been imported.
+
+use futures::Future;
+use northwind_core::errors::{invalid_fields, AppError};
+use northwind_core::import::{BatchError, ImportJobRepository, RowError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Format of an imported file, from its content type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ImportFormat {
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type {
+            "text/csv" => Some(ImportFormat::Csv),
+            "application/x-ndjson" => Some(ImportFormat::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    /// Validates the file without importing it
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Validation of an imported file
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub entity: String,
+    pub dry_run: bool,
+    pub total_rows: usize,
+    pub valid_rows: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// Rows of an imported file
+#[derive(Debug)]
+pub struct ParsedRows<T> {
+    /// Valid rows, along with their number counted from 1 without the CSV header
+    pub rows: Vec<(usize, T)>,
+    pub errors: Vec<RowError>,
+}
+
+impl<T> ParsedRows<T> {
+    /// Distinct values of a field of the valid rows
+    pub fn values<K>(&self, field: impl Fn(&T) -> Option<K>) -> Vec<K>
+    where
+        K: Eq + Hash,
+    {
+        let values: HashSet<K> = self.rows.iter().filter_map(|(_, row)| field(row)).collect();
+        values.into_iter().collect()
+    }
+
+    /// Rejects the rows whose key is already taken, by an earlier row or by one of the `existing`
+    /// entities, described by `message`
+    pub fn reject_taken<K>(&mut self, key: impl Fn(&T) -> K, existing: &[K], message: impl Fn(&K) -> String)
+    where
+        K: Eq + Hash,
+    {
+        let mut first_rows = HashMap::new();
+        self.reject(|row_number, row| {
+            let key = key(row);
+            if existing.contains(&key) {
+                return Some(message(&key));
+            }
+            match first_rows.get(&key) {
+                Some(first_row) => Some(format!("Duplicate of row {}", first_row)),
+                None => {
+                    first_rows.insert(key, row_number);
+                    None
+                }
+            }
+        });
+    }
+
+    /// Rejects the rows referencing an entity which is not one of the `known` ones, described by
+    /// `message`
+    pub fn reject_unknown<K>(
+        &mut self,
+        reference: impl Fn(&T) -> Option<K>,
+        known: &[K],
+        message: impl Fn(&K) -> String,
+    ) where
+        K: Eq,
+    {
+        self.reject(|_, row| {
+            reference(row)
+                .filter(|key| !known.contains(key))
+                .map(|key| message(&key))
+        });
+    }
+
+    fn reject(&mut self, mut check: impl FnMut(usize, &T) -> Option<String>) {
+        let mut rows = Vec::with_capacity(self.rows.len());
+        for (row, record) in self.rows.drain(..) {
+            match check(row, &record) {
+                Some(message) => self.errors.push(RowError { row, message }),
+                None => rows.push((row, record)),
+            }
+        }
+        self.rows = rows;
+        self.errors.sort_by_key(|error| error.row);
+    }
+}
+
+/// Deserializes and validates the rows of a file
+pub fn parse<T>(format: ImportFormat, body: &[u8]) -> ParsedRows<T>
+where
+    T: DeserializeOwned + Validate,
+{
+    let records: Vec<Result<T, String>> = match format {
+        ImportFormat::Csv => csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(body)
+            .deserialize()
+            .map(|record| record.map_err(|e| e.to_string()))
+            .collect(),
+        ImportFormat::Ndjson => body
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+            .map(|line| serde_json::from_slice(line).map_err(|e| e.to_string()))
+            .collect(),
+    };
+
+    let mut rows = Vec::with_capacity(records.len());
+    let mut errors = Vec::new();
+    for (index, record) in records.into_iter().enumerate() {
+        let row = index + 1;
+        match record.and_then(|record| record.validate().map(|_| record).map_err(|e| invalid_fields(&e))) {
+            Ok(record) => rows.push((row, record)),
+            Err(message) => errors.push(RowError { row, message }),
+        }
+    }
+
+    ParsedRows { rows, errors }
+}
+
+/// Runs the import jobs
+#[derive(Clone)]
+pub struct Importer {
+    pub repo: Arc<dyn ImportJobRepository>,
+    /// Rows inserted per transaction
+    pub batch_size: usize,
+}
+
+impl Importer {
+    /// Inserts the rows of a job batch after batch with `insert`, recording the progress of the
+    /// job, until they are all imported or a batch fails
+    pub async fn run<T, F, Fut>(&self, job_id: Uuid, rows: Vec<T>, insert: F) -> Result<(), AppError>
+    where
+        F: Fn(Vec<T>) -> Fut,
+        Fut: Future<Output = Result<u64, BatchError>>,
+    {
+        self.repo.start(job_id).await?;
+
+        let mut offset = 0;
+        let mut rows = rows.into_iter();
+        loop {
+            let batch: Vec<T> = rows.by_ref().take(self.batch_size).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let size = batch.len();
+
+            match insert(batch).await {
+                Ok(imported) => self.repo.progress(job_id, size as i32, imported as i32).await?,
+                Err(e) => {
+                    let row = offset + e.index + 1;
+                    warn!("Import {} failed at row {}: {}", job_id, row, e.error);
+                    return self
+                        .repo
+                        .fail(job_id, Some(row as i32), &format!("Row {}: {}", row, e.error))
+                        .await;
+                }
+            }
+            offset += size;
+        }
+
+        self.repo.complete(job_id).await
+    }
+}
+
+#[test]
+fn test_parse_csv() {
+    use northwind_traders::domain::customer::Customer;
+
+    let body = b"customer_id,company_name,contact_name,city\n\
+        NWIND,Northwind Traders, Nancy Davolio ,Seattle\n\
+        TOOLONGID,Traders,,\n\
+        EMPTY,,,\n\
+        SOUTH,South Traders,,\n";
+    let ParsedRows { rows, errors } = parse::<Customer>(ImportFormat::Csv, body);
+
+    assert_eq!(
+        vec![(1, "NWIND"), (4, "SOUTH")],
+        rows.iter()
+            .map(|(row, c)| (*row, c.customer_id.as_str()))
+            .collect::<Vec<_>>()
+    );
+    // Fields are trimmed and empty fields are missing values
+    assert_eq!(Some("Nancy Davolio"), rows[0].1.contact_name.as_deref());
+    assert_eq!(None, rows[1].1.contact_name);
+    assert_eq!(
+        vec![
+            RowError {
+                row: 2,
+                message: String::from("customer_id (length)")
+            },
+            RowError {
+                row: 3,
+                message: String::from("company_name (length)")
+            },
+        ],
+        errors
+    );
+}
+
+#[test]
+fn test_parse_ndjson() {
+    use northwind_user::domain::user::UserCreation;
+
+    let body = br#"{"lastname":"Doe","firstname":"John","email":"john@example.com","password":"00000000"}
+
+{"lastname":"Doe","firstname":"Jane","email":"jane","password":"0000"}
+{"lastname":"Doe"}
+"#;
+    let ParsedRows { rows, errors } = parse::<UserCreation>(ImportFormat::Ndjson, body);
+
+    assert_eq!(1, rows.len());
+    assert_eq!(2, errors.len());
+    assert_eq!(
+        RowError {
+            row: 2,
+            message: String::from("email (email), password (length)")
+        },
+        errors[0]
+    );
+    assert_eq!(3, errors[1].row);
+    assert!(errors[1].message.starts_with("missing field `firstname`"));
+}
+
+#[test]
+fn test_reject() {
+    use northwind_traders::domain::catalog::Product;
+
+    let body = b"product_id,product_name,category_id\n\
+        1,Chai,1\n\
+        80,Mate,\n\
+        81,,1\n\
+        80,Rooibos,1\n\
+        82,Sencha,9\n\
+        83,Genmaicha,1\n";
+    let mut rows = parse::<Product>(ImportFormat::Csv, body);
+    let mut ids = rows.values(|product| Some(product.product_id));
+    ids.sort_unstable();
+    assert_eq!(vec![1, 80, 82, 83], ids);
+
+    rows.reject_taken(
+        |product| product.product_id,
+        &[1],
+        |id| format!("Product {} already exists", id),
+    );
+    rows.reject_unknown(
+        |product| product.category_id,
+        &[1],
+        |id| format!("Unknown category {}", id),
+    );
+
+    assert_eq!(vec![2, 6], rows.rows.iter().map(|(row, _)| *row).collect::<Vec<_>>());
+    assert_eq!(
+        vec![
+            (1, "Product 1 already exists"),
+            (3, "product_name (length)"),
+            (4, "Duplicate of row 2"),
+            (5, "Unknown category 9"),
+        ],
+        rows.errors
+            .iter()
+            .map(|error| (error.row, error.message.as_str()))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_importer() {
+    use async_trait::async_trait;
+    use northwind_core::import::ImportJob;
+    use std::sync::Mutex;
+
+    /// Records the calls made to the repository, jobs being created elsewhere
+    #[derive(Default)]
+    struct Jobs(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl ImportJobRepository for Jobs {
+        async fn create(&self, _: &ImportJob) -> Result<(), AppError> {
+            Ok(())
+        }
+        async fn get_by_id(&self, _: Uuid) -> Result<Option<ImportJob>, AppError> {
+            Ok(Default::default())
+        }
+        async fn start(&self, _: Uuid) -> Result<(), AppError> {
+            self.0.lock().unwrap().push(String::from("start"));
+            Ok(())
+        }
+        async fn progress(&self, _: Uuid, processed_rows: i32, imported_rows: i32) -> Result<(), AppError> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("progress {} {}", processed_rows, imported_rows));
+            Ok(())
+        }
+        async fn complete(&self, _: Uuid) -> Result<(), AppError> {
+            self.0.lock().unwrap().push(String::from("complete"));
+            Ok(())
+        }
+        async fn fail(&self, _: Uuid, failed_row: Option<i32>, error: &str) -> Result<(), AppError> {
+            self.0.lock().unwrap().push(format!("fail {:?} {}", failed_row, error));
+            Ok(())
+        }
+    }
+
+    actix_web::rt::System::new("test").block_on(async move {
+        let jobs = Arc::new(Jobs::default());
+        let importer = Importer {
+            repo: jobs.clone(),
+            batch_size: 2,
+        };
+
+        importer
+            .run(Uuid::new_v4(), vec![1, 2, 3, 4, 5], |batch| async move {
+                Ok(batch.len() as u64)
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            vec!["start", "progress 2 2", "progress 2 2", "progress 1 1", "complete"],
+            jobs.0.lock().unwrap().drain(..).collect::<Vec<_>>()
+        );
+
+        // The job stops at the first failed batch, pointing at its failed row
+        importer
+            .run(Uuid::new_v4(), vec![1, 2, 3, 4, 5], |batch| async move {
+                match batch.iter().position(|row| *row == 4) {
+                    Some(index) => Err(BatchError {
+                        index,
+                        error: AppError::Conflict {
+                            message: String::from("Customer 4 already exists"),
+                        },
+                    }),
+                    None => Ok(batch.len() as u64),
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            vec!["start", "progress 2 2", "fail Some(4) Row 4: Customer 4 already exists"],
+            jobs.0.lock().unwrap().drain(..).collect::<Vec<_>>()
+        );
+    });
+}
@@ -0,0 +1,128 @@
+//! Background jobs module
+//!
+//! Jobs run on the actix system of the server, between two runs they only hold a timer.
+
+use chrono::{Duration as ChronoDuration, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{IdempotencyConfig, OutboxConfig, RateLimitConfig, UsersConfig};
+use crate::webhooks::WebhookSender;
+use northwind_core::audit::AuditContext;
+use northwind_core::idempotency::IdempotencyRepository;
+use northwind_core::outbox::{OutboxRelay, OutboxRepository};
+use northwind_core::rate_limit::RateLimitStore;
+use northwind_user::domain::user_repository::UserRepository;
+
+/// Periodically erases the users deleted more than `purge_after_days` days ago, if set
+pub fn spawn_user_purge(user_repo: Arc<dyn UserRepository>, config: &UsersConfig) {
+    if config.purge_after_days == 0 {
+        return;
+    }
+
+    let retention = ChronoDuration::days(i64::from(config.purge_after_days));
+    let interval = Duration::from_secs(config.purge_interval);
+
+    actix_web::rt::spawn(async move {
+        loop {
+            let deleted_before = (Utc::now() - retention).naive_utc();
+            match user_repo.purge(deleted_before, &AuditContext::default()).await {
+                Ok(0) => {}
+                Ok(count) => info!("Purged {} users deleted before {}", count, deleted_before),
+                Err(e) => error!("Deleted users purge failed: {}", e),
+            }
+
+            actix_web::rt::time::delay_for(interval).await;
+        }
+    });
+}
+
+/// Periodically deletes the expired idempotency keys
+pub fn spawn_idempotency_purge(idempotency_repo: Arc<dyn IdempotencyRepository>, config: &IdempotencyConfig) {
+    let interval = Duration::from_secs(config.purge_interval);
+
+    actix_web::rt::spawn(async move {
+        loop {
+            match idempotency_repo.purge_expired().await {
+                Ok(0) => {}
+                Ok(count) => info!("Purged {} expired idempotency keys", count),
+                Err(e) => error!("Idempotency keys purge failed: {}", e),
+            }
+
+            actix_web::rt::time::delay_for(interval).await;
+        }
+    });
+}
+
+/// Periodically forgets the rate limiting counters of the clients whose quota is fully available
+pub fn spawn_rate_limit_purge(store: Arc<dyn RateLimitStore>, config: &RateLimitConfig) {
+    let interval = Duration::from_secs(config.purge_interval);
+
+    actix_web::rt::spawn(async move {
+        loop {
+            actix_web::rt::time::delay_for(interval).await;
+
+            if let Err(e) = store.purge(Utc::now().timestamp_millis()).await {
+                error!("Rate limiting counters purge failed: {}", e);
+            }
+        }
+    });
+}
+
+/// Publishes the events of the outbox, batch after batch until it is empty, then checks it
+/// every `poll_interval` seconds
+pub fn spawn_outbox_relay(relay: OutboxRelay, config: &OutboxConfig) {
+    let interval = Duration::from_secs(config.poll_interval);
+
+    actix_web::rt::spawn(async move {
+        loop {
+            match relay.run().await {
+                Ok(report) if (report.published + report.failed) as i64 == relay.batch_size => continue,
+                Ok(_) => {}
+                Err(e) => error!("Outbox relay failed: {}", e),
+            }
+
+            actix_web::rt::time::delay_for(interval).await;
+        }
+    });
+}
+
+/// Periodically deletes the events published more than `retention_days` days ago, if set
+pub fn spawn_outbox_purge(outbox_repo: Arc<dyn OutboxRepository>, config: &OutboxConfig) {
+    if config.retention_days == 0 {
+        return;
+    }
+
+    let retention = ChronoDuration::days(i64::from(config.retention_days));
+    let interval = Duration::from_secs(config.purge_interval);
+
+    actix_web::rt::spawn(async move {
+        loop {
+            match outbox_repo.purge_published((Utc::now() - retention).naive_utc()).await {
+                Ok(0) => {}
+                Ok(count) => info!("Purged {} published outbox events", count),
+                Err(e) => error!("Outbox purge failed: {}", e),
+            }
+
+            actix_web::rt::time::delay_for(interval).await;
+        }
+    });
+}
+
+/// Sends the pending webhook deliveries, batch after batch until there are none, then checks
+/// them every `poll_interval` seconds
+pub fn spawn_webhook_sender(sender: WebhookSender) {
+    let interval = Duration::from_secs(sender.config.poll_interval);
+
+    actix_web::rt::spawn(async move {
+        loop {
+            match sender.run().await {
+                Ok(count) if count as i64 == sender.config.batch_size => continue,
+                Ok(_) => {}
+                Err(e) => error!("Webhook deliveries failed: {}", e),
+            }
+
+            actix_web::rt::time::delay_for(interval).await;
+        }
+    });
+}
@@ -1,23 +1,53 @@
-use actix_cors::Cors;
-use actix_web::middleware::{errhandlers::ErrorHandlers, Logger};
+use actix_web::middleware::errhandlers::ErrorHandlers;
 use actix_web::{http, web, App, HttpServer};
 use actix_web_prom::PrometheusMetrics;
 use color_eyre::Result;
 use sqlx::{Pool, Postgres};
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::config::Config;
-use northwind_user::services::jwt_processor_impl::JwtProcessorImpl;
-use northwind_user::AppState;
+use crate::config::{Config, OutboxSinkKind, RateLimitStoreKind};
+use northwind_core::audit::{AuditRepository, AuditRepositoryImpl};
+use northwind_core::idempotency::{IdempotencyRepository, IdempotencyRepositoryImpl};
+use northwind_core::import::{ImportJobRepository, ImportJobRepositoryImpl};
+use northwind_core::outbox::{EventSink, FanoutSink, OutboxRelay, OutboxRepository, OutboxRepositoryImpl};
+use northwind_core::rate_limit::{MemoryRateLimitStore, PgRateLimitStore, RateLimitStore};
+use northwind_traders::domain::catalog_repository::CatalogRepository;
+use northwind_traders::domain::customer_repository::CustomerRepository;
+use northwind_traders::domain::report_repository::ReportRepository;
+use northwind_traders::repositories::catalog_repository_impl::CatalogRepositoryImpl;
+use northwind_traders::repositories::customer_repository_impl::CustomerRepositoryImpl;
+use northwind_traders::repositories::employee_repository_impl::EmployeeRepositoryImpl;
+use northwind_traders::repositories::order_repository_impl::OrderRepositoryImpl;
+use northwind_traders::repositories::report_repository_impl::ReportRepositoryImpl;
 use northwind_user::domain::jwt_processor::JwtProcessor;
 use northwind_user::domain::user_repository::UserRepository;
 use northwind_user::repositories::user_repository_impl::UserRepositoryImpl;
+use northwind_user::services::jwt_processor_impl::JwtProcessorImpl;
+use northwind_user::AppState;
+use northwind_webhooks::domain::webhook_repository::WebhookRepository;
+use northwind_webhooks::repositories::webhook_repository_impl::WebhookRepositoryImpl;
+use northwind_webhooks::services::subscriptions_sink::SubscriptionsSink;
 
 pub mod config;
+pub mod db;
 pub mod errors;
+pub mod etag;
+pub mod events;
+pub mod export;
+pub mod graphql;
+pub mod grpc;
 pub mod handlers;
+pub mod import;
+pub mod jobs;
 mod logger;
+pub mod metrics;
 pub mod middlewares;
+pub mod outbox;
+pub mod patch;
+pub mod telemetry;
+pub mod tls;
+pub mod webhooks;
 
 extern crate chrono;
 extern crate serde;
@@ -26,9 +56,26 @@ extern crate serde;
 extern crate log;
 
 pub async fn run(settings: Config, db_pool: Pool<Postgres>) -> Result<()> {
-    // Logger
-    // ------
-    logger::init(settings.rust_log);
+    // Logger and telemetry
+    // --------------------
+    let telemetry = telemetry::Telemetry::init(&settings.telemetry)?;
+    logger::init(&settings.logging, telemetry.tracer())?;
+
+    // Prometheus
+    // ----------
+    let prometheus = PrometheusMetrics::new("api", None, None);
+    let registry = prometheus.registry.clone();
+    let app_metrics = metrics::Metrics::new("api")?;
+    app_metrics.register(&registry)?;
+    metrics::register_db_pool(
+        &registry,
+        "api",
+        db_pool.clone(),
+        settings.database.max_connections,
+        &app_metrics,
+    )?;
+    let app_metrics_data = web::Data::new(app_metrics.clone());
+    let metrics_config = settings.metrics.clone();
 
     // Init application state
     // ----------------------
@@ -37,36 +84,198 @@ pub async fn run(settings: Config, db_pool: Pool<Postgres>) -> Result<()> {
     let jwt_processor: Arc<dyn JwtProcessor> = Arc::new(JwtProcessorImpl {});
     let jwt_processor_data = web::Data::from(jwt_processor.clone());
 
-    let user_repo: Arc<dyn UserRepository> = Arc::new(UserRepositoryImpl{pool: a_db_pool.clone()});
+    let user_repo: Arc<dyn UserRepository> = Arc::new(metrics::MeteredUserRepository::new(
+        Arc::new(UserRepositoryImpl {
+            pool: a_db_pool.clone(),
+        }),
+        app_metrics.clone(),
+    ));
     let user_repo_data = web::Data::from(user_repo.clone());
+    jobs::spawn_user_purge(user_repo.clone(), &settings.users);
+
+    let audit_repo: Arc<dyn AuditRepository> = Arc::new(AuditRepositoryImpl {
+        pool: a_db_pool.clone(),
+    });
+    let audit_repo_data = web::Data::from(audit_repo);
+
+    let customer_repo: Arc<dyn CustomerRepository> = Arc::new(CustomerRepositoryImpl {
+        pool: a_db_pool.clone(),
+    });
+    let customer_repo_data = web::Data::from(customer_repo.clone());
+    let catalog_repo: Arc<dyn CatalogRepository> = Arc::new(CatalogRepositoryImpl {
+        pool: a_db_pool.clone(),
+    });
+    let catalog_repo_data = web::Data::from(catalog_repo.clone());
+
+    let import_job_repo: Arc<dyn ImportJobRepository> = Arc::new(ImportJobRepositoryImpl {
+        pool: a_db_pool.clone(),
+    });
+    let importer_data = web::Data::new(import::Importer {
+        repo: import_job_repo,
+        batch_size: settings.import.batch_size,
+    });
+    let import_max_size = settings.import.max_size;
+
+    let report_repo: Arc<dyn ReportRepository> = Arc::new(ReportRepositoryImpl {
+        pool: a_db_pool.clone(),
+    });
+    let report_repo_data = web::Data::from(report_repo);
+
+    let idempotency_repo: Arc<dyn IdempotencyRepository> = Arc::new(IdempotencyRepositoryImpl {
+        pool: a_db_pool.clone(),
+    });
+    jobs::spawn_idempotency_purge(idempotency_repo.clone(), &settings.idempotency);
+    let idempotency = middlewares::idempotency::Idempotency {
+        repo: idempotency_repo,
+        ttl: chrono::Duration::seconds(settings.idempotency.ttl as i64),
+        lock_timeout: chrono::Duration::seconds(settings.idempotency.lock_timeout as i64),
+        // Imports are the largest bodies of the wrapped routes, the others being limited to the
+        // default payload size of 256 kB at most
+        max_body_size: import_max_size.max(256 * 1024),
+    };
+
+    // Also sent with the deliveries of the events caused by the requests
+    let request_id = middlewares::request_id::RequestIdService::new(&settings.server.request_id)?;
+
+    let webhook_repo: Arc<dyn WebhookRepository> = Arc::new(WebhookRepositoryImpl {
+        pool: a_db_pool.clone(),
+    });
+    let webhook_repo_data = web::Data::from(webhook_repo.clone());
+    if settings.webhooks.enabled {
+        jobs::spawn_webhook_sender(webhooks::WebhookSender {
+            repo: webhook_repo.clone(),
+            config: settings.webhooks.clone(),
+            request_id_header: request_id.header().clone(),
+        });
+    }
+
+    if settings.outbox.enabled {
+        let outbox_repo: Arc<dyn OutboxRepository> = Arc::new(OutboxRepositoryImpl {
+            pool: a_db_pool.clone(),
+        });
+        let mut sinks: Vec<Arc<dyn EventSink>> = match settings.outbox.sink {
+            OutboxSinkKind::None => Vec::new(),
+            OutboxSinkKind::Stdout => vec![Arc::new(outbox::StdoutSink)],
+            OutboxSinkKind::Webhook => vec![Arc::new(outbox::WebhookSink {
+                url: settings.outbox.webhook_url.clone(),
+                timeout: Duration::from_secs(settings.outbox.webhook_timeout),
+                request_id_header: request_id.header().clone(),
+            })],
+        };
+        if settings.webhooks.enabled {
+            sinks.push(Arc::new(SubscriptionsSink {
+                repo: webhook_repo.clone(),
+            }));
+        }
+        let relay = OutboxRelay {
+            repo: outbox_repo.clone(),
+            sink: Arc::new(FanoutSink(sinks)),
+            batch_size: settings.outbox.batch_size,
+            lease: chrono::Duration::seconds(settings.outbox.lease as i64),
+            min_backoff: chrono::Duration::seconds(settings.outbox.min_backoff as i64),
+            max_backoff: chrono::Duration::seconds(settings.outbox.max_backoff as i64),
+        };
+        jobs::spawn_outbox_relay(relay, &settings.outbox);
+        jobs::spawn_outbox_purge(outbox_repo, &settings.outbox);
+    }
+
+    let rate_limit_store: Arc<dyn RateLimitStore> = match settings.rate_limit.store {
+        RateLimitStoreKind::Memory => Arc::new(MemoryRateLimitStore::default()),
+        RateLimitStoreKind::Postgres => Arc::new(PgRateLimitStore {
+            pool: a_db_pool.clone(),
+        }),
+    };
+    if settings.rate_limit.enabled {
+        jobs::spawn_rate_limit_purge(rate_limit_store.clone(), &settings.rate_limit);
+    }
+    let grpc_rate_limit = grpc::rate_limit::RateLimitLayer::new(&settings.rate_limit, rate_limit_store.clone());
+    let rate_limiter =
+        middlewares::rate_limit::RateLimiter::new(&settings.rate_limit, rate_limit_store, jwt_processor.clone())?;
+
+    let schema = graphql::schema(
+        &settings.graphql,
+        graphql::Repositories {
+            users: user_repo.clone(),
+            catalog: catalog_repo,
+            customers: customer_repo,
+            orders: Arc::new(OrderRepositoryImpl {
+                pool: a_db_pool.clone(),
+            }),
+            employees: Arc::new(EmployeeRepositoryImpl {
+                pool: a_db_pool.clone(),
+            }),
+        },
+    );
+    let schema_data = web::Data::new(schema);
+    let graphql_playground = settings.graphql.playground;
+
+    let events_data = if settings.events.enabled {
+        let broadcaster = events::EventBroadcaster::new(settings.events.buffer);
+        events::spawn_listener(db_pool.clone(), broadcaster.clone(), app_metrics.clone());
+        broadcaster.spawn_keep_alive(Duration::from_secs(settings.events.keep_alive));
+        Some(web::Data::new(broadcaster))
+    } else {
+        None
+    };
 
     let data = AppState {
-        jwt_secret_key: settings.jwt_secret_key.clone(),
-        jwt_lifetime: settings.jwt_lifetime,
+        jwt_secret_key: settings.auth.jwt_secret_key.clone(),
+        jwt_previous_keys: settings.auth.jwt_previous_keys.clone(),
+        jwt_lifetime: settings.auth.jwt_lifetime,
     };
 
-    // Prometheus
-    // ----------
-    let prometheus = PrometheusMetrics::new("api", Some("/metrics"), None);
+    // gRPC services, served until the HTTP servers stop
+    let _grpc_server = if settings.grpc.address.is_empty() {
+        None
+    } else {
+        let users = grpc::users::UserGrpcService {
+            user_repo: user_repo.clone(),
+            jwt_processor: jwt_processor.clone(),
+            app_state: data.clone(),
+            metrics: app_metrics.clone(),
+            request_id: request_id.clone(),
+        };
+        Some(grpc::serve(
+            &settings.grpc.address,
+            &settings.server.tls,
+            grpc_rate_limit,
+            users,
+        )?)
+    };
+
+    let cors = settings.cors.clone();
+    let audit_config = handlers::audit::AuditConfig {
+        trust_forwarded_for: settings.server.trust_forwarded_for,
+    };
 
     // Start server
     // ------------
-    HttpServer::new(move || {
+    let server_registry = registry.clone();
+    let server_metrics_config = metrics_config.clone();
+    let server = HttpServer::new(move || {
         // Middlewares
         // -----------
         let auth_middleware = crate::middlewares::auth::Authentication {
             jwt_processor: jwt_processor.clone(),
             user_repo: user_repo.clone(),
+            metrics: app_metrics.clone(),
+            query_token: false,
         };
 
-        App::new()
+        let mut app = App::new()
             .data(db_pool.clone())
             .data(data.clone())
             .app_data(jwt_processor_data.clone())
             .app_data(user_repo_data.clone())
-            .wrap(middlewares::request_id::RequestIdService)
+            .app_data(audit_repo_data.clone())
+            .app_data(webhook_repo_data.clone())
+            .app_data(report_repo_data.clone())
+            .app_data(app_metrics_data.clone())
+            .app_data(schema_data.clone())
+            .app_data(audit_config.clone())
             .wrap(middlewares::timer::Timer)
-            .wrap(Logger::new("%s | %r | %Ts | %{User-Agent}i | %a | %{x-request-id}o"))
+            .wrap(rate_limiter.clone())
+            .wrap(middlewares::request_logger::RequestLogger)
             .wrap(prometheus.clone())
             .wrap(
                 ErrorHandlers::new()
@@ -77,27 +286,116 @@ pub async fn run(settings: Config, db_pool: Pool<Postgres>) -> Result<()> {
                     .handler(http::StatusCode::SERVICE_UNAVAILABLE, handlers::errors::render_503)
                     .handler(http::StatusCode::GATEWAY_TIMEOUT, handlers::errors::render_504),
             )
-            .wrap(
-                Cors::default()
-                    // .allowed_origin("*")
-                    .allowed_methods(vec!["GET", "POST", "PATCH", "PUT", "DELETE", "HEAD", "OPTIONS"])
-                    .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
-                    .allowed_header(http::header::CONTENT_TYPE)
-                    .supports_credentials()
-                    .max_age(3600),
+            .wrap(middlewares::cors::cors(&cors))
+            .wrap(request_id.clone())
+            .configure(handlers::web::init_routes);
+
+        if server_metrics_config.address.is_empty() {
+            app = app
+                .data(server_registry.clone())
+                .data(server_metrics_config.clone())
+                .route(&server_metrics_config.path, web::get().to(handlers::web::metrics));
+        }
+
+        if graphql_playground {
+            app = app.route("/graphiql", web::get().to(handlers::graphql::graphiql));
+        }
+
+        let mut v1 = web::scope("/v1")
+            .wrap(idempotency.clone())
+            .configure(handlers::users::init_routes)
+            .service(
+                web::scope("/users")
+                    .wrap(auth_middleware.clone())
+                    .configure(handlers::users::init_auth_routes),
             )
-            .configure(handlers::web::init_routes)
             .service(
-                web::scope("/v1").configure(handlers::users::init_routes).service(
-                    web::scope("/users")
-                        .wrap(auth_middleware)
-                        .configure(handlers::users::init_auth_routes),
-                ),
+                web::scope("/audit")
+                    .wrap(auth_middleware.clone())
+                    .configure(handlers::audit::init_auth_routes),
             )
-    })
-    .bind(format!("{}:{}", settings.server_url, settings.server_port))?
-    .run()
-    .await?;
+            .service(
+                web::scope("/import")
+                    .app_data(importer_data.clone())
+                    .app_data(customer_repo_data.clone())
+                    .app_data(catalog_repo_data.clone())
+                    .app_data(web::PayloadConfig::new(import_max_size))
+                    .wrap(auth_middleware.clone())
+                    .configure(handlers::import::init_auth_routes),
+            )
+            .service(
+                web::scope("/reports")
+                    .wrap(auth_middleware.clone())
+                    .configure(handlers::reports::init_auth_routes),
+            )
+            .service(
+                web::scope("/webhooks")
+                    .wrap(auth_middleware.clone())
+                    .configure(handlers::webhooks::init_auth_routes),
+            );
+
+        if let Some(events_data) = &events_data {
+            v1 = v1.service(
+                web::scope("/events")
+                    .app_data(events_data.clone())
+                    .wrap(crate::middlewares::auth::Authentication {
+                        query_token: true,
+                        ..auth_middleware.clone()
+                    })
+                    .configure(handlers::events::init_auth_routes),
+            );
+        }
+
+        app.service(
+            web::scope("/graphql")
+                .wrap(auth_middleware)
+                .configure(handlers::graphql::init_auth_routes),
+        )
+        .service(v1)
+    });
+
+    let address = format!("{}:{}", settings.server.host, settings.server.port);
+    let tls = &settings.server.tls;
+    let mut servers = Vec::new();
+
+    if !tls.enabled {
+        servers.push(server.bind(address)?.run());
+    } else {
+        let resolver = Arc::new(tls::CertificateResolver::load(tls)?);
+        if tls.reload_interval > 0 {
+            resolver.watch(Duration::from_secs(tls.reload_interval));
+        }
+        servers.push(server.bind_rustls(address, tls::server_config(resolver))?.run());
+
+        if tls.redirect_http_port != 0 {
+            let server_config = settings.server.clone();
+            let redirect = HttpServer::new(move || {
+                App::new()
+                    .data(server_config.clone())
+                    .default_service(web::route().to(handlers::web::https_redirect))
+            })
+            .bind(format!("{}:{}", settings.server.host, tls.redirect_http_port))?
+            .run();
+            servers.push(redirect);
+        }
+    }
+
+    // Metrics served apart from the API
+    if !metrics_config.address.is_empty() {
+        let address = metrics_config.address.clone();
+        let metrics_server = HttpServer::new(move || {
+            App::new()
+                .data(registry.clone())
+                .data(metrics_config.clone())
+                .route(&metrics_config.path, web::get().to(handlers::web::metrics))
+        })
+        .workers(1)
+        .bind(address)?
+        .run();
+        servers.push(metrics_server);
+    }
+
+    futures::future::try_join_all(servers).await?;
 
     Ok(())
 }
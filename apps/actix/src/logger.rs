@@ -1,51 +1,182 @@
 //! Logger module for customize logs
+//!
+//! Logs are emitted through `tracing`, records of the `log` crate (actix, sqlx...) being
+//! forwarded to it. In JSON format, each line is a flat object holding the event fields
+//! along with the fields of its enclosing spans (request_id, trace_id, user_id, method, path...).
 
-use chrono::Local;
-use env_logger::fmt::Color;
-use env_logger::Builder;
-use log::Level;
-use std::io::Write;
-
-/// Initialize logger
-pub fn init(level: String) {
-    let level = match &*level {
-        "trace" => Level::Trace,
-        "debug" => Level::Debug,
-        "info" => Level::Info,
-        "warn" => Level::Warn,
-        "Error" => Level::Error,
-        &_ => Level::Error,
-    };
-
-    Builder::new()
-        .format(move |buf, record| {
-            let mut level_style = buf.style();
-
-            let (color, level_spaces) = match record.level() {
-                Level::Trace => (Color::White, " "),
-                Level::Debug => (Color::Green, " "),
-                Level::Info => (Color::Blue, "  "),
-                Level::Warn => (Color::Yellow, "  "),
-                Level::Error => (Color::Red, " "),
-            };
-
-            level_style.set_color(color).set_bold(true);
-            let line = match record.line() {
-                Some(line) => format!(":{}", line),
-                None => "".to_owned(),
-            };
-
-            writeln!(
-                buf,
-                "{} [{}]{}{}{} | {}",
-                Local::now().format("%Y-%m-%dT%H:%M:%S"),
-                level_style.value(record.level()),
-                level_spaces,
-                record.target(),
-                line,
-                record.args()
+use chrono::{SecondsFormat, Utc};
+use color_eyre::Result;
+use eyre::eyre;
+use opentelemetry::sdk::trace::Tracer;
+use serde_json::{Map, Value};
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, JsonFields};
+use tracing_subscriber::fmt::{FmtContext, FormattedFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{LogFormat, LoggingConfig};
+
+/// Initialize logger, spans being also exported through the OpenTelemetry tracer if any
+pub fn init(config: &LoggingConfig, tracer: Option<Tracer>) -> Result<()> {
+    let filter = EnvFilter::try_new(&config.level)?;
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer)));
+
+    match config.format {
+        LogFormat::Text => registry.with(tracing_subscriber::fmt::layer()).try_init(),
+        LogFormat::Json => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .fmt_fields(JsonFields::new())
+                    .event_format(JsonFormat),
             )
-        })
-        .filter(None, level.to_level_filter())
-        .init();
+            .try_init(),
+    }
+    .map_err(|e| eyre!("initializing logger: {}", e))
+}
+
+/// Formats events as flat JSON objects
+pub struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, writer: &mut dyn fmt::Write, event: &Event<'_>) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut object = Map::new();
+
+        object.insert(
+            String::from("timestamp"),
+            Value::from(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+        );
+        object.insert(
+            String::from("level"),
+            Value::from(metadata.level().to_string().to_lowercase()),
+        );
+
+        // Span fields, from the root span so that the innermost value wins
+        if let Some(span) = ctx.lookup_current() {
+            for span in span.scope().from_root() {
+                let extensions = span.extensions();
+                if let Some(fields) = extensions.get::<FormattedFields<N>>() {
+                    if let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(&fields.fields) {
+                        // `otel.*` fields only name and qualify the exported spans
+                        object.extend(fields.into_iter().filter(|(key, _)| !key.starts_with("otel.")));
+                    }
+                }
+            }
+        }
+
+        let mut visitor = JsonVisitor {
+            object: &mut object,
+            log_target: None,
+        };
+        event.record(&mut visitor);
+
+        // Records forwarded from the `log` crate carry their original target as a field
+        let target = visitor
+            .log_target
+            .take()
+            .unwrap_or_else(|| metadata.target().to_owned());
+        object.insert(String::from("target"), Value::from(target));
+
+        writeln!(writer, "{}", Value::Object(object))
+    }
+}
+
+/// Collects event fields into a JSON object
+struct JsonVisitor<'a> {
+    object: &'a mut Map<String, Value>,
+    log_target: Option<String>,
+}
+
+impl<'a> JsonVisitor<'a> {
+    fn insert(&mut self, field: &Field, value: Value) {
+        match field.name() {
+            "log.target" => self.log_target = value.as_str().map(|s| s.to_owned()),
+            name if name.starts_with("log.") => {}
+            name => {
+                self.object.insert(name.to_owned(), value);
+            }
+        }
+    }
+}
+
+impl<'a> Visit for JsonVisitor<'a> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, Value::from(format!("{:?}", value)));
+    }
+}
+
+#[test]
+fn test_json_format_flattens_span_fields() {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let subscriber = tracing_subscriber::registry().with(
+        tracing_subscriber::fmt::layer()
+            .fmt_fields(JsonFields::new())
+            .event_format(JsonFormat)
+            .with_writer(move || writer.clone()),
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("request", request_id = "42", user_id = tracing::field::Empty);
+        let _entered = span.enter();
+        span.record("user_id", "user");
+        tracing::warn!(status = 404u64, "request completed");
+    });
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let line: Value = serde_json::from_str(output.trim()).unwrap();
+    assert_eq!("warn", line["level"]);
+    assert_eq!("request completed", line["message"]);
+    assert_eq!("42", line["request_id"]);
+    assert_eq!("user", line["user_id"]);
+    assert_eq!(404, line["status"]);
+    assert_eq!("northwind_actix::logger", line["target"]);
+    assert!(line["timestamp"].is_string());
 }
@@ -1,40 +1,46 @@
 use color_eyre::Result;
-use eyre::ErrReport;
-use northwind_actix::config::Config;
+use northwind_actix::config::{Config, ConfigArgs};
+use northwind_actix::db::{configure_db_pool, MIGRATOR};
 use northwind_actix::run;
-use sqlx::postgres::PgPoolOptions;
-use sqlx::PgPool;
-
-const DB_POOL_MAX_CONNECTIONS: u32 = 5;
-
-async fn configure_with_db_url(db_uri: &str) -> Result<PgPool> {
-    PgPoolOptions::new()
-        .max_connections(DB_POOL_MAX_CONNECTIONS)
-        .connect_timeout(std::time::Duration::from_secs(10))
-        // .connect_lazy(&db_uri)
-        .connect(&db_uri)
-        .await
-        .map_err(|e| -> ErrReport { e.into() })
+use structopt::StructOpt;
+
+/// Northwind API server
+#[derive(Debug, StructOpt)]
+#[structopt(name = "northwind-actix")]
+struct Cli {
+    #[structopt(flatten)]
+    config: ConfigArgs,
+
+    /// Prints the effective configuration, secrets redacted, then exits
+    #[structopt(long)]
+    print_config: bool,
 }
 
 #[actix_web::main]
 async fn main() -> Result<()> {
-    // Load configuration
-    // ------------------
-    let settings = Config::from_env()?;
-
     // Install Color Eyre
     // ------------------
     color_eyre::install()?;
 
+    // Load configuration
+    // ------------------
+    let cli = Cli::from_args();
+    let settings = Config::build(&cli.config)?;
+
+    if cli.print_config {
+        print!("{}", settings.to_toml()?);
+        return Ok(());
+    }
+    settings.check()?;
+
     // Initialization Postgres Pool
     // ----------------------------
-    let db_pool = configure_with_db_url(&settings.database_url).await?;
+    let db_pool = configure_db_pool(&settings.database).await?;
 
     // Runs migrations
     // ---------------
-    if settings.database_auto_migration {
-        sqlx::migrate!("./../../migrations").run(&db_pool).await?;
+    if settings.database.auto_migration {
+        MIGRATOR.run(&db_pool).await?;
     }
 
     run(settings, db_pool).await
@@ -0,0 +1,403 @@
+//! Prometheus metrics module
+//!
+//! Business and infrastructure metrics registered next to the HTTP metrics of
+//! `actix-web-prom`: logins, registrations, orders placed and their revenue,
+//! repository latency, JWT validation failures and database pool state.
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use futures::stream::BoxStream;
+use futures::Future;
+use northwind_core::audit::AuditContext;
+use northwind_core::concurrency::ExpectedVersion;
+use northwind_core::errors::AppError;
+use northwind_core::import::BatchError;
+use northwind_user::domain::user::User;
+use northwind_user::domain::user_repository::UserRepository;
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{Counter, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry};
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Application metrics
+#[derive(Clone)]
+pub struct Metrics {
+    logins: IntCounterVec,
+    registrations: IntCounter,
+    orders_placed: IntCounter,
+    order_revenue: Counter,
+    repository_query_duration: HistogramVec,
+    jwt_validation_failures: IntCounterVec,
+    /// Repository calls being awaited, read by `DbPoolCollector`
+    queries_in_flight: Arc<AtomicI64>,
+}
+
+impl Metrics {
+    pub fn new(namespace: &str) -> prometheus::Result<Self> {
+        let opts = |name: &str, help: &str| Opts::new(name, help).namespace(namespace);
+
+        Ok(Self {
+            logins: IntCounterVec::new(
+                opts("logins_total", "Number of login attempts by result and failure reason"),
+                &["result", "reason"],
+            )?,
+            registrations: IntCounter::with_opts(opts("user_registrations_total", "Number of registered users"))?,
+            orders_placed: IntCounter::with_opts(opts("orders_placed_total", "Number of placed orders"))?,
+            order_revenue: Counter::with_opts(opts(
+                "order_revenue_total",
+                "Total of the placed orders, discounts deducted",
+            ))?,
+            repository_query_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "repository_query_duration_seconds",
+                    "Duration of the repository methods",
+                )
+                .namespace(namespace),
+                &["repository", "method"],
+            )?,
+            jwt_validation_failures: IntCounterVec::new(
+                opts("jwt_validation_failures_total", "Number of rejected JWT by failure type"),
+                &["reason"],
+            )?,
+            queries_in_flight: Arc::new(AtomicI64::new(0)),
+        })
+    }
+
+    /// Registers the metrics, the database pool gauges being registered by `register_db_pool`
+    pub fn register(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.logins.clone()))?;
+        registry.register(Box::new(self.registrations.clone()))?;
+        registry.register(Box::new(self.orders_placed.clone()))?;
+        registry.register(Box::new(self.order_revenue.clone()))?;
+        registry.register(Box::new(self.repository_query_duration.clone()))?;
+        registry.register(Box::new(self.jwt_validation_failures.clone()))
+    }
+
+    pub fn login_succeeded(&self) {
+        self.logins.with_label_values(&["success", ""]).inc();
+    }
+
+    pub fn login_failed(&self, reason: &str) {
+        self.logins.with_label_values(&["failure", reason]).inc();
+    }
+
+    pub fn user_registered(&self) {
+        self.registrations.inc();
+    }
+
+    pub fn order_placed(&self, revenue: f64) {
+        self.orders_placed.inc();
+        self.order_revenue.inc_by(revenue.max(0.0));
+    }
+
+    pub fn jwt_validation_failed(&self, reason: &str) {
+        self.jwt_validation_failures.with_label_values(&[reason]).inc();
+    }
+
+    /// Awaits a repository call, recording its duration
+    pub async fn time_query<F: Future>(&self, repository: &str, method: &str, query: F) -> F::Output {
+        let _timer = self
+            .repository_query_duration
+            .with_label_values(&[repository, method])
+            .start_timer();
+        let _in_flight = InFlight::start(&self.queries_in_flight);
+
+        query.await
+    }
+}
+
+/// Counts a repository call as in flight until dropped, so that cancelled calls are not counted
+struct InFlight<'a>(&'a AtomicI64);
+
+impl<'a> InFlight<'a> {
+    fn start(count: &'a AtomicI64) -> Self {
+        count.fetch_add(1, Ordering::Relaxed);
+        Self(count)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns the failure type of a JWT which could not be parsed
+pub fn jwt_failure_reason(error: &(dyn std::error::Error + 'static)) -> &'static str {
+    use jsonwebtoken::errors::{Error, ErrorKind};
+
+    match error.downcast_ref::<Error>().map(|e| e.kind()) {
+        Some(ErrorKind::ExpiredSignature) => "expired",
+        Some(ErrorKind::ImmatureSignature) => "immature",
+        Some(ErrorKind::InvalidSignature) => "invalid_signature",
+        Some(ErrorKind::InvalidAlgorithm) => "invalid_algorithm",
+        _ => "malformed",
+    }
+}
+
+/// User repository recording the duration of each method
+pub struct MeteredUserRepository {
+    inner: Arc<dyn UserRepository>,
+    metrics: Metrics,
+}
+
+impl MeteredUserRepository {
+    pub fn new(inner: Arc<dyn UserRepository>, metrics: Metrics) -> Self {
+        Self { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl UserRepository for MeteredUserRepository {
+    async fn login(&self, email: String, password: String, audit: &AuditContext) -> Result<Option<User>, AppError> {
+        self.metrics
+            .time_query("user", "login", self.inner.login(email, password, audit))
+            .await
+    }
+
+    async fn create(&self, user: &mut User, audit: &AuditContext) -> Result<Option<u64>, AppError> {
+        self.metrics.time_query("user", "create", self.inner.create(user, audit)).await
+    }
+
+    async fn import(&self, users: &mut [User], audit: &AuditContext) -> Result<u64, BatchError> {
+        self.metrics
+            .time_query("user", "import", self.inner.import(users, audit))
+            .await
+    }
+
+    async fn get_all(&self) -> Result<Vec<User>, AppError> {
+        self.metrics.time_query("user", "get_all", self.inner.get_all()).await
+    }
+
+    /// Streams are consumed as they are sent, so they are not timed
+    fn stream_all(&self) -> BoxStream<'static, Result<User, AppError>> {
+        self.inner.stream_all()
+    }
+
+    async fn get_by_id(&self, id: Uuid) -> Result<Option<User>, AppError> {
+        self.metrics.time_query("user", "get_by_id", self.inner.get_by_id(id)).await
+    }
+
+    async fn get_by_email(&self, email: &str) -> Result<Option<User>, AppError> {
+        self.metrics
+            .time_query("user", "get_by_email", self.inner.get_by_email(email))
+            .await
+    }
+
+    async fn get_by_emails(&self, emails: &[String]) -> Result<Vec<User>, AppError> {
+        self.metrics
+            .time_query("user", "get_by_emails", self.inner.get_by_emails(emails))
+            .await
+    }
+
+    async fn delete(&self, id: Uuid, expected: &ExpectedVersion, audit: &AuditContext) -> Result<Option<u64>, AppError> {
+        self.metrics
+            .time_query("user", "delete", self.inner.delete(id, expected, audit))
+            .await
+    }
+
+    async fn update(
+        &self,
+        id: Uuid,
+        firstname: String,
+        lastname: String,
+        expected: &ExpectedVersion,
+        audit: &AuditContext,
+    ) -> Result<Option<u64>, AppError> {
+        self.metrics
+            .time_query("user", "update", self.inner.update(id, firstname, lastname, expected, audit))
+            .await
+    }
+
+    fn stream_deleted(&self) -> BoxStream<'static, Result<User, AppError>> {
+        self.inner.stream_deleted()
+    }
+
+    async fn get_deleted(&self) -> Result<Vec<User>, AppError> {
+        self.metrics.time_query("user", "get_deleted", self.inner.get_deleted()).await
+    }
+
+    async fn restore(&self, id: Uuid, audit: &AuditContext) -> Result<Option<u64>, AppError> {
+        self.metrics.time_query("user", "restore", self.inner.restore(id, audit)).await
+    }
+
+    async fn reset_password(&self, id: Uuid, password: String, audit: &AuditContext) -> Result<Option<u64>, AppError> {
+        self.metrics
+            .time_query("user", "reset_password", self.inner.reset_password(id, password, audit))
+            .await
+    }
+
+    async fn purge(&self, deleted_before: NaiveDateTime, audit: &AuditContext) -> Result<u64, AppError> {
+        self.metrics
+            .time_query("user", "purge", self.inner.purge(deleted_before, audit))
+            .await
+    }
+}
+
+/// Database pool gauges, refreshed from the pool at each scrape
+///
+/// sqlx 0.5 does not expose the tasks waiting for a connection, which are acquired inside sqlx
+/// from `&PgPool`. `db_pool_pending_acquires` approximates them as the repository calls in
+/// flight beyond the connections in use, calls holding one connection at most.
+pub struct DbPoolCollector {
+    pool: PgPool,
+    queries_in_flight: Arc<AtomicI64>,
+    size: IntGauge,
+    idle: IntGauge,
+    in_use: IntGauge,
+    pending: IntGauge,
+    max: IntGauge,
+}
+
+impl DbPoolCollector {
+    pub fn new(namespace: &str, pool: PgPool, max_connections: u32, metrics: &Metrics) -> prometheus::Result<Self> {
+        let gauge = |name: &str, help: &str| IntGauge::with_opts(Opts::new(name, help).namespace(namespace));
+
+        let max = gauge("db_pool_max_connections", "Maximum number of connections of the pool")?;
+        max.set(i64::from(max_connections));
+
+        Ok(Self {
+            pool,
+            queries_in_flight: metrics.queries_in_flight.clone(),
+            size: gauge("db_pool_connections", "Number of connections currently open")?,
+            idle: gauge("db_pool_idle_connections", "Number of idle connections")?,
+            in_use: gauge("db_pool_in_use_connections", "Number of connections currently in use")?,
+            pending: gauge(
+                "db_pool_pending_acquires",
+                "Approximate number of repository calls waiting for a connection",
+            )?,
+            max,
+        })
+    }
+}
+
+impl Collector for DbPoolCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        [&self.size, &self.idle, &self.in_use, &self.pending, &self.max]
+            .iter()
+            .flat_map(|g| g.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let size = i64::from(self.pool.size());
+        let idle = self.pool.num_idle() as i64;
+
+        self.size.set(size);
+        self.idle.set(idle);
+        self.in_use.set((size - idle).max(0));
+        self.pending
+            .set((self.queries_in_flight.load(Ordering::Relaxed) - self.in_use.get()).max(0));
+
+        [&self.size, &self.idle, &self.in_use, &self.pending, &self.max]
+            .iter()
+            .flat_map(|g| g.collect())
+            .collect()
+    }
+}
+
+/// Registers the database pool gauges
+pub fn register_db_pool(
+    registry: &Registry,
+    namespace: &str,
+    pool: PgPool,
+    max_connections: u32,
+    metrics: &Metrics,
+) -> prometheus::Result<()> {
+    registry.register(Box::new(DbPoolCollector::new(
+        namespace,
+        pool,
+        max_connections,
+        metrics,
+    )?))
+}
+
+#[test]
+fn test_db_pool_collector() {
+    let pool = PgPool::connect_lazy("postgres://postgres@localhost/northwind").unwrap();
+    let metrics = Metrics::new("api").unwrap();
+    let collector = DbPoolCollector::new("api", pool, 5, &metrics).unwrap();
+
+    let gauges: Vec<(String, f64)> = collector
+        .collect()
+        .iter()
+        .map(|family| {
+            (
+                family.get_name().to_owned(),
+                family.get_metric()[0].get_gauge().get_value(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            "api_db_pool_connections",
+            "api_db_pool_idle_connections",
+            "api_db_pool_in_use_connections",
+            "api_db_pool_pending_acquires",
+            "api_db_pool_max_connections",
+        ],
+        gauges.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>()
+    );
+    // The lazy pool may be opening a connection in the background
+    assert_eq!(gauges[0].1, gauges[1].1 + gauges[2].1);
+    assert!(gauges[0].1 <= 1.0);
+    assert_eq!(0.0, gauges[3].1);
+    assert_eq!(5.0, gauges[4].1);
+    assert_eq!(5, collector.desc().len());
+
+    // Calls awaited beyond the connections in use are waiting for one
+    futures::executor::block_on(async {
+        let (tx, rx) = futures::channel::oneshot::channel::<()>();
+        let calls = futures::future::join(
+            metrics.time_query("user", "get_all", async {
+                let _ = rx.await;
+            }),
+            metrics.time_query("user", "get_by_id", async {
+                let gauges = collector.collect();
+                let gauge = |index: usize| gauges[index].get_metric()[0].get_gauge().get_value();
+                assert_eq!(2.0 - gauge(2), gauge(3));
+                tx.send(()).unwrap();
+            }),
+        );
+        calls.await;
+    });
+    assert_eq!(0.0, collector.collect()[3].get_metric()[0].get_gauge().get_value());
+}
+
+#[test]
+fn test_metrics_rendering() {
+    use prometheus::Encoder;
+
+    let registry = Registry::new();
+    let metrics = Metrics::new("api").unwrap();
+    metrics.register(&registry).unwrap();
+
+    metrics.login_succeeded();
+    metrics.login_failed("invalid_credentials");
+    metrics.login_failed("invalid_credentials");
+    futures::executor::block_on(metrics.time_query("user", "get_all", async {}));
+    metrics.order_placed(440.0);
+    metrics.order_placed(1863.5);
+
+    let expired = jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::ExpiredSignature);
+    metrics.jwt_validation_failed(jwt_failure_reason(&expired));
+    let other = std::io::Error::other("not a JWT error");
+    metrics.jwt_validation_failed(jwt_failure_reason(&other));
+
+    let mut buffer = Vec::new();
+    prometheus::TextEncoder::new()
+        .encode(&registry.gather(), &mut buffer)
+        .unwrap();
+    let output = String::from_utf8(buffer).unwrap();
+
+    assert!(output.contains("api_logins_total{reason=\"\",result=\"success\"} 1"));
+    assert!(output.contains("api_logins_total{reason=\"invalid_credentials\",result=\"failure\"} 2"));
+    assert!(output.contains("api_orders_placed_total 2"));
+    assert!(output.contains("api_order_revenue_total 2303.5"));
+    assert!(output.contains("api_repository_query_duration_seconds_count{method=\"get_all\",repository=\"user\"} 1"));
+    assert!(output.contains("api_jwt_validation_failures_total{reason=\"expired\"} 1"));
+    assert!(output.contains("api_jwt_validation_failures_total{reason=\"malformed\"} 1"));
+}
@@ -2,15 +2,15 @@
 
 use actix_service::{Service, Transform};
 use actix_web::{
-    dev::{ServiceRequest, ServiceResponse},
+    dev::{Payload, ServiceRequest, ServiceResponse},
     http::Method,
     http::StatusCode,
     web::Data,
-    Error, HttpResponse,
+    Error, FromRequest, HttpMessage, HttpRequest, HttpResponse,
 };
 use color_eyre::Result;
 use futures::{
-    future::{ok, Ready},
+    future::{err, ok, Ready},
     Future,
 };
 use sqlx::PgPool;
@@ -18,16 +18,60 @@ use std::task::{Context, Poll};
 use std::{cell::RefCell, pin::Pin, rc::Rc};
 use uuid::Uuid;
 
+use crate::errors::ApiError;
+use crate::metrics::{jwt_failure_reason, Metrics};
+use crate::middlewares::request_id::RequestIdMessage;
 use crate::AppState;
 use northwind_user::domain::user_repository::UserRepository;
 use northwind_user::domain::jwt_processor::JwtProcessor;
-use northwind_core::errors::AppErrorMessage;
+use northwind_core::errors::{AppError, AppErrorMessage};
+use northwind_user::domain::user::ROLE_ADMIN;
 use std::sync::Arc;
 
+/// User authenticated by the `Authentication` middleware
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub id: Uuid,
+    pub role: String,
+}
+
+impl AuthenticatedUser {
+    pub fn is_admin(&self) -> bool {
+        self.role == ROLE_ADMIN
+    }
+
+    /// Fails with `Forbidden` unless the user is an administrator
+    pub fn require_admin(&self) -> Result<(), AppError> {
+        if self.is_admin() {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden)
+        }
+    }
+}
+
+/// Extractor of the authenticated user, only available behind the `Authentication` middleware
+impl FromRequest for AuthenticatedUser {
+    type Error = Error;
+    type Future = Ready<Result<AuthenticatedUser, Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        match req.extensions().get::<AuthenticatedUser>() {
+            Some(user) => ok(user.clone()),
+            None => err(ApiError::from(AppError::Unauthorized).into()),
+        }
+    }
+}
 
+#[derive(Clone)]
 pub struct Authentication {
     pub jwt_processor: Arc<dyn JwtProcessor>,
     pub user_repo: Arc<dyn UserRepository>,
+    pub metrics: Metrics,
+    /// Also reads the token from the `access_token` query parameter, for clients such as
+    /// `EventSource` which cannot send headers
+    pub query_token: bool,
 }
 
 impl<S: 'static, B> Transform<S> for Authentication
@@ -48,6 +92,8 @@ where
             service: Rc::new(RefCell::new(service)),
             jwt_processor: self.jwt_processor.clone(),
             user_repo: self.user_repo.clone(),
+            metrics: self.metrics.clone(),
+            query_token: self.query_token,
         })
     }
 }
@@ -56,6 +102,8 @@ pub struct AuthenticationMiddleware<S> {
     service: Rc<RefCell<S>>,
     jwt_processor: Arc<dyn JwtProcessor>,
     user_repo: Arc<dyn UserRepository>,
+    metrics: Metrics,
+    query_token: bool,
 }
 
 impl<S, B> Service for AuthenticationMiddleware<S>
@@ -78,34 +126,52 @@ where
         let mut service_cloned = self.service.clone();
         let jwt_processor = self.jwt_processor.clone();
         let user_repo = self.user_repo.clone();
+        let metrics = self.metrics.clone();
         let mut is_authorized = false;
+        let mut has_valid_token = false;
         let mut user_id = Uuid::new_v4();
 
         if Method::OPTIONS == *req.method() {
             is_authorized = true;
         } else if let Some(app_state) = req.app_data::<Data<AppState>>() {
-            let secret_key = &app_state.jwt_secret_key;
+            let secret_keys = app_state.jwt_keys();
             let token = req
                 .headers()
                 .get("Authorization")
                 .and_then(|h| h.to_str().ok())
                 .and_then(|h| {
                     let words = h.split("Bearer").collect::<Vec<&str>>();
-                    words.get(1).map(|w| w.trim())
+                    words.get(1).map(|w| w.trim().to_owned())
+                })
+                .or_else(|| {
+                    if !self.query_token {
+                        return None;
+                    }
+                    url::form_urlencoded::parse(req.query_string().as_bytes())
+                        .find(|(name, _)| name == "access_token")
+                        .map(|(_, token)| token.into_owned())
                 });
 
             is_authorized = match token {
                 Some(token) => {
-                    let claims = jwt_processor.parse(token.to_owned(), secret_key.to_owned());
+                    let claims = jwt_processor.parse_any(token, &secret_keys);
                     match claims {
                         Ok(claims) => {
                             user_id = claims.user_id;
+                            has_valid_token = true;
+                            tracing::Span::current().record("user_id", tracing::field::display(user_id));
                             true
                         }
-                        _ => false,
+                        Err(e) => {
+                            metrics.jwt_validation_failed(jwt_failure_reason(e.as_ref()));
+                            false
+                        }
                     }
                 }
-                _ => false,
+                _ => {
+                    metrics.jwt_validation_failed("missing");
+                    false
+                }
             };
         }
 
@@ -114,7 +180,19 @@ where
                 // Check if user is still valid
                 is_authorized = match req.app_data::<Data<PgPool>>() {
                     Some(_) => match user_repo.get_by_id(user_id).await {
-                        Ok(user) => user.is_some(),
+                        Ok(Some(user)) => {
+                            req.extensions_mut().insert(AuthenticatedUser {
+                                id: user.id,
+                                role: user.role,
+                            });
+                            true
+                        }
+                        Ok(None) => {
+                            if has_valid_token {
+                                metrics.jwt_validation_failed("unknown_user");
+                            }
+                            false
+                        }
                         _ => false,
                     },
                     None => false,
@@ -124,11 +202,13 @@ where
             if is_authorized {
                 service_cloned.call(req).await
             } else {
+                let request_id = Some(req.id());
                 Ok(req.into_response(
                     HttpResponse::Unauthorized()
                         .json(AppErrorMessage {
                             code: StatusCode::UNAUTHORIZED.as_u16(),
                             message: "Unauthorized".to_owned(),
+                            request_id,
                         })
                         .into_body(),
                 ))
@@ -0,0 +1,85 @@
+//! CORS middleware module
+
+use actix_cors::Cors;
+
+use crate::config::CorsConfig;
+
+/// Builds the CORS middleware from configuration
+pub fn cors(config: &CorsConfig) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(config.allowed_methods.iter().map(|m| m.as_str()))
+        .allowed_headers(config.allowed_headers.iter().map(|h| h.as_str()))
+        .expose_headers(config.exposed_headers.iter().map(|h| h.as_str()))
+        .max_age(config.max_age);
+
+    let mut wildcards = Vec::new();
+    for origin in &config.allowed_origins {
+        if origin == "*" {
+            cors = cors.allow_any_origin();
+            if !config.supports_credentials {
+                cors = cors.send_wildcard();
+            }
+        } else if origin.contains('*') {
+            wildcards.push(origin.to_lowercase());
+        } else {
+            cors = cors.allowed_origin(origin);
+        }
+    }
+
+    if !wildcards.is_empty() {
+        cors = cors.allowed_origin_fn(move |origin, _req_head| match origin.to_str() {
+            Ok(origin) => wildcards.iter().any(|pattern| origin_matches(pattern, origin)),
+            _ => false,
+        });
+    }
+
+    if config.supports_credentials {
+        cors = cors.supports_credentials();
+    }
+
+    cors
+}
+
+/// Checks an origin against an allowed origin, which can be a wildcard subdomain
+/// pattern like `https://*.example.com`
+pub fn origin_matches(pattern: &str, origin: &str) -> bool {
+    if pattern.eq_ignore_ascii_case(origin) {
+        return true;
+    }
+
+    let origin = origin.to_lowercase();
+    match pattern.to_lowercase().split_once("://*.") {
+        Some((scheme, domain)) => {
+            let prefix = format!("{}://", scheme);
+            let suffix = format!(".{}", domain);
+
+            origin.len() > prefix.len() + suffix.len()
+                && origin.starts_with(&prefix)
+                && origin.ends_with(&suffix)
+                && origin[prefix.len()..origin.len() - suffix.len()]
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        }
+        None => false,
+    }
+}
+
+#[test]
+fn test_origin_matches() {
+    assert!(origin_matches("https://app.example.com", "https://app.example.com"));
+    assert!(origin_matches("https://*.example.com", "https://app.example.com"));
+    assert!(origin_matches("https://*.example.com", "https://eu.app.example.com"));
+    assert!(origin_matches(
+        "http://*.example.com:3000",
+        "http://app.example.com:3000"
+    ));
+
+    assert!(!origin_matches("https://*.example.com", "https://example.com"));
+    assert!(!origin_matches("https://*.example.com", "http://app.example.com"));
+    assert!(!origin_matches(
+        "https://*.example.com",
+        "https://app.example.com.evil.io"
+    ));
+    assert!(!origin_matches("https://*.example.com", "https://evil.io/.example.com"));
+    assert!(!origin_matches("https://*.example.com", "https://app.example.com:8443"));
+}
@@ -0,0 +1,385 @@
+//! Idempotency-Key middleware module
+//!
+//! `POST` requests sent with an `Idempotency-Key` header are handled once per key: retries get
+//! the stored response (with `Idempotent-Replayed: true`), requests sent while the first one is
+//! being handled get 409 Conflict, and other requests reusing the key get 422 Unprocessable Entity.
+//! Server errors and responses marked `Cache-Control: no-store` are not stored, the key being
+//! freed for a retry.
+
+use actix_service::{Service, Transform};
+use actix_web::body::{Body, ResponseBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::PayloadError;
+use actix_web::http::{header, HeaderName, HeaderValue, Method, StatusCode};
+use actix_web::web::BytesMut;
+use actix_web::{Error, HttpMessage, HttpResponse};
+use chrono::{Duration, Utc};
+use futures::future::{ok, Ready};
+use futures::{Future, StreamExt};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::{cell::RefCell, pin::Pin, rc::Rc};
+
+use crate::errors::ApiError;
+use northwind_core::errors::AppError;
+use northwind_core::idempotency::{IdempotencyRepository, IdempotencyState, StoredResponse};
+
+/// Header of the client generated key
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Header added to replayed responses
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+#[derive(Clone)]
+pub struct Idempotency {
+    pub repo: Arc<dyn IdempotencyRepository>,
+    /// Time during which a key replays the response of its request
+    pub ttl: Duration,
+    /// Lease of a key on its request, after which a retry can take it over
+    pub lock_timeout: Duration,
+    /// Largest body of a request sent with a key, at least the largest accepted by the wrapped routes
+    pub max_body_size: usize,
+}
+
+impl<S: 'static> Transform<S> for Idempotency
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = Error>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = Error;
+    type Transform = IdempotencyMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(IdempotencyMiddleware {
+            service: Rc::new(RefCell::new(service)),
+            repo: self.repo.clone(),
+            ttl: self.ttl,
+            lock_timeout: self.lock_timeout,
+            max_body_size: self.max_body_size,
+        })
+    }
+}
+
+pub struct IdempotencyMiddleware<S> {
+    service: Rc<RefCell<S>>,
+    repo: Arc<dyn IdempotencyRepository>,
+    ttl: Duration,
+    lock_timeout: Duration,
+    max_body_size: usize,
+}
+
+impl<S> Service for IdempotencyMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: ServiceRequest) -> Self::Future {
+        let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+            Some(key) if req.method() == Method::POST => key.to_str().ok().map(String::from).unwrap_or_default(),
+            _ => return Box::pin(self.service.call(req)),
+        };
+        if !is_valid_key(&key) {
+            return Box::pin(ok(req.error_response(ApiError::from(AppError::BadRequest {
+                message: String::from("Invalid Idempotency-Key header"),
+            }))));
+        }
+
+        let mut service = self.service.clone();
+        let repo = self.repo.clone();
+        let now = Utc::now();
+        let locked_until = (now + self.lock_timeout).naive_utc();
+        let expires_at = (now + self.ttl).naive_utc();
+        let max_body_size = self.max_body_size;
+
+        Box::pin(async move {
+            // The body is read to be fingerprinted, then handed back to the request
+            let mut payload = req.take_payload();
+            let mut body = BytesMut::new();
+            while let Some(chunk) = payload.next().await {
+                match chunk {
+                    Ok(chunk) if body.len() + chunk.len() <= max_body_size => body.extend_from_slice(&chunk),
+                    Ok(_) => return Ok(req.error_response(PayloadError::Overflow)),
+                    Err(e) => return Ok(req.error_response(e)),
+                }
+            }
+            let body = body.freeze();
+            let fingerprint = fingerprint(&req, &body);
+
+            let (mut sender, payload) = actix_http::h1::Payload::create(true);
+            sender.feed_data(body);
+            sender.feed_eof();
+            req.set_payload(payload.into());
+
+            let error = match repo.claim(&key, &fingerprint, locked_until, expires_at).await {
+                Ok(IdempotencyState::Claimed) => None,
+                Ok(IdempotencyState::InProgress) => Some(AppError::Conflict {
+                    message: String::from("A request with this idempotency key is in progress"),
+                }),
+                Ok(IdempotencyState::Mismatch) => Some(AppError::UnprocessableEntity {
+                    message: String::from("This idempotency key has been used for a different request"),
+                }),
+                Ok(IdempotencyState::Completed(stored)) => return Ok(req.into_response(replay(stored))),
+                Err(e) => Some(e),
+            };
+            if let Some(error) = error {
+                return Ok(req.error_response(ApiError::from(error)));
+            }
+
+            let mut res = match service.call(req).await {
+                Ok(res) => res,
+                Err(e) => {
+                    release(repo.as_ref(), &key).await;
+                    return Err(e);
+                }
+            };
+            if res.status().is_server_error() || is_no_store(&res) {
+                release(repo.as_ref(), &key).await;
+                return Ok(res);
+            }
+
+            let mut response_body = res.take_body();
+            let mut body = BytesMut::new();
+            while let Some(chunk) = response_body.next().await {
+                match chunk {
+                    Ok(chunk) => body.extend_from_slice(&chunk),
+                    Err(e) => {
+                        release(repo.as_ref(), &key).await;
+                        return Err(e);
+                    }
+                }
+            }
+            let body = body.freeze();
+
+            let stored = StoredResponse {
+                status: res.status().as_u16(),
+                headers: Value::Array(
+                    res.headers()
+                        .iter()
+                        .filter_map(|(name, value)| value.to_str().ok().map(|value| json!([name.as_str(), value])))
+                        .collect(),
+                ),
+                body: body.to_vec(),
+            };
+            if let Err(e) = repo.complete(&key, &stored).await {
+                error!("Idempotent response of key {} not stored: {}", key, e);
+            }
+
+            Ok(res.map_body(|_, _| ResponseBody::Body(Body::from(body))))
+        })
+    }
+}
+
+/// Checks that a key is made of 1 to 255 visible ASCII characters
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= MAX_KEY_LENGTH && key.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Hashes what identifies a request: its target, credentials and body
+fn fingerprint(req: &ServiceRequest, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(req.method().as_str());
+    hasher.update(b"\n");
+    hasher.update(req.uri().to_string());
+    hasher.update(b"\n");
+    if let Some(authorization) = req.headers().get(header::AUTHORIZATION) {
+        hasher.update(authorization.as_bytes());
+    }
+    hasher.update(b"\n");
+    hasher.update(body);
+
+    format!("{:x}", hasher.finalize())
+}
+
+fn is_no_store(res: &ServiceResponse) -> bool {
+    res.headers()
+        .get(header::CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|directive| directive.trim().eq_ignore_ascii_case("no-store")))
+}
+
+async fn release(repo: &dyn IdempotencyRepository, key: &str) {
+    if let Err(e) = repo.release(key).await {
+        error!("Idempotency key {} not released: {}", key, e);
+    }
+}
+
+/// Rebuilds a stored response
+fn replay(stored: StoredResponse) -> HttpResponse {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut res = HttpResponse::build(status).body(stored.body);
+
+    if let Value::Array(headers) = stored.headers {
+        for pair in headers {
+            if let (Some(name), Some(value)) = (pair[0].as_str(), pair[1].as_str()) {
+                if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                    res.headers_mut().append(name, value);
+                }
+            }
+        }
+    }
+    res.headers_mut().insert(
+        HeaderName::from_static(IDEMPOTENT_REPLAYED_HEADER),
+        HeaderValue::from_static("true"),
+    );
+
+    res
+}
+
+#[test]
+fn test_idempotent_requests() {
+    use actix_web::{test, web, App};
+    use async_trait::async_trait;
+    use chrono::NaiveDateTime;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    /// Keeps keys in memory, as (fingerprint, response) pairs
+    #[derive(Default)]
+    struct InMemoryRepository(Mutex<HashMap<String, (String, Option<StoredResponse>)>>);
+
+    #[async_trait]
+    impl IdempotencyRepository for InMemoryRepository {
+        async fn claim(
+            &self,
+            key: &str,
+            fingerprint: &str,
+            _locked_until: NaiveDateTime,
+            _expires_at: NaiveDateTime,
+        ) -> Result<IdempotencyState, AppError> {
+            let mut keys = self.0.lock().unwrap();
+            Ok(match keys.get(key) {
+                None => {
+                    keys.insert(key.to_owned(), (fingerprint.to_owned(), None));
+                    IdempotencyState::Claimed
+                }
+                Some((existing, _)) if existing != fingerprint => IdempotencyState::Mismatch,
+                Some((_, None)) => IdempotencyState::InProgress,
+                Some((_, Some(response))) => IdempotencyState::Completed(response.clone()),
+            })
+        }
+
+        async fn complete(&self, key: &str, response: &StoredResponse) -> Result<(), AppError> {
+            if let Some(entry) = self.0.lock().unwrap().get_mut(key) {
+                entry.1 = Some(response.clone());
+            }
+            Ok(())
+        }
+
+        async fn release(&self, key: &str) -> Result<(), AppError> {
+            self.0.lock().unwrap().remove(key);
+            Ok(())
+        }
+
+        async fn purge_expired(&self) -> Result<u64, AppError> {
+            Ok(0)
+        }
+    }
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    async fn create(body: String) -> HttpResponse {
+        let call = CALLS.fetch_add(1, Ordering::SeqCst);
+        HttpResponse::Created()
+            .header("location", format!("/items/{}", call))
+            .body(body)
+    }
+
+    async fn import(body: web::Bytes) -> HttpResponse {
+        HttpResponse::Accepted().body(body.len().to_string())
+    }
+
+    let repo = Arc::new(InMemoryRepository::default());
+    let idempotency = Idempotency {
+        repo: repo.clone(),
+        ttl: Duration::hours(1),
+        lock_timeout: Duration::minutes(5),
+        max_body_size: 3 * 1024 * 1024,
+    };
+
+    actix_web::rt::System::new("test").block_on(async move {
+        let mut app = test::init_service(
+            App::new().service(
+                web::scope("/v1")
+                    .wrap(idempotency)
+                    .route("/items", web::post().to(create))
+                    .service(
+                        web::resource("/import")
+                            .app_data(web::PayloadConfig::new(3 * 1024 * 1024))
+                            .route(web::post().to(import)),
+                    ),
+            ),
+        )
+        .await;
+        let post = |key: &str, body: &'static str| {
+            test::TestRequest::post()
+                .uri("/v1/items")
+                .header(IDEMPOTENCY_KEY_HEADER, key)
+                .set_payload(body)
+                .to_request()
+        };
+
+        // The request is handled once, then replayed
+        let res = test::call_service(&mut app, post("k1", "item")).await;
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert!(res.headers().get(IDEMPOTENT_REPLAYED_HEADER).is_none());
+        assert_eq!(&b"item"[..], &test::read_body(res).await[..]);
+
+        let res = test::call_service(&mut app, post("k1", "item")).await;
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert_eq!("true", res.headers().get(IDEMPOTENT_REPLAYED_HEADER).unwrap());
+        assert_eq!("/items/0", res.headers().get("location").unwrap());
+        assert_eq!(&b"item"[..], &test::read_body(res).await[..]);
+        assert_eq!(1, CALLS.load(Ordering::SeqCst));
+
+        // A different body with the same key
+        let res = test::call_service(&mut app, post("k1", "other item")).await;
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+
+        // A request still in progress
+        let fingerprint = {
+            let req = test::TestRequest::post().uri("/v1/items").to_srv_request();
+            fingerprint(&req, b"item")
+        };
+        repo.0.lock().unwrap().insert(String::from("k2"), (fingerprint, None));
+        let res = test::call_service(&mut app, post("k2", "item")).await;
+        assert_eq!(StatusCode::CONFLICT, res.status());
+
+        // Invalid keys
+        let res = test::call_service(&mut app, post("", "item")).await;
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        assert_eq!(1, CALLS.load(Ordering::SeqCst));
+
+        // Bodies are accepted up to the size of the largest route
+        let import = |key: &str, size: usize| {
+            test::TestRequest::post()
+                .uri("/v1/import")
+                .header(IDEMPOTENCY_KEY_HEADER, key)
+                .set_payload(vec![b'a'; size])
+                .to_request()
+        };
+        let res = test::call_service(&mut app, import("k3", 2 * 1024 * 1024)).await;
+        assert_eq!(StatusCode::ACCEPTED, res.status());
+        assert_eq!(&b"2097152"[..], &test::read_body(res).await[..]);
+        let res = test::call_service(&mut app, import("k4", 3 * 1024 * 1024 + 1)).await;
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, res.status());
+    });
+}
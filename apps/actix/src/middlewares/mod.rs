@@ -1,5 +1,9 @@
 //! Middlewares module
 
 pub mod request_id;
+pub mod request_logger;
 pub mod timer;
 pub mod auth;
+pub mod idempotency;
+pub mod rate_limit;
+pub mod cors;
@@ -0,0 +1,366 @@
+//! Rate limiting middleware module
+//!
+//! The first policy matching a request (path prefix and method) counts it by client IP, user or
+//! issued API key. Responses carry `RateLimit-Limit`, `RateLimit-Remaining`, `RateLimit-Reset` and
+//! `RateLimit-Policy` headers, and denied requests get 429 Too Many Requests with `Retry-After`.
+//! Requests are let through if the counters store fails.
+
+use actix_service::{Service, Transform};
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method},
+    web::Data,
+    Error, ResponseError,
+};
+use chrono::Utc;
+use color_eyre::Result;
+use eyre::eyre;
+use futures::{
+    future::{ok, Ready},
+    Future,
+};
+use northwind_core::errors::AppError;
+use northwind_core::rate_limit::{Quota, RateLimitDecision, RateLimitStore};
+use northwind_user::domain::jwt_processor::JwtProcessor;
+use northwind_user::AppState;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::{cell::RefCell, pin::Pin, rc::Rc};
+
+use crate::config::{RateLimitConfig, RateLimitKey};
+use crate::errors::ApiError;
+
+/// Policy ready to be matched against requests
+struct Policy {
+    name: String,
+    path: String,
+    methods: Vec<Method>,
+    key: RateLimitKey,
+    quota: Quota,
+    /// `RateLimit-Policy` header value, e.g. `10;w=60`
+    header: HeaderValue,
+}
+
+impl Policy {
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        let prefix = self.path.trim_end_matches('/');
+        let in_path = path == prefix || path.starts_with(&format!("{}/", prefix));
+
+        in_path && (self.methods.is_empty() || self.methods.contains(method))
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    jwt_processor: Arc<dyn JwtProcessor>,
+    policies: Arc<Vec<Policy>>,
+    api_key_header: HeaderName,
+    /// Digests of the issued API keys
+    api_keys: Arc<HashSet<String>>,
+    trust_forwarded_for: bool,
+}
+
+impl RateLimiter {
+    pub fn new(
+        config: &RateLimitConfig,
+        store: Arc<dyn RateLimitStore>,
+        jwt_processor: Arc<dyn JwtProcessor>,
+    ) -> Result<Self> {
+        let policies = if config.enabled {
+            config
+                .policies
+                .iter()
+                .map(|policy| {
+                    let methods = policy
+                        .methods
+                        .iter()
+                        .map(|m| Method::from_bytes(m.to_uppercase().as_bytes()))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| eyre!("invalid methods in rate limiting policy `{}`", policy.name))?;
+
+                    Ok(Policy {
+                        name: policy.name.clone(),
+                        path: policy.path.clone(),
+                        methods,
+                        key: policy.key,
+                        quota: Quota {
+                            limit: policy.limit,
+                            period: policy.period as i64 * 1000,
+                        },
+                        header: HeaderValue::from_str(&format!("{};w={}", policy.limit, policy.period))?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            store,
+            jwt_processor,
+            policies: Arc::new(policies),
+            api_key_header: HeaderName::from_bytes(config.api_key_header.as_bytes())?,
+            api_keys: Arc::new(config.api_keys.iter().map(|digest| digest.to_lowercase()).collect()),
+            trust_forwarded_for: config.trust_forwarded_for,
+        })
+    }
+
+    /// Returns the counter key of a request for a policy
+    fn key(&self, policy: &Policy, req: &ServiceRequest) -> String {
+        let ip = || {
+            let connection_info = req.connection_info();
+            let addr = if self.trust_forwarded_for {
+                connection_info.realip_remote_addr().map(String::from)
+            } else {
+                req.peer_addr().map(|addr| addr.to_string())
+            };
+            let ip = addr
+                .map(|addr| {
+                    addr.parse::<SocketAddr>()
+                        .map(|addr| addr.ip().to_string())
+                        .unwrap_or(addr)
+                })
+                .unwrap_or_default();
+            format!("{}:ip:{}", policy.name, ip)
+        };
+
+        match policy.key {
+            RateLimitKey::Ip => ip(),
+            RateLimitKey::User => {
+                let user_id = req.app_data::<Data<AppState>>().and_then(|app_state| {
+                    let token = req
+                        .headers()
+                        .get(header::AUTHORIZATION)
+                        .and_then(|h| h.to_str().ok())
+                        .and_then(|h| h.strip_prefix("Bearer"))
+                        .map(|token| token.trim().to_owned())?;
+                    self.jwt_processor
+                        .parse_any(token, &app_state.jwt_keys())
+                        .ok()
+                        .map(|claims| claims.user_id)
+                });
+                match user_id {
+                    Some(user_id) => format!("{}:user:{}", policy.name, user_id),
+                    None => ip(),
+                }
+            }
+            RateLimitKey::ApiKey => {
+                // Keys are not stored as is, and unknown keys would give each request a fresh quota
+                let digest = req
+                    .headers()
+                    .get(&self.api_key_header)
+                    .map(|api_key| format!("{:x}", Sha256::digest(api_key.as_bytes())))
+                    .filter(|digest| self.api_keys.contains(digest));
+                match digest {
+                    Some(digest) => format!("{}:api_key:{}", policy.name, digest),
+                    None => ip(),
+                }
+            }
+        }
+    }
+}
+
+impl<S: 'static, B> Transform<S> for RateLimiter
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RateLimiterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RateLimiterMiddleware {
+            service: Rc::new(RefCell::new(service)),
+            limiter: self.clone(),
+        })
+    }
+}
+
+pub struct RateLimiterMiddleware<S> {
+    service: Rc<RefCell<S>>,
+    limiter: RateLimiter,
+}
+
+impl<S, B> Service for RateLimiterMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let policy_index = self
+            .limiter
+            .policies
+            .iter()
+            .position(|policy| policy.matches(req.method(), req.path()));
+        let policy_index = match policy_index {
+            Some(index) if req.method() != Method::OPTIONS => index,
+            _ => return Box::pin(self.service.call(req)),
+        };
+
+        let mut service = self.service.clone();
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            let policy = &limiter.policies[policy_index];
+            let key = limiter.key(policy, &req);
+            let decision = match limiter
+                .store
+                .acquire(&key, policy.quota, Utc::now().timestamp_millis())
+                .await
+            {
+                Ok(decision) => decision,
+                Err(e) => {
+                    error!("Rate limiting of {} failed: {}", key, e);
+                    return service.call(req).await;
+                }
+            };
+
+            if decision.allowed {
+                let mut res = service.call(req).await?;
+                insert_headers(res.headers_mut(), policy, &decision);
+                Ok(res)
+            } else {
+                let mut res = ApiError::from(AppError::TooManyRequests).error_response();
+                insert_headers(res.headers_mut(), policy, &decision);
+                res.headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from(seconds(decision.retry_after)));
+                Ok(req.into_response(res.into_body()))
+            }
+        })
+    }
+}
+
+/// Rounds milliseconds up to seconds
+fn seconds(milliseconds: i64) -> i64 {
+    (milliseconds + 999) / 1000
+}
+
+fn insert_headers(headers: &mut HeaderMap, policy: &Policy, decision: &RateLimitDecision) {
+    headers.insert(
+        HeaderName::from_static("ratelimit-limit"),
+        HeaderValue::from(decision.limit),
+    );
+    headers.insert(
+        HeaderName::from_static("ratelimit-remaining"),
+        HeaderValue::from(decision.remaining),
+    );
+    headers.insert(
+        HeaderName::from_static("ratelimit-reset"),
+        HeaderValue::from(seconds(decision.reset)),
+    );
+    headers.insert(HeaderName::from_static("ratelimit-policy"), policy.header.clone());
+}
+
+#[test]
+fn test_rate_limiting() {
+    use actix_web::http::StatusCode;
+    use actix_web::{test, web, App, HttpResponse};
+    use northwind_core::rate_limit::MemoryRateLimitStore;
+    use northwind_user::services::jwt_processor_impl::JwtProcessorImpl;
+
+    use crate::config::RateLimitPolicy;
+
+    let config = RateLimitConfig {
+        api_keys: vec![format!("{:x}", Sha256::digest(b"issued"))],
+        policies: vec![
+            RateLimitPolicy {
+                name: String::from("login"),
+                path: String::from("/v1/login"),
+                methods: vec![String::from("post")],
+                key: RateLimitKey::Ip,
+                limit: 2,
+                period: 60,
+            },
+            RateLimitPolicy {
+                name: String::from("partners"),
+                path: String::from("/v1/partners"),
+                methods: Vec::new(),
+                key: RateLimitKey::ApiKey,
+                limit: 1,
+                period: 60,
+            },
+        ],
+        ..RateLimitConfig::default()
+    };
+    let limiter = RateLimiter::new(
+        &config,
+        Arc::new(MemoryRateLimitStore::default()),
+        Arc::new(JwtProcessorImpl {}),
+    )
+    .unwrap();
+
+    actix_web::rt::System::new("test").block_on(async move {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(limiter)
+                .route("/v1/login", web::post().to(HttpResponse::Ok))
+                .route("/v1/partners", web::get().to(HttpResponse::Ok))
+                .route("/v1/users", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let login = |ip: &str| {
+            test::TestRequest::post()
+                .uri("/v1/login")
+                .peer_addr(format!("{}:40000", ip).parse().unwrap())
+                .to_request()
+        };
+
+        let res = test::call_service(&mut app, login("10.0.0.1")).await;
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("2", res.headers().get("ratelimit-limit").unwrap());
+        assert_eq!("1", res.headers().get("ratelimit-remaining").unwrap());
+        assert_eq!("30", res.headers().get("ratelimit-reset").unwrap());
+        assert_eq!("2;w=60", res.headers().get("ratelimit-policy").unwrap());
+
+        let res = test::call_service(&mut app, login("10.0.0.1")).await;
+        assert_eq!(StatusCode::OK, res.status());
+        let res = test::call_service(&mut app, login("10.0.0.1")).await;
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+        assert_eq!("0", res.headers().get("ratelimit-remaining").unwrap());
+        assert_eq!("30", res.headers().get(header::RETRY_AFTER).unwrap());
+
+        // Other clients and routes are not limited
+        let res = test::call_service(&mut app, login("10.0.0.2")).await;
+        assert_eq!(StatusCode::OK, res.status());
+        let req = test::TestRequest::get().uri("/v1/users").to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert!(res.headers().get("ratelimit-limit").is_none());
+
+        // Issued API keys have their own quota, made up ones share the quota of their IP
+        let partners = |ip: &str, api_key: &str| {
+            test::TestRequest::get()
+                .uri("/v1/partners")
+                .peer_addr(format!("{}:40000", ip).parse().unwrap())
+                .header("x-api-key", api_key)
+                .to_request()
+        };
+        let res = test::call_service(&mut app, partners("10.0.0.3", "issued")).await;
+        assert_eq!(StatusCode::OK, res.status());
+        let res = test::call_service(&mut app, partners("10.0.0.4", "issued")).await;
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+        let res = test::call_service(&mut app, partners("10.0.0.3", "made-up-1")).await;
+        assert_eq!(StatusCode::OK, res.status());
+        let res = test::call_service(&mut app, partners("10.0.0.3", "made-up-2")).await;
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+    });
+}
@@ -1,21 +1,62 @@
 //! Request ID middleware module
+//!
+//! The ID sent by an upstream service in the configured header is kept if valid, a UUID v4
+//! being generated otherwise. It is echoed in the response and available, while the request
+//! is processed, through the `RequestId` extractor and the `current` function.
 
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-use actix_http::http::header;
+use actix_http::http::header::{HeaderName, HeaderValue};
 use actix_service::{Service, Transform};
+use actix_web::client::ClientRequest;
 use actix_web::{
     dev::{Payload, ServiceRequest, ServiceResponse},
     Error, FromRequest, HttpMessage, HttpRequest,
 };
 use color_eyre::Result;
+use eyre::eyre;
 use futures::future::{ok, Ready};
 use futures::Future;
 use uuid::Uuid;
 
+use crate::config::RequestIdConfig;
+
+tokio::task_local! {
+    static CURRENT: CurrentRequestId;
+}
+
+/// ID of the request being processed, along with the header carrying it
+#[derive(Clone, Debug)]
+pub struct CurrentRequestId {
+    pub header: HeaderName,
+    pub id: String,
+}
+
+/// Returns the ID of the request being processed, e.g. to forward it to outgoing calls
+pub fn current() -> Option<CurrentRequestId> {
+    CURRENT.try_with(|current| current.clone()).ok()
+}
+
+/// Checks an inbound ID, only printable characters commonly used in IDs being accepted
+pub fn is_valid_request_id(id: &str, max_length: usize) -> bool {
+    !id.is_empty()
+        && id.len() <= max_length
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.:/+=@".contains(&b))
+}
+
+/// Adds the stored ID of the request for which an outgoing call is made
+pub fn propagate(request: ClientRequest, header: &HeaderName, request_id: Option<&str>) -> ClientRequest {
+    match request_id {
+        Some(id) => request.header(header.clone(), id),
+        None => request,
+    }
+}
+
 // Inspired from https://github.com/pastjean/actix-web-requestid/blob/master/src/lib.rs
-trait RequestIdMessage {
+pub(crate) trait RequestIdMessage {
     fn id(&self) -> String;
 }
 
@@ -77,7 +118,44 @@ impl FromRequest for RequestId {
 // 1. Middleware initialization, middleware factory gets called with
 //    next service in chain as parameter.
 // 2. Middleware call method gets called with normal request.
-pub struct RequestIdService;
+#[derive(Clone)]
+pub struct RequestIdService {
+    header: HeaderName,
+    max_length: usize,
+}
+
+impl RequestIdService {
+    pub fn new(config: &RequestIdConfig) -> Result<Self> {
+        let header = HeaderName::from_bytes(config.header.as_bytes())
+            .map_err(|_| eyre!("invalid request ID header `{}`", config.header))?;
+
+        Ok(Self {
+            header,
+            max_length: config.max_length,
+        })
+    }
+
+    /// Header carrying the IDs, also sent with the outgoing calls
+    pub fn header(&self) -> &HeaderName {
+        &self.header
+    }
+
+    /// Keeps an inbound ID if valid, generates one otherwise
+    pub fn resolve(&self, inbound: Option<&str>) -> String {
+        resolve(inbound, self.max_length)
+    }
+}
+
+fn resolve(inbound: Option<&str>, max_length: usize) -> String {
+    match inbound {
+        Some(id) if is_valid_request_id(id, max_length) => id.to_owned(),
+        Some(_) => {
+            debug!("Invalid inbound request ID replaced by a generated one");
+            Uuid::new_v4().to_string()
+        }
+        None => Uuid::new_v4().to_string(),
+    }
+}
 
 // Middleware factory is `Transform` trait from actix-service crate
 // `S` - type of the next service
@@ -96,12 +174,18 @@ where
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ok(RequestIdServiceMiddleware { service })
+        ok(RequestIdServiceMiddleware {
+            service,
+            header: self.header.clone(),
+            max_length: self.max_length,
+        })
     }
 }
 
 pub struct RequestIdServiceMiddleware<S> {
     service: S,
+    header: HeaderName,
+    max_length: usize,
 }
 
 impl<S, B> Service for RequestIdServiceMiddleware<S>
@@ -121,19 +205,90 @@ where
     }
 
     fn call(&mut self, req: ServiceRequest) -> Self::Future {
-        let request_id = req.id(); // ie. RequestID(req).id();
-        let fut = self.service.call(req);
+        let inbound = req.headers().get(&self.header).and_then(|value| value.to_str().ok());
+        let request_id = resolve(inbound, self.max_length);
+        req.extensions_mut().insert(request_id.clone());
+
+        let current = CurrentRequestId {
+            header: self.header.clone(),
+            id: request_id,
+        };
+        let service = &mut self.service;
+        let fut = CURRENT.sync_scope(current.clone(), || service.call(req));
 
-        Box::pin(async move {
+        Box::pin(CURRENT.scope(current.clone(), async move {
             let mut res = fut.await?;
 
-            if let Ok(name) = header::HeaderName::from_lowercase(b"x-request-id") {
-                if let Ok(value) = header::HeaderValue::from_str(&request_id) {
-                    res.headers_mut().insert(name, value);
-                }
+            if let Ok(value) = HeaderValue::from_str(&current.id) {
+                res.headers_mut().insert(current.header, value);
             }
 
             Ok(res)
-        })
+        }))
+    }
+}
+
+#[test]
+fn test_inbound_request_id() {
+    use actix_web::{test, web, App, HttpResponse};
+    use northwind_core::errors::AppError;
+
+    use crate::errors::ApiError;
+
+    async fn index() -> HttpResponse {
+        HttpResponse::Ok().body(current().unwrap().id)
+    }
+
+    async fn not_found() -> Result<HttpResponse, ApiError> {
+        Err(AppError::NotFound {
+            message: String::from("No user found"),
+        }
+        .into())
     }
+
+    let config = RequestIdConfig {
+        header: String::from("x-correlation-id"),
+        max_length: 16,
+    };
+
+    actix_web::rt::System::new("test").block_on(async move {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(RequestIdService::new(&config).unwrap())
+                .route("/", web::get().to(index))
+                .route("/missing", web::get().to(not_found)),
+        )
+        .await;
+
+        // A valid inbound ID is kept
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header("x-correlation-id", "gateway-42")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!("gateway-42", res.headers().get("x-correlation-id").unwrap());
+        assert_eq!(&b"gateway-42"[..], &test::read_body(res).await[..]);
+
+        // Too long or malformed IDs are replaced
+        for id in &["0123456789abcdefg", "<script>"] {
+            let req = test::TestRequest::get()
+                .uri("/")
+                .header("x-correlation-id", *id)
+                .to_request();
+            let res = test::call_service(&mut app, req).await;
+            let generated = res.headers().get("x-correlation-id").unwrap().to_str().unwrap();
+            assert!(Uuid::parse_str(generated).is_ok());
+        }
+
+        // Error bodies carry the ID
+        let req = test::TestRequest::get()
+            .uri("/missing")
+            .header("x-correlation-id", "gateway-43")
+            .to_request();
+        let body: serde_json::Value = test::read_response_json(&mut app, req).await;
+        assert_eq!("gateway-43", body["request_id"]);
+        assert_eq!(404, body["code"]);
+    });
+
+    assert!(current().is_none());
 }
@@ -0,0 +1,117 @@
+//! Request logger middleware module
+//!
+//! Wraps each request in a `request` span carrying its request_id, method, path and,
+//! once authenticated, user_id, then logs the response status and latency.
+//!
+//! The span continues the trace received in the `traceparent` header, if any, and records
+//! its trace_id so that log lines and exported traces can be matched.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use actix_service::{Service, Transform};
+use actix_web::{dev::ServiceRequest, dev::ServiceResponse, Error};
+use color_eyre::Result;
+use futures::future::{ok, Ready};
+use futures::Future;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::middlewares::request_id::RequestIdMessage;
+use crate::telemetry;
+
+pub struct RequestLogger;
+
+impl<S, B> Transform<S> for RequestLogger
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestLoggerMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestLoggerMiddleware { service })
+    }
+}
+
+pub struct RequestLoggerMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for RequestLoggerMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let now = Instant::now();
+        let route = req.match_pattern().unwrap_or_else(|| req.path().to_owned());
+        let span = tracing::info_span!(
+            "request",
+            otel.name = %format!("{} {}", req.method(), route),
+            otel.kind = "server",
+            otel.status_code = tracing::field::Empty,
+            request_id = %req.id(),
+            trace_id = tracing::field::Empty,
+            method = %req.method(),
+            path = %req.path(),
+            user_id = tracing::field::Empty,
+            http.status_code = tracing::field::Empty,
+        );
+        span.set_parent(telemetry::extract_context(req.headers()));
+        if let Some(trace_id) = telemetry::trace_id(&span) {
+            span.record("trace_id", trace_id.as_str());
+        }
+
+        let fut = {
+            let _entered = span.enter();
+            self.service.call(req)
+        };
+
+        Box::pin(
+            async move {
+                let res = fut.await;
+                let latency = now.elapsed().as_secs_f64();
+                let span = tracing::Span::current();
+
+                match &res {
+                    Ok(res) => {
+                        let status = res.status().as_u16();
+                        span.record("http.status_code", status);
+                        if res.status().is_server_error() {
+                            span.record("otel.status_code", "ERROR");
+                            tracing::error!(status, latency, "request completed");
+                        } else {
+                            tracing::info!(status, latency, "request completed");
+                        }
+                    }
+                    Err(e) => {
+                        span.record("otel.status_code", "ERROR");
+                        tracing::error!(latency, error = %e, "request failed");
+                    }
+                }
+
+                res
+            }
+            .instrument(span),
+        )
+    }
+}
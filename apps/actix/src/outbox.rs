@@ -0,0 +1,148 @@
+//! Outbox sinks module
+
+use actix_web::client::Client;
+use actix_web::http::HeaderName;
+use async_trait::async_trait;
+use northwind_core::errors::AppError;
+use northwind_core::outbox::{EventSink, OutboxEvent};
+use std::io::Write;
+use std::time::Duration;
+
+use crate::middlewares::request_id;
+use crate::telemetry;
+
+/// Writes the events to the standard output, one JSON document per line
+pub struct StdoutSink;
+
+#[async_trait(?Send)]
+impl EventSink for StdoutSink {
+    async fn publish(&self, event: &OutboxEvent) -> Result<(), AppError> {
+        let line = serde_json::to_string(event).map_err(|e| AppError::InternalError { message: e.to_string() })?;
+        writeln!(std::io::stdout().lock(), "{}", line).map_err(|e| AppError::InternalError { message: e.to_string() })
+    }
+}
+
+/// Posts the events as JSON to a URL, any response other than 2xx meaning a failed delivery
+pub struct WebhookSink {
+    pub url: String,
+    pub timeout: Duration,
+    /// Header carrying the ID of the request which caused the event
+    pub request_id_header: HeaderName,
+}
+
+#[async_trait(?Send)]
+impl EventSink for WebhookSink {
+    async fn publish(&self, event: &OutboxEvent) -> Result<(), AppError> {
+        let request = Client::builder()
+            .timeout(self.timeout)
+            .finish()
+            .post(&self.url)
+            .header("X-Event-Id", event.id.to_string())
+            .header("X-Event-Type", event.event_type.as_str());
+        let request = request_id::propagate(request, &self.request_id_header, event.request_id.as_deref());
+        let response = telemetry::propagate(request, event.traceparent.as_deref())
+            .send_json(event)
+            .await
+            .map_err(|e| AppError::InternalError {
+                message: format!("Webhook request failed: {}", e),
+            })?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(AppError::InternalError {
+                message: format!("Webhook answered {}", response.status()),
+            })
+        }
+    }
+}
+
+#[test]
+fn test_webhook_sink() {
+    use actix_web::{web, App, HttpRequest, HttpResponse};
+    use chrono::Utc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    let event = OutboxEvent {
+        id: uuid::Uuid::new_v4(),
+        event_type: String::from("user.registered"),
+        aggregate_type: String::from("user"),
+        aggregate_id: String::from("b7b4c5d3-6e4a-4f8e-9a0e-2f6c9b1d7e21"),
+        payload: serde_json::json!({ "email": "john@example.com" }),
+        occurred_at: Utc::now().naive_utc(),
+        attempts: 1,
+        traceparent: Some(String::from("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")),
+        request_id: Some(String::from("gateway-42")),
+    };
+
+    actix_web::rt::System::new("test").block_on(async move {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let stub = {
+            let (calls, received) = (calls.clone(), received.clone());
+            actix_web::test::start(move || {
+                let (calls, received) = (calls.clone(), received.clone());
+                App::new().route(
+                    "/hooks",
+                    web::post().to(move |req: HttpRequest, body: web::Json<OutboxEvent>| {
+                        let header = |name| req.headers().get(name).cloned();
+                        received.lock().unwrap().push((
+                            header("X-Event-Id"),
+                            header("traceparent"),
+                            header("x-request-id"),
+                            body.into_inner(),
+                        ));
+                        // Fails the first call
+                        let res = if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                            HttpResponse::ServiceUnavailable().finish()
+                        } else {
+                            HttpResponse::NoContent().finish()
+                        };
+                        futures::future::ready(res)
+                    }),
+                )
+            })
+        };
+
+        let sink = WebhookSink {
+            url: stub.url("/hooks"),
+            timeout: Duration::from_secs(5),
+            request_id_header: HeaderName::from_static("x-request-id"),
+        };
+        let error = sink.publish(&event).await.unwrap_err();
+        assert_eq!("Webhook answered 503 Service Unavailable", error.to_string());
+        sink.publish(&event).await.unwrap();
+
+        {
+            let received = received.lock().unwrap();
+            assert_eq!(2, received.len());
+            assert_eq!(event.id.to_string(), received[1].0.as_ref().unwrap().to_str().unwrap());
+            assert_eq!(
+                event.traceparent.as_deref(),
+                received[1].1.as_ref().unwrap().to_str().ok()
+            );
+            assert_eq!(
+                event.request_id.as_deref(),
+                received[1].2.as_ref().unwrap().to_str().ok()
+            );
+            assert_eq!(
+                OutboxEvent {
+                    attempts: 0,
+                    traceparent: None,
+                    request_id: None,
+                    ..event.clone()
+                },
+                received[1].3
+            );
+        }
+
+        // Unreachable endpoints are failed deliveries too
+        let sink = WebhookSink {
+            url: String::from("http://127.0.0.1:9/hooks"),
+            timeout: Duration::from_secs(5),
+            request_id_header: HeaderName::from_static("x-request-id"),
+        };
+        assert!(sink.publish(&event).await.is_err());
+    });
+}
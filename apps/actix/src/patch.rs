@@ -0,0 +1,42 @@
+//! Merge patch extractor module
+
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use futures::future::{FutureExt, LocalBoxFuture};
+use northwind_core::errors::AppError;
+use northwind_core::patch::MERGE_PATCH_CONTENT_TYPE;
+use serde_json::Value;
+
+use crate::errors::ApiError;
+
+/// JSON Merge Patch body, sent as `application/merge-patch+json`
+pub struct MergePatch(pub Value);
+
+impl FromRequest for MergePatch {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let is_merge_patch = req.content_type().eq_ignore_ascii_case(MERGE_PATCH_CONTENT_TYPE);
+        let body = web::Bytes::from_request(req, payload);
+
+        async move {
+            if !is_merge_patch {
+                return Err(ApiError::from(AppError::UnsupportedMediaType {
+                    message: format!("Content type must be {}", MERGE_PATCH_CONTENT_TYPE),
+                })
+                .into());
+            }
+
+            let body = body.await?;
+            serde_json::from_slice(&body).map(MergePatch).map_err(|e| {
+                ApiError::from(AppError::BadRequest {
+                    message: format!("Invalid JSON: {}", e),
+                })
+                .into()
+            })
+        }
+        .boxed_local()
+    }
+}
@@ -0,0 +1,226 @@
+//! Telemetry module
+//!
+//! `tracing` spans (requests, handlers, repositories and SQL queries) are exported over
+//! OTLP through an OpenTelemetry layer added to the logger. The exporter runs on its own
+//! Tokio runtime, actix-web running on an older one, and the W3C `traceparent` header is
+//! used to propagate the trace context to and from other services. Calls made in the
+//! background for a request, such as webhooks, send the `traceparent` stored with their work.
+
+use actix_web::client::ClientRequest;
+use actix_web::http::HeaderMap;
+use color_eyre::Result;
+use eyre::WrapErr;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::trace::{self, Sampler, Tracer};
+use opentelemetry::sdk::Resource;
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::{global, Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use std::collections::HashMap;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::config::TelemetryConfig;
+
+/// Header of the W3C trace context
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Span exporter, flushed and stopped when dropped
+pub struct Telemetry {
+    tracer: Option<Tracer>,
+    runtime: Option<tokio::runtime::Runtime>,
+}
+
+impl Telemetry {
+    /// Installs the OTLP pipeline and the `traceparent` propagator if telemetry is enabled
+    pub fn init(config: &TelemetryConfig) -> Result<Self> {
+        if !config.enabled {
+            return Ok(Self {
+                tracer: None,
+                runtime: None,
+            });
+        }
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("otlp-exporter")
+            .enable_all()
+            .build()
+            .context("starting the telemetry runtime")?;
+
+        let tracer = {
+            let _guard = runtime.enter();
+            opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(config.endpoint.as_str()),
+                )
+                .with_trace_config(
+                    trace::config()
+                        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                            config.sampling_ratio,
+                        ))))
+                        .with_resource(Resource::new(vec![KeyValue::new(
+                            "service.name",
+                            config.service_name.clone(),
+                        )])),
+                )
+                .install_batch(opentelemetry::runtime::Tokio)
+                .context("installing the OTLP exporter")?
+        };
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
+        Ok(Self {
+            tracer: Some(tracer),
+            runtime: Some(runtime),
+        })
+    }
+
+    /// Tracer used by the OpenTelemetry layer, if telemetry is enabled
+    pub fn tracer(&self) -> Option<Tracer> {
+        self.tracer.clone()
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        // Pending spans are exported by the runtime, it must outlive the provider
+        if self.runtime.is_some() {
+            global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// Reads the trace context from HTTP headers
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Returns the trace context sent by the caller, if any
+pub fn extract_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+/// Returns the `traceparent` of the current span if it is traced, e.g. to be stored with the work
+/// done in the background for a request
+pub fn current_traceparent() -> Option<String> {
+    let mut headers = HashMap::new();
+    let context = Span::current().context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut headers));
+
+    headers.remove(TRACEPARENT_HEADER)
+}
+
+/// Adds the stored trace context of the request for which an outgoing call is made
+pub fn propagate(request: ClientRequest, traceparent: Option<&str>) -> ClientRequest {
+    match traceparent {
+        Some(traceparent) => request.header(TRACEPARENT_HEADER, traceparent),
+        None => request,
+    }
+}
+
+/// Returns the hexadecimal trace ID of a span, if it is traced
+pub fn trace_id(span: &Span) -> Option<String> {
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+
+    if span_context.is_valid() {
+        Some(span_context.trace_id().to_hex())
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_trace_context_propagation() {
+    use actix_web::{test, web, App, HttpResponse};
+    use opentelemetry::sdk::export::trace::SpanData;
+    use opentelemetry::sdk::trace::{Span as SdkSpan, SpanProcessor, TracerProvider};
+    use opentelemetry::trace::{TraceResult, TracerProvider as _};
+    use opentelemetry::Key;
+    use std::sync::{Arc, Mutex};
+    use tracing::Instrument;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Keeps ended spans in memory
+    #[derive(Debug, Clone, Default)]
+    struct InMemoryProcessor(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanProcessor for InMemoryProcessor {
+        fn on_start(&self, _span: &mut SdkSpan, _cx: &Context) {}
+
+        fn on_end(&self, span: SpanData) {
+            self.0.lock().unwrap().push(span);
+        }
+
+        fn force_flush(&self) -> TraceResult<()> {
+            Ok(())
+        }
+
+        fn shutdown(&mut self) -> TraceResult<()> {
+            Ok(())
+        }
+    }
+
+    async fn handler(audit: crate::handlers::audit::Audit) -> HttpResponse {
+        async {}
+            .instrument(northwind_core::telemetry::query_span("SELECT", "users"))
+            .await;
+
+        // Stored with the outbox events, to be sent along with the webhooks
+        HttpResponse::Ok().body(audit.0.traceparent.unwrap())
+    }
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let spans = InMemoryProcessor::default();
+    let provider = TracerProvider::builder().with_span_processor(spans.clone()).build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test", None)));
+    let _default = tracing::subscriber::set_default(subscriber);
+
+    let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let outgoing = actix_web::rt::System::new("test").block_on(async move {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(crate::middlewares::request_logger::RequestLogger)
+                .route("/", web::get().to(handler)),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header("traceparent", traceparent)
+            .to_request();
+
+        test::read_response(&mut app, req).await
+    });
+
+    // The stored header continues the inbound trace
+    let outgoing = String::from_utf8(outgoing.to_vec()).unwrap();
+    assert!(outgoing.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+    assert!(!outgoing.contains("00f067aa0ba902b7"));
+
+    let spans = spans.0.lock().unwrap();
+    let request = spans.iter().find(|s| s.name == "GET /").unwrap();
+    let query = spans.iter().find(|s| s.name == "SELECT users").unwrap();
+
+    assert_eq!("4bf92f3577b34da6a3ce929d0e0e4736", request.span_context.trace_id().to_hex());
+    assert_eq!("00f067aa0ba902b7", request.parent_span_id.to_hex());
+    assert_eq!(request.span_context.trace_id(), query.span_context.trace_id());
+    assert_eq!(request.span_context.span_id(), query.parent_span_id);
+    assert!(request.attributes.get(&Key::new("request_id")).is_some());
+    assert_eq!(
+        Some(&"postgresql".into()),
+        query.attributes.get(&Key::new("db.system"))
+    );
+}
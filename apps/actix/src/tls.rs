@@ -0,0 +1,214 @@
+//! TLS module
+//!
+//! Certificates are served through a resolver which periodically re-reads the certificate
+//! and key files, so that renewed certificates are picked up without restarting the server.
+//! HTTP/2 is negotiated through ALPN by actix-web.
+
+use color_eyre::Result;
+use eyre::{eyre, WrapErr};
+use rustls::internal::pemfile;
+use rustls::sign::{self, CertifiedKey};
+use rustls::{ClientHello, NoClientAuth, ResolvesServerCert, ServerConfig};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::config::TlsConfig;
+
+/// Certificate resolver reloading its certificate when the files change
+pub struct CertificateResolver {
+    cert_path: String,
+    key_path: String,
+    current: RwLock<LoadedCertificate>,
+}
+
+/// Certificate currently served, along with the file contents it has been parsed from
+struct LoadedCertificate {
+    cert: Vec<u8>,
+    key: Vec<u8>,
+    certified_key: CertifiedKey,
+}
+
+impl CertificateResolver {
+    /// Loads the certificate chain and private key
+    pub fn load(config: &TlsConfig) -> Result<Self> {
+        let (cert, key) = read_files(&config.cert_path, &config.key_path)?;
+        let certified_key = certified_key(&cert, &key)?;
+
+        Ok(Self {
+            cert_path: config.cert_path.clone(),
+            key_path: config.key_path.clone(),
+            current: RwLock::new(LoadedCertificate {
+                cert,
+                key,
+                certified_key,
+            }),
+        })
+    }
+
+    /// Reloads the certificate if the files have changed, returns true if it has been replaced
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let (cert, key) = read_files(&self.cert_path, &self.key_path)?;
+        {
+            let current = self.current.read().map_err(|_| eyre!("certificate lock poisoned"))?;
+            if current.cert == cert && current.key == key {
+                return Ok(false);
+            }
+        }
+
+        let certified_key = certified_key(&cert, &key)?;
+        let mut current = self.current.write().map_err(|_| eyre!("certificate lock poisoned"))?;
+        *current = LoadedCertificate {
+            cert,
+            key,
+            certified_key,
+        };
+
+        Ok(true)
+    }
+
+    /// Starts a thread checking the certificate files for changes
+    pub fn watch(self: &Arc<Self>, interval: Duration) {
+        let resolver = self.clone();
+
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            match resolver.reload_if_changed() {
+                Ok(true) => info!("TLS certificate reloaded from {}", resolver.cert_path),
+                Ok(false) => {}
+                Err(e) => error!("TLS certificate reload failed, keeping the current one: {:#}", e),
+            }
+        });
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<CertifiedKey> {
+        self.current.read().ok().map(|current| current.certified_key.clone())
+    }
+}
+
+/// Builds the rustls server configuration
+pub fn server_config(resolver: Arc<CertificateResolver>) -> ServerConfig {
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config.cert_resolver = resolver;
+
+    config
+}
+
+fn read_files(cert_path: &str, key_path: &str) -> Result<(Vec<u8>, Vec<u8>)> {
+    let cert = std::fs::read(cert_path).with_context(|| format!("reading TLS certificate {}", cert_path))?;
+    let key = std::fs::read(key_path).with_context(|| format!("reading TLS private key {}", key_path))?;
+
+    Ok((cert, key))
+}
+
+/// Parses a PEM certificate chain and a PEM private key (PKCS#8 or RSA)
+fn certified_key(cert: &[u8], key: &[u8]) -> Result<CertifiedKey> {
+    let chain = pemfile::certs(&mut &cert[..]).map_err(|_| eyre!("invalid TLS certificate"))?;
+    if chain.is_empty() {
+        return Err(eyre!("no certificate found in the TLS certificate file"));
+    }
+
+    let mut keys = pemfile::pkcs8_private_keys(&mut &key[..]).map_err(|_| eyre!("invalid TLS private key"))?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut &key[..]).map_err(|_| eyre!("invalid TLS private key"))?;
+    }
+    let key = keys
+        .first()
+        .ok_or_else(|| eyre!("no private key found in the TLS private key file"))?;
+    let signing_key = sign::any_supported_type(key).map_err(|_| eyre!("unsupported TLS private key type"))?;
+
+    Ok(CertifiedKey::new(chain, Arc::new(signing_key)))
+}
+
+/// Writes a self-signed certificate for `localhost`, returns its configuration and its DER encoding
+#[cfg(test)]
+pub(crate) fn self_signed(dir: &std::path::Path) -> (TlsConfig, Vec<u8>) {
+    let certificate = rcgen::generate_simple_self_signed(vec![String::from("localhost")]).unwrap();
+    let config = TlsConfig {
+        enabled: true,
+        cert_path: dir.join("cert.pem").to_string_lossy().into_owned(),
+        key_path: dir.join("key.pem").to_string_lossy().into_owned(),
+        ..TlsConfig::default()
+    };
+
+    std::fs::write(&config.cert_path, certificate.serialize_pem().unwrap()).unwrap();
+    std::fs::write(&config.key_path, certificate.serialize_private_key_pem()).unwrap();
+
+    let pem = std::fs::read(&config.cert_path).unwrap();
+    let der = pemfile::certs(&mut &pem[..]).unwrap().remove(0).0;
+
+    (config, der)
+}
+
+#[cfg(test)]
+pub(crate) fn test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("northwind-{}-{}", name, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_certificate_reload() {
+    let dir = test_dir("tls-reload");
+    let (config, first) = self_signed(&dir);
+
+    let resolver = CertificateResolver::load(&config).unwrap();
+    assert!(!resolver.reload_if_changed().unwrap());
+
+    let (_, second) = self_signed(&dir);
+    assert!(resolver.reload_if_changed().unwrap());
+
+    let served = resolver.current.read().unwrap().certified_key.cert[0].0.clone();
+    assert_ne!(first, served);
+    assert_eq!(second, served);
+
+    std::fs::write(&config.key_path, "not a key").unwrap();
+    assert!(resolver.reload_if_changed().is_err());
+    assert_eq!(served, resolver.current.read().unwrap().certified_key.cert[0].0);
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_handshake_negotiates_http2() {
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use rustls::{ClientConfig, ClientSession, Session};
+    use std::net::TcpStream;
+
+    let dir = test_dir("tls-handshake");
+    let (config, der) = self_signed(&dir);
+
+    // The server is bound as `run` binds it, ALPN being left to the production configuration
+    let resolver = Arc::new(CertificateResolver::load(&config).unwrap());
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        actix_web::rt::System::new("tls-handshake").block_on(async move {
+            let server = HttpServer::new(|| App::new().route("/", web::get().to(HttpResponse::Ok)))
+                .bind_rustls("127.0.0.1:0", server_config(resolver))
+                .unwrap();
+            sender.send(server.addrs()[0]).unwrap();
+            server.run().await
+        })
+    });
+    let address = receiver.recv().unwrap();
+
+    let negotiate = |protocols: &[Vec<u8>]| {
+        let mut client_config = ClientConfig::new();
+        client_config.root_store.add(&rustls::Certificate(der.clone())).unwrap();
+        client_config.set_protocols(protocols);
+
+        let name = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+        let mut client = ClientSession::new(&Arc::new(client_config), name);
+        let mut socket = TcpStream::connect(address).unwrap();
+        while client.is_handshaking() {
+            client.complete_io(&mut socket).unwrap();
+        }
+        client.get_alpn_protocol().map(|protocol| protocol.to_vec())
+    };
+
+    assert_eq!(Some(b"h2".to_vec()), negotiate(&[b"h2".to_vec(), b"http/1.1".to_vec()]));
+    assert_eq!(Some(b"http/1.1".to_vec()), negotiate(&[b"http/1.1".to_vec()]));
+
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,228 @@
+//! Webhook deliveries module
+//!
+//! Pending deliveries are posted to their subscription with a signature of the payload, and
+//! retried with an exponential backoff until they succeed or run out of attempts.
+
+use actix_web::client::Client;
+use actix_web::http::HeaderName;
+use chrono::Utc;
+use northwind_core::errors::AppError;
+use northwind_core::outbox::backoff;
+use northwind_webhooks::domain::delivery::{DeliveryAttempt, PendingDelivery};
+use northwind_webhooks::domain::signature::{sign, SIGNATURE_HEADER};
+use northwind_webhooks::domain::webhook_repository::WebhookRepository;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::WebhooksConfig;
+use crate::middlewares::request_id;
+use crate::telemetry;
+
+/// Sends the pending webhook deliveries
+pub struct WebhookSender {
+    pub repo: Arc<dyn WebhookRepository>,
+    pub config: WebhooksConfig,
+    /// Header carrying the ID of the request which caused the events
+    pub request_id_header: HeaderName,
+}
+
+impl WebhookSender {
+    /// Sends a batch of due deliveries, returns the number of sent deliveries
+    pub async fn run(&self) -> Result<usize, AppError> {
+        let lease = chrono::Duration::seconds(self.config.lease as i64);
+        let deliveries = self
+            .repo
+            .claim(self.config.batch_size, (Utc::now() + lease).naive_utc())
+            .await?;
+
+        for delivery in &deliveries {
+            let attempt = send(
+                delivery,
+                Duration::from_secs(self.config.timeout),
+                &self.request_id_header,
+            )
+            .await;
+            let retry_at = if attempt.succeeded() || delivery.attempts >= self.config.max_attempts {
+                None
+            } else {
+                let delay = backoff(
+                    delivery.attempts,
+                    chrono::Duration::seconds(self.config.min_backoff as i64),
+                    chrono::Duration::seconds(self.config.max_backoff as i64),
+                );
+                Some((Utc::now() + delay).naive_utc())
+            };
+
+            if !attempt.succeeded() {
+                warn!(
+                    "Webhook delivery {} of {} to {} failed (attempt {}): {}",
+                    delivery.id,
+                    delivery.event_type,
+                    delivery.url,
+                    delivery.attempts,
+                    attempt
+                        .error
+                        .clone()
+                        .or_else(|| attempt.response_status.map(|status| format!("status {}", status)))
+                        .unwrap_or_default()
+                );
+            }
+
+            let disabled = self
+                .repo
+                .record_attempt(delivery, &attempt, retry_at, self.config.disable_after)
+                .await?;
+            if disabled {
+                warn!(
+                    "Webhook subscription {} disabled after {} consecutive failures",
+                    delivery.subscription_id, self.config.disable_after
+                );
+            }
+        }
+
+        Ok(deliveries.len())
+    }
+}
+
+/// Posts a delivery, signed with the secret of its subscription
+pub async fn send(delivery: &PendingDelivery, timeout: Duration, request_id_header: &HeaderName) -> DeliveryAttempt {
+    let attempted_at = Utc::now();
+    let started = Instant::now();
+    let body = serde_json::to_vec(&delivery.payload).unwrap_or_default();
+
+    let request = Client::builder()
+        .timeout(timeout)
+        .finish()
+        .post(&delivery.url)
+        .content_type("application/json")
+        .header("Northwind-Webhook-Id", delivery.id.to_string())
+        .header("Northwind-Event-Id", delivery.event_id.to_string())
+        .header("Northwind-Event-Type", delivery.event_type.as_str())
+        .header(
+            SIGNATURE_HEADER,
+            sign(&delivery.secret, attempted_at.timestamp(), &body),
+        );
+    let request = request_id::propagate(request, request_id_header, delivery.request_id.as_deref());
+    let response = telemetry::propagate(request, delivery.traceparent.as_deref())
+        .send_body(body)
+        .await;
+
+    let (response_status, error) = match response {
+        Ok(response) => (Some(response.status().as_u16() as i16), None),
+        Err(e) => (None, Some(format!("Webhook request failed: {}", e))),
+    };
+
+    DeliveryAttempt {
+        attempted_at: attempted_at.naive_utc(),
+        response_status,
+        error,
+        duration_ms: started.elapsed().as_millis().min(i32::MAX as u128) as i32,
+    }
+}
+
+#[test]
+fn test_signed_delivery() {
+    use actix_web::{web, App, HttpRequest, HttpResponse};
+    use northwind_webhooks::domain::signature::verify;
+    use std::sync::Mutex;
+
+    let delivery = PendingDelivery {
+        id: uuid::Uuid::new_v4(),
+        subscription_id: uuid::Uuid::new_v4(),
+        event_id: uuid::Uuid::new_v4(),
+        event_type: String::from("order.shipped"),
+        payload: serde_json::json!({ "type": "order.shipped", "payload": { "order_id": 11077 } }),
+        attempts: 1,
+        url: String::new(),
+        secret: String::from("whsec_0123456789abcdef"),
+        traceparent: Some(String::from("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")),
+        request_id: Some(String::from("gateway-42")),
+    };
+
+    actix_web::rt::System::new("test").block_on(async move {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let stub = {
+            let received = received.clone();
+            actix_web::test::start(move || {
+                let received = received.clone();
+                App::new().route(
+                    "/{status}",
+                    web::post().to(move |req: HttpRequest, body: web::Bytes| {
+                        let header = |name| req.headers().get(name).unwrap().to_str().unwrap().to_owned();
+                        received.lock().unwrap().push((
+                            header(SIGNATURE_HEADER),
+                            header("Northwind-Event-Type"),
+                            header("traceparent"),
+                            header("x-request-id"),
+                            body.to_vec(),
+                        ));
+                        let status = req.match_info().query("status").parse().unwrap();
+                        let status = actix_web::http::StatusCode::from_u16(status).unwrap();
+                        futures::future::ready(HttpResponse::build(status).finish())
+                    }),
+                )
+            })
+        };
+        let timeout = Duration::from_secs(5);
+        let request_id_header = HeaderName::from_static("x-request-id");
+
+        let ok = send(
+            &PendingDelivery {
+                url: stub.url("/204"),
+                ..delivery.clone()
+            },
+            timeout,
+            &request_id_header,
+        )
+        .await;
+        assert!(ok.succeeded());
+        assert_eq!((Some(204), None), (ok.response_status, ok.error));
+
+        // The receiver can check the payload and its signature
+        {
+            let received = received.lock().unwrap();
+            let (signature, event_type, traceparent, request_id, body) = &received[0];
+            assert_eq!("order.shipped", event_type);
+            // The delivery continues the trace of the request which caused the event
+            assert_eq!(delivery.traceparent.as_ref(), Some(traceparent));
+            assert_eq!(delivery.request_id.as_ref(), Some(request_id));
+            assert_eq!(
+                delivery.payload,
+                serde_json::from_slice::<serde_json::Value>(body).unwrap()
+            );
+            assert!(verify(&delivery.secret, signature, body, Utc::now().timestamp(), 300));
+            assert!(!verify(
+                "whsec_other_secret",
+                signature,
+                body,
+                Utc::now().timestamp(),
+                300
+            ));
+        }
+
+        let failed = send(
+            &PendingDelivery {
+                url: stub.url("/500"),
+                ..delivery.clone()
+            },
+            timeout,
+            &request_id_header,
+        )
+        .await;
+        assert!(!failed.succeeded());
+        assert_eq!(Some(500), failed.response_status);
+
+        let unreachable = send(
+            &PendingDelivery {
+                url: String::from("http://127.0.0.1:9/"),
+                ..delivery.clone()
+            },
+            timeout,
+            &request_id_header,
+        )
+        .await;
+        assert!(!unreachable.succeeded());
+        assert_eq!(None, unreachable.response_status);
+        assert!(unreachable.error.is_some());
+    });
+}
@@ -0,0 +1,286 @@
+//! Configuration commands module
+
+use color_eyre::Result;
+use eyre::{eyre, WrapErr};
+use northwind_actix::config::{AuthConfig, Config, ENV_PREFIX};
+use northwind_actix::export::{Cell, Tabular};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::output::{print, write, Format};
+
+/// Length of the generated JWT keys, about 512 bits of entropy for HS512
+const JWT_KEY_LENGTH: usize = 86;
+
+/// Configuration key with its environment variable and value
+#[derive(Debug, PartialEq, Serialize)]
+struct Setting {
+    key: String,
+    variable: String,
+    value: String,
+}
+
+impl Setting {
+    fn new(key: &str, value: String) -> Self {
+        Self {
+            key: key.to_owned(),
+            variable: format!("{}_{}", ENV_PREFIX, key.to_uppercase().replace('.', "__")),
+            value,
+        }
+    }
+}
+
+impl Tabular for Setting {
+    fn columns() -> &'static [&'static str] {
+        &["Key", "Environment variable", "Value"]
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            self.key.as_str().into(),
+            self.variable.as_str().into(),
+            self.value.as_str().into(),
+        ]
+    }
+}
+
+/// Flattens a configuration into settings, lists being comma separated as in environment variables
+fn settings(prefix: &str, value: &Value, settings: &mut Vec<Setting>) {
+    let text = |value: &Value| match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    };
+
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                let key = if prefix.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{}.{}", prefix, k)
+                };
+                self::settings(&key, v, settings);
+            }
+        }
+        Value::Array(values) if values.iter().all(|v| !v.is_object()) => {
+            let values: Vec<String> = values.iter().map(text).collect();
+            settings.push(Setting::new(prefix, values.join(",")));
+        }
+        value => settings.push(Setting::new(prefix, text(value))),
+    }
+}
+
+/// Prints the effective configuration, secrets redacted
+pub fn print_config(config: &Config, format: Format) -> Result<()> {
+    let value = serde_json::to_value(config.redacted())?;
+
+    match format {
+        Format::Table => {
+            let mut rows = Vec::new();
+            settings("", &value, &mut rows);
+            print(format, &rows)
+        }
+        Format::Json => write(&value),
+    }
+}
+
+/// Random alphanumeric key
+fn generate_key() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(JWT_KEY_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// Previous keys after a rotation: the replaced key first, then the former previous keys,
+/// `keep` keys at most
+fn previous_keys(auth: &AuthConfig, keep: usize) -> Vec<String> {
+    std::iter::once(&auth.jwt_secret_key)
+        .chain(auth.jwt_previous_keys.iter())
+        .filter(|key| !key.is_empty())
+        .take(keep)
+        .cloned()
+        .collect()
+}
+
+/// Repository containing a path, i.e. its closest ancestor holding a `.git` entry
+fn repository(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|dir| dir.join(".git").exists())
+}
+
+/// Checks that the keys are not written where they could be committed, returning the path made
+/// absolute
+fn secrets_path(path: &Path) -> Result<PathBuf> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| eyre!("{} is not a file path", path.display()))?;
+    let path = dir
+        .canonicalize()
+        .with_context(|| format!("resolving {}", dir.display()))?
+        .join(file_name);
+
+    match repository(&path) {
+        Some(repository) => Err(eyre!(
+            "{} is in the repository {}, write the keys outside of it",
+            path.display(),
+            repository.display()
+        )),
+        None => Ok(path),
+    }
+}
+
+/// Sets the keys in a TOML configuration file readable by its owner only, keeping its other keys
+/// and comments
+fn write_keys(path: &Path, secret_key: &str, previous_keys: &[String]) -> Result<()> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+    let mut document = content
+        .parse::<toml_edit::Document>()
+        .with_context(|| format!("parsing {}", path.display()))?;
+
+    document["auth"]["jwt_secret_key"] = toml_edit::value(secret_key);
+    document["auth"]["jwt_previous_keys"] = toml_edit::value(previous_keys.iter().collect::<toml_edit::Array>());
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        // The mode only applies to created files
+        options.mode(0o600);
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+                .with_context(|| format!("restricting the permissions of {}", path.display()))?;
+        }
+    }
+
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(document.to_string().as_bytes()))
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Generates a new signing key, the replaced key still verifying the tokens it signed, and
+/// prints the new settings, also writing them to a configuration file outside of the repository
+/// when asked to
+pub fn rotate_jwt_key(config: &Config, keep: usize, write: Option<&Path>, format: Format) -> Result<()> {
+    if config.auth.jwt_secret_key.is_empty() {
+        return Err(eyre!("auth.jwt_secret_key is not set, there is no key to rotate"));
+    }
+
+    let secret_key = generate_key();
+    let previous_keys = previous_keys(&config.auth, keep);
+    let rows = vec![
+        Setting::new("auth.jwt_secret_key", secret_key.clone()),
+        Setting::new("auth.jwt_previous_keys", previous_keys.join(",")),
+    ];
+
+    if let Some(path) = write {
+        let path = secrets_path(path)?;
+        write_keys(&path, &secret_key, &previous_keys)?;
+        eprintln!("Keys written to {}, restart the instances to use them", path.display());
+        for row in rows.iter().filter(|row| std::env::var(&row.variable).is_ok()) {
+            eprintln!("Warning: {} is set and overrides the file", row.variable);
+        }
+    } else {
+        eprintln!("Set these values in the configuration of every instance, then restart them");
+    }
+
+    print(format, &rows)
+}
+
+#[test]
+fn test_previous_keys() {
+    let auth = AuthConfig {
+        jwt_secret_key: String::from("current"),
+        jwt_previous_keys: vec![String::from("previous"), String::from("oldest")],
+        jwt_lifetime: 2,
+    };
+
+    assert_eq!(vec!["current"], previous_keys(&auth, 1));
+    assert_eq!(vec!["current", "previous"], previous_keys(&auth, 2));
+    assert!(previous_keys(&auth, 0).is_empty());
+    assert_eq!(JWT_KEY_LENGTH, generate_key().len());
+}
+
+#[test]
+fn test_write_keys() {
+    let path = std::env::temp_dir().join(format!("northwind-admin-{}.toml", uuid::Uuid::new_v4()));
+    std::fs::write(
+        &path,
+        "# Development configuration\n\n[auth]\njwt_secret_key = \"old\" # rotated\njwt_lifetime = 2\n",
+    )
+    .unwrap();
+
+    write_keys(&path, "new", &[String::from("old")]).unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(0o600, std::fs::metadata(&path).unwrap().permissions().mode() & 0o777);
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(content.starts_with("# Development configuration\n"));
+    let config: toml::Value = toml::from_str(&content).unwrap();
+    assert_eq!("new", config["auth"]["jwt_secret_key"].as_str().unwrap());
+    assert_eq!(2, config["auth"]["jwt_lifetime"].as_integer().unwrap());
+    assert_eq!(
+        vec!["old"],
+        config["auth"]["jwt_previous_keys"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|key| key.as_str().unwrap())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_secrets_path() {
+    let repository = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../config/production.toml");
+    assert!(secrets_path(&repository).is_err());
+
+    let path = std::env::temp_dir().join("northwind-production.toml");
+    assert_eq!(
+        std::env::temp_dir()
+            .canonicalize()
+            .unwrap()
+            .join("northwind-production.toml"),
+        secrets_path(&path).unwrap()
+    );
+    assert!(secrets_path(Path::new("/nonexistent/production.toml")).is_err());
+}
+
+#[test]
+fn test_settings() {
+    let mut rows = Vec::new();
+    settings(
+        "",
+        &serde_json::json!({ "cors": { "allowed_origins": ["a", "b"] } }),
+        &mut rows,
+    );
+
+    assert_eq!(
+        vec![Setting {
+            key: String::from("cors.allowed_origins"),
+            variable: String::from("NORTHWIND_CORS__ALLOWED_ORIGINS"),
+            value: String::from("a,b"),
+        }],
+        rows
+    );
+}
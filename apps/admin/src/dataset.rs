@@ -0,0 +1,669 @@
+//! Synthetic dataset module
+//!
+//! Rows are drawn from a generator seeded by the user, the same seed and references giving the
+//! same rows. Popularity is skewed, a few customers placing most orders and a few products
+//! selling most, and orders grow more frequent over the period.
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use northwind_traders::domain::catalog::Product;
+use northwind_traders::domain::customer::Customer;
+use northwind_traders::domain::order::{Order, OrderDetail};
+use northwind_user::domain::user::{User, ROLE_USER};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use uuid::{Builder, Variant, Version};
+
+/// Share of the picks going to the most popular rows grows with this exponent
+const POPULARITY_SKEW: f64 = 2.5;
+
+/// Number of lines of an order, with their weights (about 2.6 lines per order, as in the
+/// original data)
+const ORDER_LINES: [(usize, u32); 5] = [(1, 30), (2, 30), (3, 20), (4, 12), (5, 8)];
+
+/// Discounts granted on order lines, no discount being the most frequent
+const DISCOUNTS: [(f32, u32); 6] = [(0.0, 60), (0.05, 12), (0.1, 10), (0.15, 8), (0.2, 6), (0.25, 4)];
+
+const FIRST_NAMES: [&str; 24] = [
+    "Maria",
+    "Ana",
+    "Thomas",
+    "Christina",
+    "Hanna",
+    "Frederique",
+    "Martin",
+    "Laurence",
+    "Elizabeth",
+    "Victoria",
+    "Patricio",
+    "Francisco",
+    "Yang",
+    "Pedro",
+    "Aria",
+    "Diego",
+    "Yoshi",
+    "Helen",
+    "Jose",
+    "Paula",
+    "Carlos",
+    "Liu",
+    "Sven",
+    "Karin",
+];
+
+const LAST_NAMES: [&str; 24] = [
+    "Anders",
+    "Trujillo",
+    "Moreno",
+    "Hardy",
+    "Berglund",
+    "Moos",
+    "Citeaux",
+    "Sommer",
+    "Lebihan",
+    "Lincoln",
+    "Ashworth",
+    "Simpson",
+    "Chang",
+    "Afonso",
+    "Cruz",
+    "Roel",
+    "Latimer",
+    "Bennett",
+    "Pavarotti",
+    "Wilson",
+    "Hernandez",
+    "Wong",
+    "Ottlieb",
+    "Josephs",
+];
+
+const CONTACT_TITLES: [&str; 8] = [
+    "Sales Representative",
+    "Owner",
+    "Order Administrator",
+    "Marketing Manager",
+    "Accounting Manager",
+    "Sales Manager",
+    "Purchasing Manager",
+    "Sales Agent",
+];
+
+const COMPANY_PREFIXES: [&str; 16] = [
+    "North", "Blue", "Royal", "Golden", "Old", "Great", "Green", "Silver", "Eastern", "Western", "Little", "Grand",
+    "Bright", "Alpine", "Harbor", "Island",
+];
+
+const COMPANY_NOUNS: [&str; 16] = [
+    "Market",
+    "Pantry",
+    "Table",
+    "Kitchen",
+    "Harvest",
+    "Orchard",
+    "Mill",
+    "Cellar",
+    "Bakery",
+    "Delicatessen",
+    "Grocer",
+    "Larder",
+    "Farm",
+    "Dairy",
+    "Spice",
+    "Fishery",
+];
+
+const COMPANY_SUFFIXES: [&str; 6] = ["Traders", "Imports", "Foods", "& Co.", "Stores", "Supplies"];
+
+const STREETS: [&str; 12] = [
+    "Obere Str.",
+    "Avda. de la Constitucion",
+    "Mataderos",
+    "Hanover Sq.",
+    "Berguvsvagen",
+    "Forsterstr.",
+    "place Kleber",
+    "Gran Via",
+    "rue des Bouchers",
+    "Fauntleroy Circus",
+    "Cerrito",
+    "Walserweg",
+];
+
+/// City, region, country and postal code prefix of the customers
+const LOCATIONS: [(&str, Option<&str>, &str, &str); 16] = [
+    ("Berlin", None, "Germany", "12"),
+    ("Mexico D.F.", None, "Mexico", "05"),
+    ("London", None, "UK", "WC"),
+    ("Lulea", None, "Sweden", "S-95"),
+    ("Mannheim", None, "Germany", "68"),
+    ("Strasbourg", None, "France", "67"),
+    ("Madrid", None, "Spain", "28"),
+    ("Marseille", None, "France", "13"),
+    ("Tsawassen", Some("BC"), "Canada", "T2F"),
+    ("Buenos Aires", None, "Argentina", "10"),
+    ("Bern", None, "Switzerland", "30"),
+    ("Sao Paulo", Some("SP"), "Brazil", "05"),
+    ("Seattle", Some("WA"), "USA", "98"),
+    ("Portland", Some("OR"), "USA", "97"),
+    ("Lisboa", None, "Portugal", "17"),
+    ("Oulu", None, "Finland", "90"),
+];
+
+const PRODUCT_ADJECTIVES: [&str; 12] = [
+    "Organic", "Smoked", "Classic", "Spicy", "Sweet", "Aged", "Fresh", "Dried", "Wild", "Golden", "Rustic", "Premium",
+];
+
+/// Items of the original categories, by category name
+const PRODUCT_ITEMS: [(&str, [&str; 4]); 8] = [
+    ("Beverages", ["Lager", "Green Tea", "Coffee", "Cider"]),
+    ("Condiments", ["Mustard", "Syrup", "Hot Sauce", "Relish"]),
+    ("Confections", ["Chocolate", "Biscuits", "Marzipan", "Toffee"]),
+    ("Dairy Products", ["Gouda", "Camembert", "Mozzarella", "Butter"]),
+    ("Grains/Cereals", ["Gnocchi", "Crispbread", "Ravioli", "Oats"]),
+    ("Meat/Poultry", ["Sausages", "Pate", "Bacon", "Duck Breast"]),
+    ("Produce", ["Dried Apples", "Tofu", "Pears", "Tomatoes"]),
+    ("Seafood", ["Salmon", "Crab Meat", "Herring", "Caviar"]),
+];
+
+/// Items of the categories added since
+const OTHER_ITEMS: [&str; 4] = ["Preserves", "Crackers", "Noodles", "Honey"];
+
+const PACKAGES: [&str; 8] = [
+    "10 boxes x 20 bags",
+    "24 - 12 oz bottles",
+    "12 - 550 ml bottles",
+    "48 - 6 oz jars",
+    "36 boxes",
+    "12 - 200 g jars",
+    "24 - 250 g pkgs.",
+    "1 kg pkg.",
+];
+
+/// Rows of the database referenced by the generated rows, and where new keys start
+#[derive(Debug, Clone, Default)]
+pub struct References {
+    pub customers: Vec<Customer>,
+    pub products: Vec<Product>,
+    pub employee_ids: Vec<i16>,
+    pub shipper_ids: Vec<i16>,
+    /// Categories, with their names
+    pub categories: Vec<(i16, String)>,
+    pub supplier_ids: Vec<i16>,
+    pub next_order_id: i16,
+    pub next_product_id: i16,
+    /// Index of the next generated customer, generated IDs being `0` followed by a base 36 index
+    pub next_customer: usize,
+    /// Index of the next generated user, emailed `loadtest.<index>@example.com`
+    pub next_user: usize,
+}
+
+/// Number of rows to generate
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Counts {
+    pub customers: usize,
+    pub products: usize,
+    pub users: usize,
+    pub orders: usize,
+}
+
+/// Generated rows
+#[derive(Debug, Default)]
+pub struct Dataset {
+    pub customers: Vec<Customer>,
+    pub products: Vec<Product>,
+    pub users: Vec<User>,
+    pub orders: Vec<Order>,
+    pub order_details: Vec<OrderDetail>,
+}
+
+/// Index of a row in a list ordered by popularity, the first rows being picked the most
+fn popular<R: Rng>(rng: &mut R, len: usize) -> usize {
+    ((rng.gen::<f64>().powf(POPULARITY_SKEW) * len as f64) as usize).min(len - 1)
+}
+
+fn weighted<R: Rng, T: Copy>(rng: &mut R, choices: &[(T, u32)]) -> T {
+    let mut pick = rng.gen_range(0..choices.iter().map(|(_, weight)| weight).sum::<u32>());
+    for (choice, weight) in choices {
+        if pick < *weight {
+            return *choice;
+        }
+        pick -= weight;
+    }
+    unreachable!()
+}
+
+fn pick<'a, R: Rng, T>(rng: &mut R, choices: &'a [T]) -> &'a T {
+    choices.choose(rng).unwrap()
+}
+
+fn round2(value: f64) -> f32 {
+    ((value * 100.0).round() / 100.0) as f32
+}
+
+fn digits<R: Rng>(rng: &mut R, count: usize) -> String {
+    (0..count).map(|_| char::from(b'0' + rng.gen_range(0..10))).collect()
+}
+
+/// Customer ID of a generated customer, never colliding with the original letters only IDs
+pub fn customer_id(index: usize) -> String {
+    const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    let mut id = [b'0'; 5];
+    let mut rest = index;
+    for position in (1..5).rev() {
+        id[position] = DIGITS[rest % 36];
+        rest /= 36;
+    }
+    String::from_utf8(id.to_vec()).unwrap()
+}
+
+/// Largest number of generated customers
+pub const MAX_CUSTOMERS: usize = 36 * 36 * 36 * 36;
+
+pub struct Generator {
+    rng: StdRng,
+    /// Orders are placed between these dates, `to` excluded
+    from: NaiveDate,
+    to: NaiveDate,
+    /// Hash of the password of the generated users
+    password_hash: String,
+}
+
+impl Generator {
+    pub fn new(seed: u64, from: NaiveDate, to: NaiveDate, password_hash: String) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            from,
+            to,
+            password_hash,
+        }
+    }
+
+    /// Date of the period, later dates being more likely
+    fn date(&mut self) -> NaiveDate {
+        let days = (self.to - self.from).num_days().max(1) as f64;
+        self.from + Duration::days((self.rng.gen::<f64>().sqrt() * days) as i64)
+    }
+
+    fn date_time(&mut self) -> NaiveDateTime {
+        let seconds = self.rng.gen_range(0..86_400);
+        self.date().and_time(NaiveTime::MIN) + Duration::seconds(seconds)
+    }
+
+    fn name(&mut self) -> (String, String) {
+        (
+            pick(&mut self.rng, &FIRST_NAMES).to_string(),
+            pick(&mut self.rng, &LAST_NAMES).to_string(),
+        )
+    }
+
+    fn customer(&mut self, index: usize) -> Customer {
+        let (first_name, last_name) = self.name();
+        let (city, region, country, postal_prefix) = *pick(&mut self.rng, &LOCATIONS);
+        let phone = format!(
+            "({}) {}-{}",
+            digits(&mut self.rng, 3),
+            digits(&mut self.rng, 3),
+            digits(&mut self.rng, 4)
+        );
+        let fax = if self.rng.gen_bool(0.5) {
+            Some(format!(
+                "({}) {}-{}",
+                digits(&mut self.rng, 3),
+                digits(&mut self.rng, 3),
+                digits(&mut self.rng, 4)
+            ))
+        } else {
+            None
+        };
+
+        Customer {
+            customer_id: customer_id(index),
+            company_name: format!(
+                "{} {} {}",
+                pick(&mut self.rng, &COMPANY_PREFIXES),
+                pick(&mut self.rng, &COMPANY_NOUNS),
+                pick(&mut self.rng, &COMPANY_SUFFIXES)
+            ),
+            contact_name: Some(format!("{} {}", first_name, last_name)),
+            contact_title: Some(pick(&mut self.rng, &CONTACT_TITLES).to_string()),
+            address: Some(format!(
+                "{} {}",
+                pick(&mut self.rng, &STREETS),
+                self.rng.gen_range(1..300)
+            )),
+            city: Some(city.to_owned()),
+            region: region.map(str::to_owned),
+            postal_code: Some(format!("{}{}", postal_prefix, digits(&mut self.rng, 3))),
+            country: Some(country.to_owned()),
+            phone: Some(phone),
+            fax,
+        }
+    }
+
+    fn product(&mut self, product_id: i16, references: &References) -> Product {
+        let (category_id, category_name) = pick(&mut self.rng, &references.categories).clone();
+        let items = PRODUCT_ITEMS
+            .iter()
+            .find(|(name, _)| *name == category_name)
+            .map(|(_, items)| &items[..])
+            .unwrap_or(&OTHER_ITEMS);
+        let units_on_order = if self.rng.gen_bool(0.2) {
+            self.rng.gen_range(1..10) * 10
+        } else {
+            0
+        };
+
+        Product {
+            product_id,
+            product_name: format!(
+                "{} {}",
+                pick(&mut self.rng, &PRODUCT_ADJECTIVES),
+                pick(&mut self.rng, items)
+            ),
+            supplier_id: Some(*pick(&mut self.rng, &references.supplier_ids)),
+            category_id: Some(category_id),
+            quantity_per_unit: Some(pick(&mut self.rng, &PACKAGES).to_string()),
+            // From 2 to 250, cheap products being the most common
+            unit_price: Some(round2(2.0 * 125f64.powf(self.rng.gen::<f64>().powi(2)))),
+            units_in_stock: Some(self.rng.gen_range(0..125)),
+            units_on_order: Some(units_on_order),
+            reorder_level: Some(self.rng.gen_range(0..7) * 5),
+            discontinued: self.rng.gen_bool(0.05) as i32,
+        }
+    }
+
+    fn user(&mut self, index: usize) -> User {
+        let (firstname, lastname) = self.name();
+        let created_at = self.date_time();
+        let id = Builder::from_bytes(self.rng.gen())
+            .set_variant(Variant::RFC4122)
+            .set_version(Version::Random)
+            .build();
+
+        User::init(
+            id,
+            lastname,
+            firstname,
+            format!("loadtest.{}@example.com", index),
+            self.password_hash.clone(),
+            ROLE_USER.to_owned(),
+            created_at,
+            created_at,
+            None,
+            1,
+        )
+    }
+
+    fn order(
+        &mut self,
+        order_id: i16,
+        order_date: NaiveDate,
+        customers: &[&Customer],
+        products: &[&Product],
+        references: &References,
+    ) -> (Order, Vec<OrderDetail>) {
+        let customer = customers[popular(&mut self.rng, customers.len())];
+
+        let lines = weighted(&mut self.rng, &ORDER_LINES).min(products.len());
+        let mut details: Vec<OrderDetail> = Vec::with_capacity(lines);
+        while details.len() < lines {
+            let product = products[popular(&mut self.rng, products.len())];
+            if details.iter().any(|detail| detail.product_id == product.product_id) {
+                continue;
+            }
+            // Quantities from 1 to 120, small ones being the most common
+            let quantity = 1 + (self.rng.gen::<f64>().powi(2) * 120.0) as i16;
+            details.push(OrderDetail {
+                order_id,
+                product_id: product.product_id,
+                unit_price: product.unit_price.unwrap_or_default(),
+                quantity,
+                discount: weighted(&mut self.rng, &DISCOUNTS),
+            });
+        }
+        let subtotal: f64 = details
+            .iter()
+            .map(|d| d.unit_price as f64 * d.quantity as f64 * (1.0 - d.discount as f64))
+            .sum();
+
+        // Orders are shipped within 1 to 35 days, or not yet at the end of the period
+        let shipped_date = Some(order_date + Duration::days(1 + (self.rng.gen::<f64>().powi(3) * 35.0) as i64))
+            .filter(|shipped_date| *shipped_date < self.to);
+        let required_days = weighted(&mut self.rng, &[(28, 85), (14, 10), (42, 5)]);
+
+        let order = Order {
+            order_id,
+            customer_id: Some(customer.customer_id.clone()),
+            employee_id: Some(*pick(&mut self.rng, &references.employee_ids)),
+            order_date: Some(order_date),
+            required_date: Some(order_date + Duration::days(required_days)),
+            shipped_date,
+            ship_via: Some(*pick(&mut self.rng, &references.shipper_ids)),
+            freight: Some(round2(1.0 + subtotal * self.rng.gen_range(0.01..0.08))),
+            ship_name: Some(customer.company_name.clone()),
+            ship_address: customer.address.clone(),
+            ship_city: customer.city.clone(),
+            ship_region: customer.region.clone(),
+            ship_postal_code: customer.postal_code.clone(),
+            ship_country: customer.country.clone(),
+        };
+
+        (order, details)
+    }
+
+    /// Generates the rows, orders referencing the existing and the generated customers and
+    /// products
+    pub fn generate(&mut self, counts: &Counts, references: &References) -> Result<Dataset, String> {
+        let available = |next: i16| i16::MAX as usize - next as usize + 1;
+        if counts.products > available(references.next_product_id) {
+            return Err(format!(
+                "at most {} products can be added, product IDs being smallint",
+                available(references.next_product_id)
+            ));
+        }
+        if counts.orders > available(references.next_order_id) {
+            return Err(format!(
+                "at most {} orders can be added, order IDs being smallint",
+                available(references.next_order_id)
+            ));
+        }
+        if references.next_customer + counts.customers > MAX_CUSTOMERS {
+            return Err(format!("at most {} customers can be generated", MAX_CUSTOMERS));
+        }
+        if counts.products > 0 && (references.categories.is_empty() || references.supplier_ids.is_empty()) {
+            return Err(String::from("products need at least a category and a supplier"));
+        }
+
+        let mut dataset = Dataset {
+            customers: (0..counts.customers)
+                .map(|i| self.customer(references.next_customer + i))
+                .collect(),
+            ..Dataset::default()
+        };
+        dataset.products = (0..counts.products)
+            .map(|i| self.product(references.next_product_id + i as i16, references))
+            .collect();
+        dataset.users = (0..counts.users).map(|i| self.user(references.next_user + i)).collect();
+
+        if counts.orders > 0 {
+            // Popularity follows the shuffled order of the customers and products
+            let mut customers: Vec<&Customer> = references.customers.iter().chain(&dataset.customers).collect();
+            let mut products: Vec<&Product> = references
+                .products
+                .iter()
+                .chain(&dataset.products)
+                .filter(|product| product.discontinued == 0)
+                .collect();
+            if customers.is_empty() || products.is_empty() {
+                return Err(String::from("orders need at least a customer and a product"));
+            }
+            if references.employee_ids.is_empty() || references.shipper_ids.is_empty() {
+                return Err(String::from("orders need at least an employee and a shipper"));
+            }
+            customers.shuffle(&mut self.rng);
+            products.shuffle(&mut self.rng);
+
+            // Order IDs follow the order dates
+            let mut dates: Vec<NaiveDate> = (0..counts.orders).map(|_| self.date()).collect();
+            dates.sort_unstable();
+            for (i, order_date) in dates.into_iter().enumerate() {
+                let order_id = references.next_order_id + i as i16;
+                let (order, details) = self.order(order_id, order_date, &customers, &products, references);
+                dataset.orders.push(order);
+                dataset.order_details.extend(details);
+            }
+        }
+
+        Ok(dataset)
+    }
+}
+
+#[cfg(test)]
+fn references() -> References {
+    let mut generator = Generator::new(0, NaiveDate::MIN, NaiveDate::MIN, String::new());
+    let categories = vec![(1, String::from("Beverages")), (9, String::from("Frozen"))];
+    let mut references = References {
+        employee_ids: vec![1, 2, 3],
+        shipper_ids: vec![1, 2],
+        categories,
+        supplier_ids: vec![1, 2, 3],
+        next_order_id: 10248,
+        next_product_id: 10,
+        ..References::default()
+    };
+    references.customers = vec![Customer {
+        customer_id: String::from("ALFKI"),
+        ..generator.customer(0)
+    }];
+    references.products = (1..10).map(|id| generator.product(id, &references)).collect();
+
+    references
+}
+
+#[cfg(test)]
+fn generate(seed: u64) -> Dataset {
+    let counts = Counts {
+        customers: 50,
+        products: 20,
+        users: 10,
+        orders: 500,
+    };
+    let (from, to) = (
+        NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+        NaiveDate::from_ymd_opt(2026, 1, 1).unwrap(),
+    );
+
+    Generator::new(seed, from, to, String::from("hash"))
+        .generate(&counts, &references())
+        .unwrap()
+}
+
+#[test]
+fn test_generate_is_deterministic() {
+    let json = |dataset: &Dataset| {
+        serde_json::to_string(&(
+            &dataset.customers,
+            &dataset.products,
+            &dataset.users,
+            &dataset.orders,
+            &dataset.order_details,
+        ))
+        .unwrap()
+    };
+
+    assert_eq!(json(&generate(42)), json(&generate(42)));
+    assert_ne!(json(&generate(42)), json(&generate(43)));
+}
+
+#[test]
+fn test_generate_keeps_keys_consistent() {
+    use std::collections::{HashMap, HashSet};
+
+    let references = references();
+    let dataset = generate(42);
+    assert_eq!(50, dataset.customers.len());
+    assert_eq!(20, dataset.products.len());
+    assert_eq!(10, dataset.users.len());
+    assert_eq!(500, dataset.orders.len());
+
+    let customers: HashSet<&str> = references
+        .customers
+        .iter()
+        .chain(&dataset.customers)
+        .map(|c| c.customer_id.as_str())
+        .collect();
+    assert_eq!(51, customers.len());
+    let products: HashMap<i16, &Product> = references
+        .products
+        .iter()
+        .chain(&dataset.products)
+        .map(|p| (p.product_id, p))
+        .collect();
+    assert_eq!(29, products.len());
+    let orders: HashSet<i16> = dataset.orders.iter().map(|o| o.order_id).collect();
+    assert_eq!((10248..10748).collect::<HashSet<i16>>(), orders);
+
+    for order in &dataset.orders {
+        assert!(customers.contains(order.customer_id.as_deref().unwrap()));
+        assert!(references.employee_ids.contains(&order.employee_id.unwrap()));
+        assert!(references.shipper_ids.contains(&order.ship_via.unwrap()));
+        assert!(order.required_date > order.order_date);
+    }
+    let mut lines = HashSet::new();
+    for detail in &dataset.order_details {
+        assert!(orders.contains(&detail.order_id));
+        assert!(lines.insert((detail.order_id, detail.product_id)));
+        assert_eq!(products[&detail.product_id].unit_price.unwrap(), detail.unit_price);
+    }
+    for product in &dataset.products {
+        assert!(references.supplier_ids.contains(&product.supplier_id.unwrap()));
+    }
+
+    // Columns lengths
+    assert!(dataset.customers.iter().all(|c| c.company_name.len() <= 40
+        && c.contact_name.as_ref().unwrap().len() <= 30
+        && c.city.as_ref().unwrap().len() <= 15));
+    assert!(dataset.products.iter().all(|p| p.product_name.len() <= 40));
+}
+
+#[test]
+fn test_popularity_is_skewed() {
+    use std::collections::HashMap;
+
+    let dataset = generate(7);
+    let mut orders_per_customer: HashMap<&str, usize> = HashMap::new();
+    for order in &dataset.orders {
+        *orders_per_customer
+            .entry(order.customer_id.as_deref().unwrap())
+            .or_default() += 1;
+    }
+    let mut counts: Vec<usize> = orders_per_customer.values().copied().collect();
+    counts.sort_unstable_by(|a, b| b.cmp(a));
+
+    // The top 10% of the customers place more than a third of the orders
+    assert!(counts.iter().take(5).sum::<usize>() * 3 > dataset.orders.len());
+}
+
+#[test]
+fn test_limits() {
+    let references = References {
+        next_order_id: i16::MAX,
+        ..references()
+    };
+    let counts = Counts {
+        orders: 2,
+        ..Counts::default()
+    };
+    let mut generator = Generator::new(0, NaiveDate::MIN, NaiveDate::MAX, String::new());
+
+    assert_eq!(
+        Err(String::from("at most 1 orders can be added, order IDs being smallint")),
+        generator.generate(&counts, &references).map(|_| ())
+    );
+    assert_eq!("00000", customer_id(0));
+    assert_eq!("0000Z", customer_id(35));
+    assert_eq!("00010", customer_id(36));
+    assert_eq!("0ZZZZ", customer_id(MAX_CUSTOMERS - 1));
+}
@@ -0,0 +1,314 @@
+//! Synthetic data command module
+//!
+//! Generated rows are bulk inserted with `COPY` in a single transaction, the row triggers of
+//! the tables being disabled meanwhile: generated rows are neither notified nor published as
+//! domain events.
+
+use chrono::NaiveDate;
+use color_eyre::Result;
+use eyre::{eyre, WrapErr};
+use northwind_actix::export::{Cell, Tabular};
+use northwind_traders::domain::catalog::Product;
+use northwind_traders::domain::customer::Customer;
+use northwind_user::domain::user::hash_password;
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+use std::fmt::Display;
+use structopt::StructOpt;
+
+use crate::dataset::{Counts, Generator, References, MAX_CUSTOMERS};
+use crate::output::{print, Format};
+
+/// Size of the chunks sent to a `COPY` stream
+const COPY_CHUNK_SIZE: usize = 1 << 20;
+
+/// Tables filled by the generator, in the order of their foreign keys
+const TABLES: [&str; 5] = ["customers", "products", "users", "orders", "order_details"];
+
+#[derive(Debug, StructOpt)]
+pub struct GenerateCommand {
+    /// Seed of the generator, the same seed giving the same rows on the same database
+    #[structopt(long, default_value = "1")]
+    seed: u64,
+    #[structopt(long, default_value = "0")]
+    customers: usize,
+    #[structopt(long, default_value = "0")]
+    products: usize,
+    #[structopt(long, default_value = "0")]
+    users: usize,
+    #[structopt(long, default_value = "0")]
+    orders: usize,
+    /// First day of the orders
+    #[structopt(long, default_value = "2024-01-01")]
+    from: NaiveDate,
+    /// Day after the last day of the orders
+    #[structopt(long, default_value = "2026-01-01")]
+    to: NaiveDate,
+    /// Password of the generated users, emailed `loadtest.<n>@example.com`
+    #[structopt(long, default_value = "loadtest")]
+    password: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Generated {
+    table: &'static str,
+    rows: u64,
+}
+
+impl Tabular for Generated {
+    fn columns() -> &'static [&'static str] {
+        &["Table", "Generated rows"]
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![self.table.into(), (self.rows as i64).into()]
+    }
+}
+
+/// Reads the rows referenced by the generated rows
+async fn references(conn: &mut PgConnection) -> Result<References> {
+    let customers = sqlx::query_as!(Customer, r#"SELECT * FROM customers ORDER BY customer_id"#)
+        .fetch_all(&mut *conn)
+        .await?;
+    let next_customer = customers.iter().filter(|c| c.customer_id.starts_with('0')).count();
+
+    Ok(References {
+        customers,
+        products: sqlx::query_as!(Product, r#"SELECT * FROM products ORDER BY product_id"#)
+            .fetch_all(&mut *conn)
+            .await?,
+        employee_ids: sqlx::query_scalar!(r#"SELECT employee_id FROM employees ORDER BY employee_id"#)
+            .fetch_all(&mut *conn)
+            .await?,
+        shipper_ids: sqlx::query_scalar!(r#"SELECT shipper_id FROM shippers ORDER BY shipper_id"#)
+            .fetch_all(&mut *conn)
+            .await?,
+        categories: sqlx::query!(r#"SELECT category_id, category_name FROM categories ORDER BY category_id"#)
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|c| (c.category_id, c.category_name))
+            .collect(),
+        supplier_ids: sqlx::query_scalar!(r#"SELECT supplier_id FROM suppliers ORDER BY supplier_id"#)
+            .fetch_all(&mut *conn)
+            .await?,
+        next_order_id: sqlx::query_scalar!(r#"SELECT COALESCE(MAX(order_id) + 1, 1)::smallint AS "id!" FROM orders"#)
+            .fetch_one(&mut *conn)
+            .await?,
+        next_product_id: sqlx::query_scalar!(
+            r#"SELECT COALESCE(MAX(product_id) + 1, 1)::smallint AS "id!" FROM products"#
+        )
+        .fetch_one(&mut *conn)
+        .await?,
+        next_customer,
+        next_user: sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM users WHERE email LIKE 'loadtest.%@example.com'"#
+        )
+        .fetch_one(&mut *conn)
+        .await? as usize,
+    })
+}
+
+/// Field of the text format of `COPY`, `\N` being NULL
+fn field<T: Display>(value: Option<T>) -> String {
+    match value {
+        None => String::from("\\N"),
+        Some(value) => value
+            .to_string()
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r"),
+    }
+}
+
+/// Copies rows into a table, each row being given as its fields
+async fn copy<T>(
+    conn: &mut PgConnection,
+    table: &str,
+    columns: &str,
+    rows: &[T],
+    fields: impl Fn(&T) -> Vec<String>,
+) -> Result<u64> {
+    let mut copy = conn
+        .copy_in_raw(&format!("COPY {} ({}) FROM STDIN", table, columns))
+        .await?;
+
+    let mut chunk = String::with_capacity(COPY_CHUNK_SIZE);
+    for row in rows {
+        chunk.push_str(&fields(row).join("\t"));
+        chunk.push('\n');
+        if chunk.len() >= COPY_CHUNK_SIZE {
+            copy.send(chunk.as_bytes()).await?;
+            chunk.clear();
+        }
+    }
+    if !chunk.is_empty() {
+        copy.send(chunk.as_bytes()).await?;
+    }
+
+    copy.finish().await.with_context(|| format!("copying {}", table))
+}
+
+pub async fn run(command: GenerateCommand, pool: &PgPool, format: Format) -> Result<()> {
+    if command.from >= command.to {
+        return Err(eyre!("--from must be before --to"));
+    }
+    let counts = Counts {
+        customers: command.customers,
+        products: command.products,
+        users: command.users,
+        orders: command.orders,
+    };
+    if counts.customers > MAX_CUSTOMERS {
+        return Err(eyre!("at most {} customers can be generated", MAX_CUSTOMERS));
+    }
+
+    let mut tx = pool.begin().await?;
+    let references = references(&mut tx).await?;
+    let dataset = Generator::new(command.seed, command.from, command.to, hash_password(&command.password))
+        .generate(&counts, &references)
+        .map_err(|e| eyre!(e))?;
+
+    for table in TABLES.iter() {
+        sqlx::query(&format!("ALTER TABLE {} DISABLE TRIGGER USER", table))
+            .execute(&mut tx)
+            .await?;
+    }
+
+    let customers = copy(
+        &mut tx,
+        "customers",
+        "customer_id, company_name, contact_name, contact_title, address, city, region, postal_code, country, phone, fax",
+        &dataset.customers,
+        |c| {
+            vec![
+                field(Some(&c.customer_id)),
+                field(Some(&c.company_name)),
+                field(c.contact_name.as_ref()),
+                field(c.contact_title.as_ref()),
+                field(c.address.as_ref()),
+                field(c.city.as_ref()),
+                field(c.region.as_ref()),
+                field(c.postal_code.as_ref()),
+                field(c.country.as_ref()),
+                field(c.phone.as_ref()),
+                field(c.fax.as_ref()),
+            ]
+        },
+    )
+    .await?;
+    let products = copy(
+        &mut tx,
+        "products",
+        "product_id, product_name, supplier_id, category_id, quantity_per_unit, unit_price, units_in_stock, \
+         units_on_order, reorder_level, discontinued",
+        &dataset.products,
+        |p| {
+            vec![
+                field(Some(p.product_id)),
+                field(Some(&p.product_name)),
+                field(p.supplier_id),
+                field(p.category_id),
+                field(p.quantity_per_unit.as_ref()),
+                field(p.unit_price),
+                field(p.units_in_stock),
+                field(p.units_on_order),
+                field(p.reorder_level),
+                field(Some(p.discontinued)),
+            ]
+        },
+    )
+    .await?;
+    let users = copy(
+        &mut tx,
+        "users",
+        "id, lastname, firstname, email, password, role, created_at, updated_at, version",
+        &dataset.users,
+        |u| {
+            vec![
+                field(Some(u.id)),
+                field(Some(&u.lastname)),
+                field(Some(&u.firstname)),
+                field(Some(&u.email)),
+                field(Some(&u.password)),
+                field(Some(&u.role)),
+                field(Some(u.created_at)),
+                field(Some(u.updated_at)),
+                field(Some(u.version)),
+            ]
+        },
+    )
+    .await?;
+    let orders = copy(
+        &mut tx,
+        "orders",
+        "order_id, customer_id, employee_id, order_date, required_date, shipped_date, ship_via, freight, \
+         ship_name, ship_address, ship_city, ship_region, ship_postal_code, ship_country",
+        &dataset.orders,
+        |o| {
+            vec![
+                field(Some(o.order_id)),
+                field(o.customer_id.as_ref()),
+                field(o.employee_id),
+                field(o.order_date),
+                field(o.required_date),
+                field(o.shipped_date),
+                field(o.ship_via),
+                field(o.freight),
+                field(o.ship_name.as_ref()),
+                field(o.ship_address.as_ref()),
+                field(o.ship_city.as_ref()),
+                field(o.ship_region.as_ref()),
+                field(o.ship_postal_code.as_ref()),
+                field(o.ship_country.as_ref()),
+            ]
+        },
+    )
+    .await?;
+    let order_details = copy(
+        &mut tx,
+        "order_details",
+        "order_id, product_id, unit_price, quantity, discount",
+        &dataset.order_details,
+        |d| {
+            vec![
+                field(Some(d.order_id)),
+                field(Some(d.product_id)),
+                field(Some(d.unit_price)),
+                field(Some(d.quantity)),
+                field(Some(d.discount)),
+            ]
+        },
+    )
+    .await?;
+
+    for table in TABLES.iter() {
+        sqlx::query(&format!("ALTER TABLE {} ENABLE TRIGGER USER", table))
+            .execute(&mut tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    // Fresh statistics for the planner, the tables having grown at once
+    sqlx::query(&format!("ANALYZE {}", TABLES.join(", ")))
+        .execute(pool)
+        .await?;
+
+    let rows = [customers, products, users, orders, order_details];
+    print(
+        format,
+        &TABLES
+            .iter()
+            .zip(rows.iter())
+            .map(|(table, rows)| Generated { table, rows: *rows })
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[test]
+fn test_field() {
+    assert_eq!("\\N", field::<&str>(None));
+    assert_eq!("a\\tb\\\\c\\nd", field(Some("a\tb\\c\nd")));
+    assert_eq!("2024-01-31", field(NaiveDate::from_ymd_opt(2024, 1, 31)));
+}
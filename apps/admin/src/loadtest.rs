@@ -0,0 +1,391 @@
+//! Load test command module
+//!
+//! Virtual users run a scenario concurrently against a running server: each iteration logs
+//! in, creates, reads, updates and deletes a user with the token, then reads orders. The
+//! latency and errors of every step are reported once all iterations are done.
+
+use actix_web::client::{Client, ClientRequest};
+use actix_web::http::header::{AUTHORIZATION, ETAG, IF_MATCH};
+use chrono::Utc;
+use color_eyre::Result;
+use eyre::eyre;
+use futures::future::join_all;
+use northwind_actix::config::Config;
+use northwind_actix::export::{Cell, Tabular};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+
+use crate::output::{print, Format};
+
+/// Largest response body read, users being listed in a single response
+const BODY_LIMIT: usize = 16 << 20;
+
+/// Orders read by the `orders` step, through GraphQL
+const ORDERS_QUERY: &str = "query Orders($offset: Int) { \
+     orders(limit: 10, offset: $offset) { orderId orderDate customer { companyName } \
+     details { quantity unitPrice product { productName } } } }";
+
+#[derive(Debug, StructOpt)]
+pub struct LoadTestCommand {
+    /// Base URL of the server, `http://{server.host}:{server.port}` by default
+    #[structopt(long)]
+    url: Option<String>,
+    /// Number of virtual users running the scenario concurrently
+    #[structopt(long, default_value = "10")]
+    concurrency: usize,
+    /// Number of iterations of the scenario per virtual user
+    #[structopt(long, default_value = "100")]
+    iterations: usize,
+    /// Number of `loadtest.<n>@example.com` users logged in as, generated by `generate`
+    #[structopt(long, default_value = "1")]
+    users: usize,
+    /// Password of the `loadtest.<n>@example.com` users
+    #[structopt(long, default_value = "loadtest")]
+    password: String,
+    /// Request timeout, in seconds
+    #[structopt(long, default_value = "10")]
+    timeout: u64,
+    /// Fails if the error rate of a step is above this ratio, e.g. `0.01`
+    #[structopt(long)]
+    max_error_rate: Option<f64>,
+    /// Fails if the 99th percentile latency of a step is above this many milliseconds
+    #[structopt(long)]
+    max_p99: Option<f64>,
+}
+
+/// Steps of the scenario, in their order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Login,
+    ListUsers,
+    CreateUser,
+    GetUser,
+    UpdateUser,
+    DeleteUser,
+    Orders,
+}
+
+impl Step {
+    const ALL: [Step; 7] = [
+        Step::Login,
+        Step::ListUsers,
+        Step::CreateUser,
+        Step::GetUser,
+        Step::UpdateUser,
+        Step::DeleteUser,
+        Step::Orders,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Step::Login => "login",
+            Step::ListUsers => "list users",
+            Step::CreateUser => "create user",
+            Step::GetUser => "get user",
+            Step::UpdateUser => "update user",
+            Step::DeleteUser => "delete user",
+            Step::Orders => "orders",
+        }
+    }
+}
+
+/// Requests sent by a step
+#[derive(Debug, Default, Clone)]
+struct Samples {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+impl Samples {
+    fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        self.errors += other.errors;
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct StepStats {
+    step: &'static str,
+    requests: usize,
+    errors: usize,
+    error_rate: f64,
+    /// Requests per second
+    throughput: f64,
+    /// Latency percentiles and maximum, in milliseconds
+    p50: f64,
+    p95: f64,
+    p99: f64,
+    max: f64,
+}
+
+impl Tabular for StepStats {
+    fn columns() -> &'static [&'static str] {
+        &[
+            "Step",
+            "Requests",
+            "Errors",
+            "Error rate",
+            "Req/s",
+            "p50 (ms)",
+            "p95 (ms)",
+            "p99 (ms)",
+            "Max (ms)",
+        ]
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            self.step.into(),
+            (self.requests as i64).into(),
+            (self.errors as i64).into(),
+            self.error_rate.into(),
+            self.throughput.into(),
+            self.p50.into(),
+            self.p95.into(),
+            self.p99.into(),
+            self.max.into(),
+        ]
+    }
+}
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let factor = 10_f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+/// Nearest-rank percentile of sorted latencies, in milliseconds to a tenth
+fn percentile(sorted: &[Duration], percent: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    round(sorted[rank.clamp(1, sorted.len()) - 1].as_secs_f64() * 1000.0, 1)
+}
+
+fn stats(step: &'static str, samples: &Samples, elapsed: Duration) -> StepStats {
+    let mut latencies = samples.latencies.clone();
+    latencies.sort_unstable();
+    let requests = latencies.len();
+
+    StepStats {
+        step,
+        requests,
+        errors: samples.errors,
+        error_rate: if requests == 0 {
+            0.0
+        } else {
+            round(samples.errors as f64 / requests as f64, 4)
+        },
+        throughput: round(requests as f64 / elapsed.as_secs_f64().max(f64::EPSILON), 1),
+        p50: percentile(&latencies, 50.0),
+        p95: percentile(&latencies, 95.0),
+        p99: percentile(&latencies, 99.0),
+        max: percentile(&latencies, 100.0),
+    }
+}
+
+/// Successful response of a request
+struct Response {
+    etag: Option<String>,
+    body: Value,
+}
+
+/// Runs the scenario with its own samples
+struct VirtualUser {
+    client: Client,
+    url: String,
+    id: usize,
+    /// Suffix of the emails of the created users, unique to the run
+    run: i64,
+    login: Value,
+    samples: Vec<Samples>,
+}
+
+impl VirtualUser {
+    /// Sends a request, recording its latency and whether it failed, a failed request ending the iteration
+    async fn send(&mut self, step: Step, request: ClientRequest, body: Option<Value>) -> Option<Response> {
+        let started = Instant::now();
+        let response = match body {
+            Some(body) => request.send_json(&body).await,
+            None => request.send().await,
+        };
+        let response = match response {
+            Ok(mut response) if response.status().is_success() => {
+                let etag = response
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|etag| etag.to_str().ok())
+                    .map(String::from);
+                response.body().limit(BODY_LIMIT).await.ok().and_then(|bytes| {
+                    let body = if bytes.is_empty() {
+                        Value::Null
+                    } else {
+                        serde_json::from_slice(&bytes).ok()?
+                    };
+                    // GraphQL reports errors in successful responses
+                    match body.get("errors") {
+                        Some(_) => None,
+                        None => Some(Response { etag, body }),
+                    }
+                })
+            }
+            _ => None,
+        };
+
+        let samples = &mut self.samples[step as usize];
+        samples.latencies.push(started.elapsed());
+        if response.is_none() {
+            samples.errors += 1;
+        }
+        response
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+
+    async fn iteration(&mut self, iteration: usize) -> Option<()> {
+        let login = self.client.post(self.url("/v1/login"));
+        let response = self.send(Step::Login, login, Some(self.login.clone())).await?;
+        let authorization = format!("Bearer {}", response.body["token"].as_str()?);
+
+        let request = self
+            .client
+            .get(self.url("/v1/users"))
+            .header(AUTHORIZATION, authorization.as_str());
+        self.send(Step::ListUsers, request, None).await?;
+
+        let email = format!("crud.{}.{}.{}@loadtest.example.com", self.run, self.id, iteration);
+        let request = self.client.post(self.url("/v1/register"));
+        let body = json!({"lastname": "Load", "firstname": "Test", "email": email, "password": "loadtest"});
+        let response = self.send(Step::CreateUser, request, Some(body)).await?;
+        let user = self.url(&format!("/v1/users/{}", response.body["id"].as_str()?));
+
+        let request = self.client.get(&user).header(AUTHORIZATION, authorization.as_str());
+        let response = self.send(Step::GetUser, request, None).await?;
+
+        let request = self
+            .client
+            .put(&user)
+            .header(AUTHORIZATION, authorization.as_str())
+            .header(IF_MATCH, response.etag?);
+        let body = json!({"lastname": "Load", "firstname": format!("Test {}", iteration)});
+        let response = self.send(Step::UpdateUser, request, Some(body)).await?;
+
+        let request = self
+            .client
+            .delete(&user)
+            .header(AUTHORIZATION, authorization.as_str())
+            .header(IF_MATCH, response.etag?);
+        self.send(Step::DeleteUser, request, None).await?;
+
+        let request = self
+            .client
+            .post(self.url("/graphql"))
+            .header(AUTHORIZATION, authorization.as_str());
+        let body = json!({"query": ORDERS_QUERY, "variables": {"offset": (self.id * 31 + iteration * 10) % 800}});
+        self.send(Step::Orders, request, Some(body)).await?;
+
+        Some(())
+    }
+
+    async fn run(mut self, iterations: usize) -> Vec<Samples> {
+        for iteration in 0..iterations {
+            self.iteration(iteration).await;
+        }
+        self.samples
+    }
+}
+
+pub async fn run(command: LoadTestCommand, settings: &Config, format: Format) -> Result<()> {
+    if command.concurrency == 0 || command.users == 0 {
+        return Err(eyre!("--concurrency and --users must be positive"));
+    }
+    let url = command
+        .url
+        .clone()
+        .unwrap_or_else(|| format!("http://{}:{}", settings.server.host, settings.server.port))
+        .trim_end_matches('/')
+        .to_string();
+
+    let client = Client::builder().timeout(Duration::from_secs(command.timeout)).finish();
+    let run = Utc::now().timestamp_millis();
+    let users = (0..command.concurrency).map(|id| {
+        VirtualUser {
+            client: client.clone(),
+            url: url.clone(),
+            id,
+            run,
+            login: json!({
+                "email": format!("loadtest.{}@example.com", id % command.users),
+                "password": command.password,
+            }),
+            samples: vec![Samples::default(); Step::ALL.len()],
+        }
+        .run(command.iterations)
+    });
+
+    let started = Instant::now();
+    let mut samples = vec![Samples::default(); Step::ALL.len()];
+    for user_samples in join_all(users).await {
+        for (samples, user_samples) in samples.iter_mut().zip(user_samples) {
+            samples.merge(user_samples);
+        }
+    }
+    let elapsed = started.elapsed();
+
+    let mut total = Samples::default();
+    let mut rows: Vec<StepStats> = Step::ALL
+        .iter()
+        .zip(samples)
+        .map(|(step, samples)| {
+            let row = stats(step.name(), &samples, elapsed);
+            total.merge(samples);
+            row
+        })
+        .collect();
+    let failed: Vec<&'static str> = rows
+        .iter()
+        .filter(|row| {
+            command.max_error_rate.is_some_and(|max| row.error_rate > max)
+                || command.max_p99.is_some_and(|max| row.p99 > max)
+        })
+        .map(|row| row.step)
+        .collect();
+    rows.push(stats("total", &total, elapsed));
+    print(format, &rows)?;
+
+    if !failed.is_empty() {
+        return Err(eyre!("thresholds exceeded by {}", failed.join(", ")));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_percentile() {
+    let latencies: Vec<Duration> = (1..=200).map(Duration::from_millis).collect();
+
+    assert_eq!(100.0, percentile(&latencies, 50.0));
+    assert_eq!(190.0, percentile(&latencies, 95.0));
+    assert_eq!(198.0, percentile(&latencies, 99.0));
+    assert_eq!(200.0, percentile(&latencies, 100.0));
+    assert_eq!(1.0, percentile(&latencies[..1], 99.0));
+    assert_eq!(0.0, percentile(&[], 50.0));
+}
+
+#[test]
+fn test_stats() {
+    let samples = Samples {
+        latencies: vec![Duration::from_millis(20), Duration::from_millis(10)],
+        errors: 1,
+    };
+    let stats = stats("login", &samples, Duration::from_secs(2));
+
+    assert_eq!(2, stats.requests);
+    assert_eq!(0.5, stats.error_rate);
+    assert_eq!(1.0, stats.throughput);
+    assert_eq!(10.0, stats.p50);
+    assert_eq!(20.0, stats.max);
+}
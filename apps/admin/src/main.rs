@@ -0,0 +1,104 @@
+use color_eyre::Result;
+use northwind_actix::config::{Config, ConfigArgs};
+use northwind_actix::db::configure_db_pool;
+use northwind_core::audit::AuditContext;
+use northwind_user::repositories::user_repository_impl::UserRepositoryImpl;
+use std::path::PathBuf;
+use std::sync::Arc;
+use structopt::StructOpt;
+
+mod config;
+mod dataset;
+mod generate;
+mod loadtest;
+mod migrations;
+mod output;
+mod users;
+
+use generate::GenerateCommand;
+use loadtest::LoadTestCommand;
+use migrations::MigrateCommand;
+use output::Format;
+use users::UsersCommand;
+
+/// Northwind administration tool
+#[derive(Debug, StructOpt)]
+#[structopt(name = "northwind-admin")]
+struct Cli {
+    #[structopt(flatten)]
+    config: ConfigArgs,
+
+    /// Output format, `table` or `json`
+    #[structopt(short, long, default_value = "table", global = true)]
+    output: Format,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Manages the users
+    Users(UsersCommand),
+    /// Applies or reverts the database migrations
+    Migrate(MigrateCommand),
+    /// Bulk loads synthetic customers, products, users and orders, for load testing
+    Generate(GenerateCommand),
+    /// Runs authenticated scenarios against a running server and reports latencies and error rates
+    LoadTest(LoadTestCommand),
+    /// Replaces the key signing the tokens, the replaced key still verifying the tokens it signed
+    RotateJwtKey {
+        /// Number of replaced keys still verifying tokens, 0 revoking every token at once
+        #[structopt(long, default_value = "1")]
+        keep: usize,
+        /// Also writes the keys to a TOML configuration file outside of the repository, readable by
+        /// its owner only
+        #[structopt(long, parse(from_os_str))]
+        write: Option<PathBuf>,
+    },
+    /// Prints the effective configuration, secrets redacted
+    Config,
+}
+
+#[actix_web::main]
+async fn main() -> Result<()> {
+    // Install Color Eyre
+    // ------------------
+    color_eyre::install()?;
+
+    // Load configuration
+    // ------------------
+    let cli = Cli::from_args();
+    let settings = Config::build(&cli.config)?;
+
+    // Commands not using the database
+    // ---------------------------------------
+    match cli.command {
+        Command::Config => return config::print_config(&settings, cli.output),
+        Command::RotateJwtKey { keep, write } => {
+            return config::rotate_jwt_key(&settings, keep, write.as_deref(), cli.output)
+        }
+        Command::LoadTest(command) => return loadtest::run(command, &settings, cli.output).await,
+        _ => {}
+    }
+
+    // Initialization Postgres Pool
+    // ----------------------------
+    let db_pool = configure_db_pool(&settings.database).await?;
+
+    match cli.command {
+        Command::Users(command) => {
+            let repo = UserRepositoryImpl {
+                pool: Arc::new(db_pool),
+            };
+            let audit = AuditContext {
+                user_agent: Some(format!("northwind-admin/{}", env!("CARGO_PKG_VERSION"))),
+                ..AuditContext::default()
+            };
+            users::run(command, &repo, &audit, cli.output).await
+        }
+        Command::Migrate(command) => migrations::run(command, &db_pool, cli.output).await,
+        Command::Generate(command) => generate::run(command, &db_pool, cli.output).await,
+        Command::RotateJwtKey { .. } | Command::Config | Command::LoadTest(_) => unreachable!(),
+    }
+}
@@ -0,0 +1,111 @@
+//! Migrations commands module
+
+use color_eyre::Result;
+use eyre::eyre;
+use northwind_actix::db::MIGRATOR;
+use northwind_actix::export::{Cell, Tabular};
+use serde::Serialize;
+use sqlx::migrate::Migrate;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use structopt::StructOpt;
+
+use crate::output::{print, Format};
+
+#[derive(Debug, StructOpt)]
+pub enum MigrateCommand {
+    /// Lists the migrations and whether they are applied
+    List,
+    /// Applies the pending migrations
+    Run,
+    /// Reverts the latest applied migration, or every migration after a version
+    Revert {
+        /// Version kept applied, the migrations after it being reverted
+        #[structopt(long)]
+        target: Option<i64>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct MigrationStatus {
+    version: i64,
+    description: String,
+    applied: bool,
+}
+
+impl Tabular for MigrationStatus {
+    fn columns() -> &'static [&'static str] {
+        &["Version", "Description", "Applied"]
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            self.version.into(),
+            self.description.as_str().into(),
+            if self.applied { "yes" } else { "no" }.into(),
+        ]
+    }
+}
+
+/// Versions of the applied migrations, in ascending order
+async fn applied_versions(pool: &PgPool) -> Result<Vec<i64>> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let mut versions: Vec<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    versions.sort_unstable();
+
+    Ok(versions)
+}
+
+/// Version to revert to for the latest applied migration to be reverted, 0 reverting them all
+fn previous_version(applied: &[i64]) -> Option<i64> {
+    match applied {
+        [] => None,
+        [.., previous, _] => Some(*previous),
+        [_] => Some(0),
+    }
+}
+
+async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>> {
+    let applied: HashSet<i64> = applied_versions(pool).await?.into_iter().collect();
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.contains(&migration.version),
+        })
+        .collect())
+}
+
+pub async fn run(command: MigrateCommand, pool: &PgPool, format: Format) -> Result<()> {
+    match command {
+        MigrateCommand::List => {}
+        MigrateCommand::Run => MIGRATOR.run(pool).await?,
+        MigrateCommand::Revert { target } => {
+            let target = match target {
+                Some(target) => target,
+                None => {
+                    previous_version(&applied_versions(pool).await?).ok_or_else(|| eyre!("no migration applied"))?
+                }
+            };
+            MIGRATOR.undo(pool, target).await?;
+        }
+    }
+
+    print(format, &status(pool).await?)
+}
+
+#[test]
+fn test_previous_version() {
+    assert_eq!(None, previous_version(&[]));
+    assert_eq!(Some(0), previous_version(&[20210101]));
+    assert_eq!(Some(20210101), previous_version(&[20210101, 20210202]));
+}
@@ -0,0 +1,124 @@
+//! Output module
+//!
+//! Commands print rows, as an aligned table for operators or as a JSON array for scripts.
+
+use color_eyre::Result;
+use eyre::WrapErr;
+use northwind_actix::export::Tabular;
+use serde::Serialize;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Output format of the commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format `{}`, expected table or json", s)),
+        }
+    }
+}
+
+/// Prints rows in a format
+pub fn print<T: Tabular>(format: Format, rows: &[T]) -> Result<()> {
+    match format {
+        Format::Table => write_out(&table(rows)),
+        Format::Json => write(&rows),
+    }
+}
+
+/// Prints a value as JSON
+pub fn write<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    write_out(&(serde_json::to_string_pretty(value).context("serializing output")? + "\n"))
+}
+
+/// Writes to the standard output, not to panic as `print!` when it is closed, e.g. piped to `head`
+fn write_out(output: &str) -> Result<()> {
+    std::io::stdout().write_all(output.as_bytes()).context("writing output")
+}
+
+/// Renders rows as a table, each column as wide as its widest cell
+fn table<T: Tabular>(rows: &[T]) -> String {
+    let mut lines: Vec<Vec<String>> = vec![T::columns().iter().map(|column| column.to_string()).collect()];
+    lines.extend(
+        rows.iter()
+            .map(|row| row.cells().iter().map(|cell| cell.to_string()).collect()),
+    );
+
+    let mut widths = vec![0; T::columns().len()];
+    for line in &lines {
+        for (width, text) in widths.iter_mut().zip(line) {
+            *width = (*width).max(text.chars().count());
+        }
+    }
+    lines.insert(1, widths.iter().map(|width| "-".repeat(*width)).collect());
+
+    let mut output = String::new();
+    for line in &lines {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .map(|(text, width)| format!("{:width$}", text, width = width))
+            .collect();
+        output.push_str(cells.join("  ").trim_end());
+        output.push('\n');
+    }
+
+    output
+}
+
+#[test]
+fn test_table() {
+    use northwind_actix::export::Cell;
+
+    #[derive(Serialize)]
+    struct Row {
+        name: &'static str,
+        count: Option<i64>,
+    }
+
+    impl Tabular for Row {
+        fn columns() -> &'static [&'static str] {
+            &["Name", "Count"]
+        }
+
+        fn cells(&self) -> Vec<Cell> {
+            vec![self.name.into(), self.count.into()]
+        }
+    }
+
+    let rows = vec![
+        Row {
+            name: "customers",
+            count: Some(91),
+        },
+        Row {
+            name: "users",
+            count: None,
+        },
+    ];
+
+    assert_eq!(
+        "Name       Count\n\
+         ---------  -----\n\
+         customers  91\n\
+         users\n",
+        table(&rows)
+    );
+    assert_eq!("Name  Count\n----  -----\n", table::<Row>(&[]));
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(Ok(Format::Json), "json".parse());
+    assert!("yaml".parse::<Format>().is_err());
+}
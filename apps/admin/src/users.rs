@@ -0,0 +1,194 @@
+//! Users commands module
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use color_eyre::Result;
+use eyre::eyre;
+use northwind_actix::export::{Cell, Tabular};
+use northwind_core::audit::AuditContext;
+use northwind_core::errors::invalid_fields;
+use northwind_user::domain::user::{User, UserCreation, ROLE_ADMIN};
+use northwind_user::domain::user_repository::UserRepository;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Serialize;
+use structopt::StructOpt;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::output::{print, Format};
+
+/// Length of the generated passwords
+const PASSWORD_LENGTH: usize = 20;
+
+/// Shortest password accepted, as on registration
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+#[derive(Debug, StructOpt)]
+pub enum UsersCommand {
+    /// Creates an administrator, with a generated password unless one is given
+    CreateAdmin {
+        #[structopt(long)]
+        email: String,
+        #[structopt(long)]
+        firstname: String,
+        #[structopt(long)]
+        lastname: String,
+        #[structopt(long)]
+        password: Option<String>,
+    },
+    /// Replaces the password of a user, with a generated one unless one is given
+    ResetPassword {
+        email: String,
+        #[structopt(long)]
+        password: Option<String>,
+    },
+    /// Lists the deleted users, most recently deleted first
+    Deleted,
+    /// Restores a deleted user
+    Restore { id: Uuid },
+    /// Permanently erases the users deleted for more than a number of days
+    Purge {
+        #[structopt(long)]
+        days: u32,
+    },
+}
+
+/// Credentials of a created or updated user, the password only shown when generated
+#[derive(Debug, Serialize)]
+struct Credentials {
+    id: Uuid,
+    email: String,
+    role: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<String>,
+}
+
+impl Tabular for Credentials {
+    fn columns() -> &'static [&'static str] {
+        &["ID", "Email", "Role", "Generated password"]
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            self.id.into(),
+            self.email.as_str().into(),
+            self.role.as_str().into(),
+            self.password.clone().into(),
+        ]
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Purged {
+    purged: u64,
+    deleted_before: NaiveDateTime,
+}
+
+impl Tabular for Purged {
+    fn columns() -> &'static [&'static str] {
+        &["Purged users", "Deleted before"]
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![(self.purged as i64).into(), self.deleted_before.into()]
+    }
+}
+
+/// Random alphanumeric password
+fn generate_password() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(PASSWORD_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// Uses the given password, else a generated one which is returned to be shown
+fn password_or_generated(password: Option<String>) -> (String, Option<String>) {
+    match password {
+        Some(password) => (password, None),
+        None => {
+            let password = generate_password();
+            (password.clone(), Some(password))
+        }
+    }
+}
+
+pub async fn run(command: UsersCommand, repo: &dyn UserRepository, audit: &AuditContext, format: Format) -> Result<()> {
+    match command {
+        UsersCommand::CreateAdmin {
+            email,
+            firstname,
+            lastname,
+            password,
+        } => {
+            let (password, generated) = password_or_generated(password);
+            let creation = UserCreation {
+                lastname,
+                firstname,
+                email,
+                password,
+            };
+            creation
+                .validate()
+                .map_err(|e| eyre!("invalid user: {}", invalid_fields(&e)))?;
+
+            let mut user = User::new(creation);
+            user.role = ROLE_ADMIN.to_owned();
+            repo.create(&mut user, audit).await?;
+
+            print(
+                format,
+                &[Credentials {
+                    id: user.id,
+                    email: user.email,
+                    role: user.role,
+                    password: generated,
+                }],
+            )
+        }
+        UsersCommand::ResetPassword { email, password } => {
+            let (password, generated) = password_or_generated(password);
+            if password.chars().count() < MIN_PASSWORD_LENGTH {
+                return Err(eyre!(
+                    "invalid password: must have at least {} characters",
+                    MIN_PASSWORD_LENGTH
+                ));
+            }
+
+            let user = repo
+                .get_by_email(&email)
+                .await?
+                .ok_or_else(|| eyre!("no user found with email {}", email))?;
+            repo.reset_password(user.id, password, audit).await?;
+
+            print(
+                format,
+                &[Credentials {
+                    id: user.id,
+                    email: user.email,
+                    role: user.role,
+                    password: generated,
+                }],
+            )
+        }
+        UsersCommand::Deleted => print(format, &repo.get_deleted().await?),
+        UsersCommand::Restore { id } => {
+            if repo.restore(id, audit).await? != Some(1) {
+                return Err(eyre!("no deleted user found with ID {}", id));
+            }
+            let user = repo
+                .get_by_id(id)
+                .await?
+                .ok_or_else(|| eyre!("user {} not found", id))?;
+
+            print(format, &[user])
+        }
+        UsersCommand::Purge { days } => {
+            let deleted_before = Utc::now().naive_utc() - Duration::days(days as i64);
+            let purged = repo.purge(deleted_before, audit).await?;
+
+            print(format, &[Purged { purged, deleted_before }])
+        }
+    }
+}
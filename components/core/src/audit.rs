@@ -0,0 +1,242 @@
+//! Audit log module
+//!
+//! Security relevant and data changing actions are appended to the `audit_events` table,
+//! in the transaction of the change they describe. Only the fields which changed are kept
+//! in `before` and `after`.
+
+use async_stream::try_stream;
+use async_trait::async_trait;
+use futures::stream::{BoxStream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{Executor, PgPool, Postgres};
+use std::sync::Arc;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::telemetry::query_span;
+
+/// Who performed an action, and from where
+#[derive(Debug, Clone, Default)]
+pub struct AuditContext {
+    /// Authenticated user, `None` for anonymous actions (registration, login...)
+    pub actor_id: Option<Uuid>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    /// W3C `traceparent` of the request, continued by the work done in the background for it
+    pub traceparent: Option<String>,
+}
+
+/// Action to record
+#[derive(Debug, Clone)]
+pub struct AuditRecord<'a> {
+    pub action: &'a str,
+    pub target_type: &'a str,
+    pub target_id: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl<'a> AuditRecord<'a> {
+    /// Builds a record keeping only the top-level fields which differ between two states
+    pub fn change(action: &'a str, target_type: &'a str, target_id: String, before: Value, after: Value) -> Self {
+        let (before, after) = diff(before, after);
+
+        Self {
+            action,
+            target_type,
+            target_id: Some(target_id),
+            before: Some(before),
+            after: Some(after),
+        }
+    }
+}
+
+/// Recorded action
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub occurred_at: chrono::NaiveDateTime,
+    pub actor_id: Option<Uuid>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+/// Audit events search criteria, every criterion being optional
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditFilter {
+    pub actor_id: Option<Uuid>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub request_id: Option<String>,
+    /// Events which occurred at or after this time
+    pub from: Option<chrono::NaiveDateTime>,
+    /// Events which occurred before this time
+    pub to: Option<chrono::NaiveDateTime>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Default and maximum number of events returned by a search
+pub const AUDIT_PAGE_SIZE: i64 = 50;
+pub const AUDIT_MAX_PAGE_SIZE: i64 = 500;
+
+/// Appends an event, `executor` being the transaction of the audited change if any
+pub async fn record<'e, E>(executor: E, context: &AuditContext, record: AuditRecord<'_>) -> Result<(), AppError>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query!(
+        r#"
+            INSERT INTO audit_events (actor_id, action, target_type, target_id, before, after, ip, user_agent, request_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+        context.actor_id,
+        record.action,
+        record.target_type,
+        record.target_id,
+        record.before,
+        record.after,
+        context.ip,
+        context.user_agent,
+        context.request_id,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Keeps the top-level fields of two JSON objects which have different values
+pub fn diff(before: Value, after: Value) -> (Value, Value) {
+    match (before, after) {
+        (Value::Object(before), Value::Object(mut after)) => {
+            let mut changed_before = Map::new();
+            let mut changed_after = Map::new();
+
+            for (key, value) in before {
+                match after.remove(&key) {
+                    Some(new_value) if new_value == value => {}
+                    Some(new_value) => {
+                        changed_before.insert(key.clone(), value);
+                        changed_after.insert(key, new_value);
+                    }
+                    None => {
+                        changed_before.insert(key, value);
+                    }
+                }
+            }
+            changed_after.extend(after);
+
+            (Value::Object(changed_before), Value::Object(changed_after))
+        }
+        (before, after) => (before, after),
+    }
+}
+
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    async fn search(&self, filter: &AuditFilter) -> Result<Vec<AuditEvent>, AppError>;
+    /// Streams every event matching the filter, ignoring its page
+    fn stream(&self, filter: AuditFilter) -> BoxStream<'static, Result<AuditEvent, AppError>>;
+}
+
+pub struct AuditRepositoryImpl {
+    pub pool: Arc<PgPool>,
+}
+
+#[async_trait]
+impl AuditRepository for AuditRepositoryImpl {
+    /// Returns the events matching the filter, most recent first
+    async fn search(&self, filter: &AuditFilter) -> Result<Vec<AuditEvent>, AppError> {
+        let limit = filter.limit.unwrap_or(AUDIT_PAGE_SIZE).clamp(1, AUDIT_MAX_PAGE_SIZE);
+        let offset = filter.offset.unwrap_or(0).max(0);
+
+        sqlx::query_as!(
+            AuditEvent,
+            r#"
+                SELECT *
+                FROM audit_events
+                WHERE ($1::uuid IS NULL OR actor_id = $1)
+                    AND ($2::varchar IS NULL OR action = $2)
+                    AND ($3::varchar IS NULL OR target_type = $3)
+                    AND ($4::varchar IS NULL OR target_id = $4)
+                    AND ($5::text IS NULL OR request_id = $5)
+                    AND ($6::timestamp IS NULL OR occurred_at >= $6)
+                    AND ($7::timestamp IS NULL OR occurred_at < $7)
+                ORDER BY occurred_at DESC, id DESC
+                LIMIT $8 OFFSET $9
+            "#,
+            filter.actor_id,
+            filter.action,
+            filter.target_type,
+            filter.target_id,
+            filter.request_id,
+            filter.from,
+            filter.to,
+            limit,
+            offset,
+        )
+        .fetch_all(self.pool.as_ref())
+        .await
+        .map_err(|e| -> AppError { e.into() })
+    }
+
+    /// Streams the events matching the filter, most recent first
+    fn stream(&self, filter: AuditFilter) -> BoxStream<'static, Result<AuditEvent, AppError>> {
+        let pool = self.pool.clone();
+
+        Box::pin(try_stream! {
+            let span = query_span("SELECT", "audit_events");
+            let mut events = sqlx::query_as!(
+                AuditEvent,
+                r#"
+                    SELECT *
+                    FROM audit_events
+                    WHERE ($1::uuid IS NULL OR actor_id = $1)
+                        AND ($2::varchar IS NULL OR action = $2)
+                        AND ($3::varchar IS NULL OR target_type = $3)
+                        AND ($4::varchar IS NULL OR target_id = $4)
+                        AND ($5::text IS NULL OR request_id = $5)
+                        AND ($6::timestamp IS NULL OR occurred_at >= $6)
+                        AND ($7::timestamp IS NULL OR occurred_at < $7)
+                    ORDER BY occurred_at DESC, id DESC
+                "#,
+                filter.actor_id,
+                filter.action,
+                filter.target_type,
+                filter.target_id,
+                filter.request_id,
+                filter.from,
+                filter.to,
+            )
+            .fetch(pool.as_ref());
+
+            while let Some(event) = events.try_next().instrument(span.clone()).await? {
+                yield event;
+            }
+        })
+    }
+}
+
+#[test]
+fn test_diff_keeps_changed_fields() {
+    use serde_json::json;
+
+    let (before, after) = diff(
+        json!({"id": 1, "firstname": "Thang", "lastname": "Chung", "nickname": "tc"}),
+        json!({"id": 1, "firstname": "Thang", "lastname": "Nguyen", "email": "t@example.com"}),
+    );
+
+    assert_eq!(json!({"lastname": "Chung", "nickname": "tc"}), before);
+    assert_eq!(json!({"lastname": "Nguyen", "email": "t@example.com"}), after);
+}
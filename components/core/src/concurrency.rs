@@ -0,0 +1,46 @@
+//! Optimistic concurrency module
+//!
+//! Versioned rows carry a `version` column incremented by every change. Clients send back the
+//! version they read (as an HTTP `If-Match` ETag), and a change is refused if the row has been
+//! modified since.
+
+use crate::errors::AppError;
+
+/// Versions a change may apply to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpectedVersion {
+    /// Any version, the resource only has to exist
+    Any,
+    /// One of these versions
+    OneOf(Vec<i32>),
+}
+
+impl ExpectedVersion {
+    pub fn matches(&self, version: i32) -> bool {
+        match self {
+            Self::Any => true,
+            Self::OneOf(versions) => versions.contains(&version),
+        }
+    }
+
+    /// Fails with `PreconditionFailed` if the current version is not expected
+    pub fn check(&self, version: i32) -> Result<(), AppError> {
+        if self.matches(version) {
+            Ok(())
+        } else {
+            Err(AppError::PreconditionFailed {
+                message: String::from("The resource has been modified"),
+            })
+        }
+    }
+}
+
+#[test]
+fn test_expected_version() {
+    assert!(ExpectedVersion::Any.check(3).is_ok());
+    assert!(ExpectedVersion::OneOf(vec![2, 3]).check(3).is_ok());
+    assert!(matches!(
+        ExpectedVersion::OneOf(vec![2]).check(3),
+        Err(AppError::PreconditionFailed { .. })
+    ));
+}
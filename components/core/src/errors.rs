@@ -1,11 +1,14 @@
 use derive_more::{Display, Error};
 use serde::Serialize;
+use validator::ValidationErrors;
 
 /// Represents the custom error message
 #[derive(Serialize)]
 pub struct AppErrorMessage {
     pub code: u16,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 /// Defines available errors
@@ -22,6 +25,27 @@ pub enum AppError {
 
     #[display(fmt = "Unauthorized")]
     Unauthorized,
+
+    #[display(fmt = "Forbidden")]
+    Forbidden,
+
+    #[display(fmt = "{}", message)]
+    Conflict { message: String },
+
+    #[display(fmt = "{}", message)]
+    UnprocessableEntity { message: String },
+
+    #[display(fmt = "{}", message)]
+    PreconditionFailed { message: String },
+
+    #[display(fmt = "{}", message)]
+    PreconditionRequired { message: String },
+
+    #[display(fmt = "{}", message)]
+    UnsupportedMediaType { message: String },
+
+    #[display(fmt = "Too Many Requests")]
+    TooManyRequests,
 }
 
 impl AppError {
@@ -31,16 +55,35 @@ impl AppError {
             Self::BadRequest { message: m } => m.to_owned(),
             Self::InternalError { message: m } => m.to_owned(),
             Self::Unauthorized => "Unauthorized".to_owned(),
+            Self::Forbidden => "Forbidden".to_owned(),
+            Self::Conflict { message: m } => m.to_owned(),
+            Self::UnprocessableEntity { message: m } => m.to_owned(),
+            Self::PreconditionFailed { message: m } => m.to_owned(),
+            Self::PreconditionRequired { message: m } => m.to_owned(),
+            Self::UnsupportedMediaType { message: m } => m.to_owned(),
+            Self::TooManyRequests => "Too Many Requests".to_owned(),
         }
     }
 }
 
 impl From<sqlx::Error> for AppError {
-    fn from(error: sqlx::Error) -> Self {
-        match error {
-            _ => Self::InternalError {
-                message: "Database Error".to_owned(),
-            },
+    fn from(_error: sqlx::Error) -> Self {
+        Self::InternalError {
+            message: "Database Error".to_owned(),
         }
     }
 }
+
+/// Lists the invalid fields of a model with their failed validations, e.g. `email (email), password (length)`
+pub fn invalid_fields(errors: &ValidationErrors) -> String {
+    let mut fields: Vec<String> = errors
+        .field_errors()
+        .iter()
+        .map(|(field, errors)| {
+            let codes: Vec<&str> = errors.iter().map(|error| error.code.as_ref()).collect();
+            format!("{} ({})", field, codes.join(", "))
+        })
+        .collect();
+    fields.sort();
+    fields.join(", ")
+}
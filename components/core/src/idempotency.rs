@@ -0,0 +1,221 @@
+//! Idempotency keys module
+//!
+//! A request sent with an idempotency key claims it with the fingerprint of the request, and
+//! stores its response once handled. Until the key expires, the same request is answered with
+//! the stored response instead of being handled again.
+//!
+//! A claim is a lease: a request which neither stores its response nor frees its key before
+//! the end of its lease is deemed lost, and a retry takes the key over.
+
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::Instrument;
+
+use crate::errors::AppError;
+use crate::telemetry::query_span;
+
+/// Response stored for an idempotency key
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredResponse {
+    pub status: u16,
+    /// Headers as `[name, value]` pairs
+    pub headers: Value,
+    pub body: Vec<u8>,
+}
+
+/// State of an idempotency key when a request claims it
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdempotencyState {
+    /// The key was free (expired, or its lease ended), the request has to be handled
+    Claimed,
+    /// A request with the same key is still being handled, within its lease
+    InProgress,
+    /// The request has already been handled
+    Completed(StoredResponse),
+    /// The key has been used by a different request
+    Mismatch,
+}
+
+#[async_trait]
+pub trait IdempotencyRepository: Send + Sync {
+    /// Claims a key for a request until `locked_until`, or returns the state of the request which claimed it
+    async fn claim(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        locked_until: NaiveDateTime,
+        expires_at: NaiveDateTime,
+    ) -> Result<IdempotencyState, AppError>;
+    /// Stores the response of a request which claimed a key
+    async fn complete(&self, key: &str, response: &StoredResponse) -> Result<(), AppError>;
+    /// Frees a key whose request could not be handled, so that it can be retried
+    async fn release(&self, key: &str) -> Result<(), AppError>;
+    /// Deletes the expired keys, returns the number of deleted keys
+    async fn purge_expired(&self) -> Result<u64, AppError>;
+}
+
+pub struct IdempotencyRepositoryImpl {
+    pub pool: Arc<PgPool>,
+}
+
+#[async_trait]
+impl IdempotencyRepository for IdempotencyRepositoryImpl {
+    async fn claim(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        locked_until: NaiveDateTime,
+        expires_at: NaiveDateTime,
+    ) -> Result<IdempotencyState, AppError> {
+        let now = Utc::now().naive_utc();
+
+        // An expired key, or one whose request did not complete within its lease (its server
+        // crashed or its future was dropped), is claimed again as if it were free
+        let claimed = sqlx::query!(
+            r#"
+                INSERT INTO idempotency_keys (key, fingerprint, created_at, expires_at, locked_until)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (key) DO UPDATE
+                SET fingerprint = EXCLUDED.fingerprint,
+                    status = NULL,
+                    headers = NULL,
+                    body = NULL,
+                    created_at = EXCLUDED.created_at,
+                    expires_at = EXCLUDED.expires_at,
+                    locked_until = EXCLUDED.locked_until
+                WHERE idempotency_keys.expires_at <= EXCLUDED.created_at
+                    OR (idempotency_keys.status IS NULL AND idempotency_keys.locked_until <= EXCLUDED.created_at)
+                RETURNING key
+            "#,
+            key,
+            fingerprint,
+            now,
+            expires_at,
+            locked_until,
+        )
+        .fetch_optional(self.pool.as_ref())
+        .instrument(query_span("INSERT", "idempotency_keys"))
+        .await?;
+        if claimed.is_some() {
+            return Ok(IdempotencyState::Claimed);
+        }
+
+        let existing = sqlx::query!(
+            r#"SELECT fingerprint, status, headers, body FROM idempotency_keys WHERE key = $1"#,
+            key
+        )
+        .fetch_optional(self.pool.as_ref())
+        .instrument(query_span("SELECT", "idempotency_keys"))
+        .await?;
+
+        Ok(match existing {
+            // Released in the meantime
+            None => IdempotencyState::InProgress,
+            Some(existing) if existing.fingerprint != fingerprint => IdempotencyState::Mismatch,
+            Some(existing) => match existing.status {
+                None => IdempotencyState::InProgress,
+                Some(status) => IdempotencyState::Completed(StoredResponse {
+                    status: status as u16,
+                    headers: existing.headers.unwrap_or_else(|| Value::Array(Vec::new())),
+                    body: existing.body.unwrap_or_default(),
+                }),
+            },
+        })
+    }
+
+    async fn complete(&self, key: &str, response: &StoredResponse) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+                UPDATE idempotency_keys
+                SET status = $1, headers = $2, body = $3, locked_until = NULL
+                WHERE key = $4
+            "#,
+            response.status as i16,
+            response.headers,
+            response.body,
+            key,
+        )
+        .execute(self.pool.as_ref())
+        .instrument(query_span("UPDATE", "idempotency_keys"))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn release(&self, key: &str) -> Result<(), AppError> {
+        sqlx::query!(r#"DELETE FROM idempotency_keys WHERE key = $1 AND status IS NULL"#, key)
+            .execute(self.pool.as_ref())
+            .instrument(query_span("DELETE", "idempotency_keys"))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64, AppError> {
+        sqlx::query!(
+            r#"DELETE FROM idempotency_keys WHERE expires_at <= $1"#,
+            Utc::now().naive_utc()
+        )
+        .execute(self.pool.as_ref())
+        .instrument(query_span("DELETE", "idempotency_keys"))
+        .await
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.into())
+    }
+}
+
+#[test]
+#[ignore = "needs a migrated database at DATABASE_URL"]
+fn test_claim_stale_lock() {
+    use chrono::Duration;
+
+    futures::executor::block_on(async {
+        let pool = PgPool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
+        let repo = IdempotencyRepositoryImpl { pool: Arc::new(pool) };
+        let key = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now().naive_utc();
+        let expires_at = now + Duration::hours(1);
+        let fingerprint = "a".repeat(64);
+
+        assert_eq!(
+            IdempotencyState::Claimed,
+            repo.claim(&key, &fingerprint, now + Duration::minutes(5), expires_at).await.unwrap()
+        );
+        // A request still holding its lease is in progress
+        assert_eq!(
+            IdempotencyState::InProgress,
+            repo.claim(&key, &fingerprint, now + Duration::minutes(5), expires_at).await.unwrap()
+        );
+
+        // A request which never completed loses the key once its lease has ended
+        let stale = uuid::Uuid::new_v4().to_string();
+        repo.claim(&stale, &fingerprint, now - Duration::seconds(1), expires_at).await.unwrap();
+        assert_eq!(
+            IdempotencyState::Claimed,
+            repo.claim(&stale, &fingerprint, now + Duration::minutes(5), expires_at).await.unwrap()
+        );
+
+        // A completed request is replayed whatever its lease
+        let response = StoredResponse {
+            status: 201,
+            headers: Value::Array(Vec::new()),
+            body: b"{}".to_vec(),
+        };
+        repo.complete(&stale, &response).await.unwrap();
+        assert_eq!(
+            IdempotencyState::Completed(response),
+            repo.claim(&stale, &fingerprint, now + Duration::minutes(5), expires_at).await.unwrap()
+        );
+
+        for key in [&key, &stale].iter() {
+            sqlx::query("DELETE FROM idempotency_keys WHERE key = $1")
+                .bind(key)
+                .execute(repo.pool.as_ref())
+                .await
+                .unwrap();
+        }
+    });
+}
@@ -0,0 +1,191 @@
+//! Bulk import jobs module
+//!
+//! Files are validated as a whole, then their rows are inserted in batches, each batch in a
+//! transaction, by a job which records its progress so that clients can poll it. A failed
+//! batch stops the job, the previous batches staying imported.
+
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use derive_more::Display;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::telemetry::query_span;
+
+pub const IMPORT_PENDING: &str = "pending";
+pub const IMPORT_RUNNING: &str = "running";
+pub const IMPORT_COMPLETED: &str = "completed";
+pub const IMPORT_FAILED: &str = "failed";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportJob {
+    pub id: Uuid,
+    pub entity: String,
+    pub status: String,
+    pub total_rows: i32,
+    pub processed_rows: i32,
+    pub imported_rows: i32,
+    /// Row which failed the job, counted from 1 without the CSV header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_row: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+impl ImportJob {
+    pub fn new(entity: &str, total_rows: usize, created_by: Option<Uuid>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            entity: entity.to_owned(),
+            status: String::from(IMPORT_PENDING),
+            total_rows: total_rows as i32,
+            processed_rows: 0,
+            imported_rows: 0,
+            failed_row: None,
+            error: None,
+            created_by,
+            created_at: Utc::now().naive_utc(),
+            started_at: None,
+            finished_at: None,
+        }
+    }
+}
+
+/// Invalid row of an imported file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RowError {
+    /// Counted from 1 without the CSV header
+    pub row: usize,
+    pub message: String,
+}
+
+/// Failure of a batch insert, `index` being the position of the failed row in the batch
+#[derive(Debug, Display)]
+#[display(fmt = "{}", error)]
+pub struct BatchError {
+    pub index: usize,
+    pub error: AppError,
+}
+
+#[async_trait]
+pub trait ImportJobRepository: Send + Sync {
+    async fn create(&self, job: &ImportJob) -> Result<(), AppError>;
+    async fn get_by_id(&self, id: Uuid) -> Result<Option<ImportJob>, AppError>;
+    async fn start(&self, id: Uuid) -> Result<(), AppError>;
+    /// Adds the rows of a committed batch to the progress of a job
+    async fn progress(&self, id: Uuid, processed_rows: i32, imported_rows: i32) -> Result<(), AppError>;
+    async fn complete(&self, id: Uuid) -> Result<(), AppError>;
+    async fn fail(&self, id: Uuid, failed_row: Option<i32>, error: &str) -> Result<(), AppError>;
+}
+
+pub struct ImportJobRepositoryImpl {
+    pub pool: Arc<PgPool>,
+}
+
+#[async_trait]
+impl ImportJobRepository for ImportJobRepositoryImpl {
+    #[tracing::instrument(name = "ImportJobRepository::create", skip(self, job))]
+    async fn create(&self, job: &ImportJob) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+                INSERT INTO import_jobs (id, entity, status, total_rows, created_by, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            job.id,
+            job.entity,
+            job.status,
+            job.total_rows,
+            job.created_by,
+            job.created_at,
+        )
+        .execute(self.pool.as_ref())
+        .instrument(query_span("INSERT", "import_jobs"))
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "ImportJobRepository::get_by_id", skip(self))]
+    async fn get_by_id(&self, id: Uuid) -> Result<Option<ImportJob>, AppError> {
+        Ok(
+            sqlx::query_as!(ImportJob, r#"SELECT * FROM import_jobs WHERE id = $1"#, id)
+                .fetch_optional(self.pool.as_ref())
+                .instrument(query_span("SELECT", "import_jobs"))
+                .await?,
+        )
+    }
+
+    #[tracing::instrument(name = "ImportJobRepository::start", skip(self))]
+    async fn start(&self, id: Uuid) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"UPDATE import_jobs SET status = $1, started_at = $2 WHERE id = $3"#,
+            IMPORT_RUNNING,
+            Utc::now().naive_utc(),
+            id
+        )
+        .execute(self.pool.as_ref())
+        .instrument(query_span("UPDATE", "import_jobs"))
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "ImportJobRepository::progress", skip(self))]
+    async fn progress(&self, id: Uuid, processed_rows: i32, imported_rows: i32) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+                UPDATE import_jobs
+                SET processed_rows = processed_rows + $1, imported_rows = imported_rows + $2
+                WHERE id = $3
+            "#,
+            processed_rows,
+            imported_rows,
+            id
+        )
+        .execute(self.pool.as_ref())
+        .instrument(query_span("UPDATE", "import_jobs"))
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "ImportJobRepository::complete", skip(self))]
+    async fn complete(&self, id: Uuid) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"UPDATE import_jobs SET status = $1, finished_at = $2 WHERE id = $3"#,
+            IMPORT_COMPLETED,
+            Utc::now().naive_utc(),
+            id
+        )
+        .execute(self.pool.as_ref())
+        .instrument(query_span("UPDATE", "import_jobs"))
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "ImportJobRepository::fail", skip(self))]
+    async fn fail(&self, id: Uuid, failed_row: Option<i32>, error: &str) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"UPDATE import_jobs SET status = $1, failed_row = $2, error = $3, finished_at = $4 WHERE id = $5"#,
+            IMPORT_FAILED,
+            failed_row,
+            error,
+            Utc::now().naive_utc(),
+            id
+        )
+        .execute(self.pool.as_ref())
+        .instrument(query_span("UPDATE", "import_jobs"))
+        .await?;
+
+        Ok(())
+    }
+}
@@ -1 +1,9 @@
-pub mod errors;
\ No newline at end of file
+pub mod audit;
+pub mod concurrency;
+pub mod errors;
+pub mod idempotency;
+pub mod import;
+pub mod outbox;
+pub mod patch;
+pub mod rate_limit;
+pub mod telemetry;
@@ -0,0 +1,266 @@
+//! Transactional outbox module
+//!
+//! Domain events are appended to the `outbox` table in the transaction of the change they
+//! describe, so that an event exists if and only if its change is committed. A relay then
+//! publishes them to a sink, retrying with an exponential backoff until the sink accepts them:
+//! events are delivered at least once, and consumers deduplicate them by ID.
+
+use async_trait::async_trait;
+use chrono::{Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Executor, PgPool, Postgres};
+use std::sync::Arc;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::audit::AuditContext;
+use crate::errors::AppError;
+use crate::telemetry::query_span;
+
+/// Change of an aggregate which other systems may have to know about
+pub trait DomainEvent: Serialize {
+    /// Event type, e.g. `user.registered`
+    fn event_type(&self) -> &'static str;
+    /// Type of the changed aggregate, e.g. `user`
+    fn aggregate_type(&self) -> &'static str;
+    fn aggregate_id(&self) -> String;
+}
+
+/// Event stored in the outbox, as sent to the sinks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutboxEvent {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub aggregate_type: String,
+    pub aggregate_id: String,
+    pub payload: Value,
+    pub occurred_at: NaiveDateTime,
+    /// Delivery attempts, including the current one
+    #[serde(skip)]
+    pub attempts: i32,
+    /// W3C `traceparent` of the request which caused the event, sent as a header
+    #[serde(skip)]
+    pub traceparent: Option<String>,
+    /// ID of the request which caused the event, sent as a header
+    #[serde(skip)]
+    pub request_id: Option<String>,
+}
+
+/// Appends an event, `executor` being the transaction of the change it describes, along with the
+/// trace context and the ID of the request which caused it
+pub async fn enqueue<'e, E, D>(executor: E, event: &D, audit: &AuditContext) -> Result<Uuid, AppError>
+where
+    E: Executor<'e, Database = Postgres>,
+    D: DomainEvent,
+{
+    let id = Uuid::new_v4();
+    let payload = serde_json::to_value(event).map_err(|e| AppError::InternalError {
+        message: format!("Invalid domain event: {}", e),
+    })?;
+
+    sqlx::query!(
+        r#"
+            INSERT INTO outbox (id, event_type, aggregate_type, aggregate_id, payload, occurred_at, next_attempt_at,
+                traceparent, request_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $6, $7, $8)
+        "#,
+        id,
+        event.event_type(),
+        event.aggregate_type(),
+        event.aggregate_id(),
+        payload,
+        Utc::now().naive_utc(),
+        audit.traceparent,
+        audit.request_id,
+    )
+    .execute(executor)
+    .instrument(query_span("INSERT", "outbox"))
+    .await?;
+
+    Ok(id)
+}
+
+/// Destination of the published events
+///
+/// Sinks run on the actix system of the relay, so their futures do not have to be `Send`.
+#[async_trait(?Send)]
+pub trait EventSink: Send + Sync {
+    /// Delivers an event, an error meaning that it has to be delivered again
+    async fn publish(&self, event: &OutboxEvent) -> Result<(), AppError>;
+}
+
+/// Publishes the events to several sinks, an event being delivered again to all of them if
+/// one fails
+pub struct FanoutSink(pub Vec<Arc<dyn EventSink>>);
+
+#[async_trait(?Send)]
+impl EventSink for FanoutSink {
+    async fn publish(&self, event: &OutboxEvent) -> Result<(), AppError> {
+        for sink in &self.0 {
+            sink.publish(event).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+pub trait OutboxRepository: Send + Sync {
+    /// Claims up to `limit` events due for delivery, oldest first, until `lease_until`: an event
+    /// neither published nor failed by then, e.g. if the instance stopped, is claimed again
+    async fn claim(&self, limit: i64, lease_until: NaiveDateTime) -> Result<Vec<OutboxEvent>, AppError>;
+    async fn mark_published(&self, id: Uuid) -> Result<(), AppError>;
+    /// Schedules the next delivery attempt of an event
+    async fn mark_failed(&self, id: Uuid, error: &str, retry_at: NaiveDateTime) -> Result<(), AppError>;
+    /// Deletes the events published before a date, returns the number of deleted events
+    async fn purge_published(&self, published_before: NaiveDateTime) -> Result<u64, AppError>;
+}
+
+pub struct OutboxRepositoryImpl {
+    pub pool: Arc<PgPool>,
+}
+
+#[async_trait]
+impl OutboxRepository for OutboxRepositoryImpl {
+    async fn claim(&self, limit: i64, lease_until: NaiveDateTime) -> Result<Vec<OutboxEvent>, AppError> {
+        // Skipping locked rows lets several instances relay events at the same time
+        let mut events = sqlx::query_as!(
+            OutboxEvent,
+            r#"
+                UPDATE outbox
+                SET attempts = attempts + 1, next_attempt_at = $3
+                WHERE id IN (
+                    SELECT id FROM outbox
+                    WHERE published_at IS NULL AND next_attempt_at <= $1
+                    ORDER BY occurred_at
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, event_type, aggregate_type, aggregate_id, payload, occurred_at, attempts, traceparent,
+                    request_id
+            "#,
+            Utc::now().naive_utc(),
+            limit,
+            lease_until,
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("UPDATE", "outbox"))
+        .await?;
+        events.sort_by_key(|event| event.occurred_at);
+
+        Ok(events)
+    }
+
+    async fn mark_published(&self, id: Uuid) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"UPDATE outbox SET published_at = $1, last_error = NULL WHERE id = $2"#,
+            Utc::now().naive_utc(),
+            id
+        )
+        .execute(self.pool.as_ref())
+        .instrument(query_span("UPDATE", "outbox"))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn mark_failed(&self, id: Uuid, error: &str, retry_at: NaiveDateTime) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"UPDATE outbox SET next_attempt_at = $1, last_error = $2 WHERE id = $3"#,
+            retry_at,
+            error,
+            id
+        )
+        .execute(self.pool.as_ref())
+        .instrument(query_span("UPDATE", "outbox"))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn purge_published(&self, published_before: NaiveDateTime) -> Result<u64, AppError> {
+        sqlx::query!(r#"DELETE FROM outbox WHERE published_at < $1"#, published_before)
+            .execute(self.pool.as_ref())
+            .instrument(query_span("DELETE", "outbox"))
+            .await
+            .map(|r| r.rows_affected())
+            .map_err(|e| e.into())
+    }
+}
+
+/// Delay before the next attempt after `attempts` failed ones: `min`, then doubled at each
+/// attempt up to `max`
+pub fn backoff(attempts: i32, min: Duration, max: Duration) -> Duration {
+    let exponent = (attempts - 1).clamp(0, 30) as u32;
+    let delay = min.num_milliseconds().saturating_mul(2_i64.saturating_pow(exponent));
+
+    Duration::milliseconds(delay.min(max.num_milliseconds()))
+}
+
+/// Outcome of a relay run
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RelayReport {
+    pub published: usize,
+    pub failed: usize,
+}
+
+/// Publishes the events of the outbox to a sink
+pub struct OutboxRelay {
+    pub repo: Arc<dyn OutboxRepository>,
+    pub sink: Arc<dyn EventSink>,
+    /// Events claimed per run
+    pub batch_size: i64,
+    /// Time given to a run to deliver its events before they are claimed again
+    pub lease: Duration,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl OutboxRelay {
+    /// Delivers a batch of due events
+    pub async fn run(&self) -> Result<RelayReport, AppError> {
+        let events = self
+            .repo
+            .claim(self.batch_size, (Utc::now() + self.lease).naive_utc())
+            .await?;
+        let mut report = RelayReport::default();
+
+        for event in &events {
+            match self.sink.publish(event).await {
+                Ok(()) => {
+                    self.repo.mark_published(event.id).await?;
+                    report.published += 1;
+                }
+                Err(e) => {
+                    let retry_at = Utc::now() + backoff(event.attempts, self.min_backoff, self.max_backoff);
+                    tracing::warn!(
+                        event_id = %event.id,
+                        attempts = event.attempts,
+                        "Publishing event {} failed: {}",
+                        event.event_type,
+                        e
+                    );
+                    self.repo
+                        .mark_failed(event.id, &e.to_string(), retry_at.naive_utc())
+                        .await?;
+                    report.failed += 1;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+#[test]
+fn test_backoff() {
+    let (min, max) = (Duration::seconds(1), Duration::minutes(1));
+
+    assert_eq!(Duration::seconds(1), backoff(1, min, max));
+    assert_eq!(Duration::seconds(2), backoff(2, min, max));
+    assert_eq!(Duration::seconds(32), backoff(6, min, max));
+    assert_eq!(Duration::minutes(1), backoff(7, min, max));
+    assert_eq!(Duration::minutes(1), backoff(1000, min, max));
+}
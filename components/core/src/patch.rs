@@ -0,0 +1,132 @@
+//! JSON Merge Patch module
+//!
+//! Partial updates are applied as JSON Merge Patches (RFC 7386) to the serialized editable
+//! fields of a resource, the result being deserialized and validated as the full update model
+//! would be. A single update model per entity thus serves both `PUT` and `PATCH`.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use validator::Validate;
+
+use crate::errors::{invalid_fields, AppError};
+
+/// Media type of JSON Merge Patch documents
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+
+/// Merges a patch into a JSON document: `null` members are removed, objects are merged
+/// recursively and any other value replaces the target
+pub fn merge(target: &mut Value, patch: Value) {
+    match patch {
+        Value::Object(patch) => {
+            if !target.is_object() {
+                *target = Value::Object(Default::default());
+            }
+            if let Value::Object(target) = target {
+                for (key, value) in patch {
+                    if value.is_null() {
+                        target.remove(&key);
+                    } else {
+                        merge(target.entry(key).or_insert(Value::Null), value);
+                    }
+                }
+            }
+        }
+        patch => *target = patch,
+    }
+}
+
+/// Applies a patch to a model, returning the patched model once validated
+///
+/// Unlike a full update body, whose unknown members are ignored, a patch may only name fields
+/// of the model: a misspelled field would otherwise be silently dropped.
+pub fn apply<T>(model: &T, patch: Value) -> Result<T, AppError>
+where
+    T: Serialize + DeserializeOwned + Validate,
+{
+    let mut document = serde_json::to_value(model).map_err(|e| AppError::InternalError {
+        message: e.to_string(),
+    })?;
+    if let (Value::Object(document), Value::Object(patch)) = (&document, &patch) {
+        if let Some(field) = patch.keys().find(|field| !document.contains_key(*field)) {
+            return Err(AppError::BadRequest {
+                message: format!("Invalid patch: unknown field `{}`", field),
+            });
+        }
+    }
+    merge(&mut document, patch);
+
+    let patched: T = serde_json::from_value(document).map_err(|e| AppError::BadRequest {
+        message: format!("Invalid patch: {}", e),
+    })?;
+    patched.validate().map_err(|e| AppError::BadRequest {
+        message: format!("Invalid patch: invalid {}", invalid_fields(&e)),
+    })?;
+
+    Ok(patched)
+}
+
+#[test]
+fn test_merge_patch() {
+    use serde_json::json;
+
+    // Example of RFC 7386 section 3
+    let mut document = json!({
+        "title": "Goodbye!",
+        "author": {"givenName": "John", "familyName": "Doe"},
+        "tags": ["example", "sample"],
+        "content": "This will be unchanged"
+    });
+    merge(
+        &mut document,
+        json!({
+            "title": "Hello!",
+            "phoneNumber": "+01-123-456-7890",
+            "author": {"familyName": null},
+            "tags": ["example"]
+        }),
+    );
+
+    assert_eq!(
+        json!({
+            "title": "Hello!",
+            "author": {"givenName": "John"},
+            "tags": ["example"],
+            "content": "This will be unchanged",
+            "phoneNumber": "+01-123-456-7890"
+        }),
+        document
+    );
+}
+
+#[test]
+fn test_apply() {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Validate)]
+    struct Model {
+        #[validate(length(max = 5))]
+        name: String,
+        size: u8,
+    }
+    let model = Model {
+        name: String::from("small"),
+        size: 1,
+    };
+
+    assert_eq!(
+        Model {
+            name: String::from("big"),
+            size: 1
+        },
+        apply(&model, json!({"name": "big"})).unwrap()
+    );
+    let message = |patch| match apply(&model, patch) {
+        Err(AppError::BadRequest { message }) => message,
+        result => panic!("unexpected {:?}", result),
+    };
+    assert_eq!("Invalid patch: invalid name (length)", message(json!({"name": "larger"})));
+    assert_eq!("Invalid patch: unknown field `color`", message(json!({"color": "red"})));
+    assert!(message(json!({"size": null})).starts_with("Invalid patch: missing field `size`"));
+}
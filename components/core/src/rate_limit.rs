@@ -0,0 +1,189 @@
+//! Rate limiting module
+//!
+//! Requests are limited with the Generic Cell Rate Algorithm: a quota of `limit` requests per
+//! `period` lets a request through every `period / limit`, with bursts of up to `limit` requests.
+//! Each key only needs its theoretical arrival time (TAT), the time at which its quota will be
+//! fully available again, stored in memory or in Postgres to be shared by several instances.
+
+use async_trait::async_trait;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::Instrument;
+
+use crate::errors::AppError;
+use crate::telemetry::query_span;
+
+/// Number of requests allowed per period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub limit: u32,
+    /// Period, in milliseconds
+    pub period: i64,
+}
+
+impl Quota {
+    /// Time between two requests at the sustained rate, in milliseconds
+    fn emission_interval(&self) -> i64 {
+        (self.period / i64::from(self.limit.max(1))).max(1)
+    }
+}
+
+/// Outcome of a rate limited request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    /// Requests still allowed right now
+    pub remaining: u32,
+    /// Milliseconds until the quota is fully available again
+    pub reset: i64,
+    /// Milliseconds until a request is allowed again, if denied
+    pub retry_after: i64,
+}
+
+/// Applies a request to a key's theoretical arrival time, returning the decision and the new TAT
+/// to store if the request is allowed
+pub fn gcra(tat: Option<i64>, now: i64, quota: Quota) -> (RateLimitDecision, Option<i64>) {
+    let interval = quota.emission_interval();
+    let tolerance = interval * i64::from(quota.limit.max(1));
+    let tat = tat.unwrap_or(now).max(now);
+    let new_tat = tat + interval;
+
+    if new_tat - now > tolerance {
+        let decision = RateLimitDecision {
+            allowed: false,
+            limit: quota.limit,
+            remaining: 0,
+            reset: tat - now,
+            retry_after: new_tat - tolerance - now,
+        };
+        (decision, None)
+    } else {
+        let decision = RateLimitDecision {
+            allowed: true,
+            limit: quota.limit,
+            remaining: ((tolerance - (new_tat - now)) / interval) as u32,
+            reset: new_tat - now,
+            retry_after: 0,
+        };
+        (decision, Some(new_tat))
+    }
+}
+
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Counts a request of a key against a quota, `now` being in milliseconds since the epoch
+    async fn acquire(&self, key: &str, quota: Quota, now: i64) -> Result<RateLimitDecision, AppError>;
+    /// Forgets the keys whose quota is fully available, returns the number of forgotten keys
+    async fn purge(&self, now: i64) -> Result<u64, AppError>;
+}
+
+/// Store of a single instance
+#[derive(Default)]
+pub struct MemoryRateLimitStore {
+    tats: Mutex<HashMap<String, i64>>,
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn acquire(&self, key: &str, quota: Quota, now: i64) -> Result<RateLimitDecision, AppError> {
+        let mut tats = self.tats.lock().unwrap();
+        let (decision, new_tat) = gcra(tats.get(key).copied(), now, quota);
+        if let Some(new_tat) = new_tat {
+            tats.insert(key.to_owned(), new_tat);
+        }
+
+        Ok(decision)
+    }
+
+    async fn purge(&self, now: i64) -> Result<u64, AppError> {
+        let mut tats = self.tats.lock().unwrap();
+        let count = tats.len();
+        tats.retain(|_, tat| *tat > now);
+
+        Ok((count - tats.len()) as u64)
+    }
+}
+
+/// Store shared by the instances using the same database
+pub struct PgRateLimitStore {
+    pub pool: Arc<PgPool>,
+}
+
+#[async_trait]
+impl RateLimitStore for PgRateLimitStore {
+    async fn acquire(&self, key: &str, quota: Quota, now: i64) -> Result<RateLimitDecision, AppError> {
+        let interval = quota.emission_interval();
+        let tolerance = interval * i64::from(quota.limit.max(1));
+
+        // The TAT only moves if the request is allowed, in a single atomic statement
+        let updated = sqlx::query!(
+            r#"
+                INSERT INTO rate_limits (key, tat)
+                VALUES ($1, $2::bigint + $3::bigint)
+                ON CONFLICT (key) DO UPDATE
+                SET tat = GREATEST(rate_limits.tat, $2) + $3
+                WHERE GREATEST(rate_limits.tat, $2) + $3 - $2 <= $4
+                RETURNING tat
+            "#,
+            key,
+            now,
+            interval,
+            tolerance,
+        )
+        .fetch_optional(self.pool.as_ref())
+        .instrument(query_span("INSERT", "rate_limits"))
+        .await?;
+
+        match updated {
+            Some(updated) => Ok(gcra(Some(updated.tat - interval), now, quota).0),
+            None => {
+                let current = sqlx::query!(r#"SELECT tat FROM rate_limits WHERE key = $1"#, key)
+                    .fetch_optional(self.pool.as_ref())
+                    .instrument(query_span("SELECT", "rate_limits"))
+                    .await?;
+                Ok(gcra(current.map(|current| current.tat), now, quota).0)
+            }
+        }
+    }
+
+    async fn purge(&self, now: i64) -> Result<u64, AppError> {
+        sqlx::query!(r#"DELETE FROM rate_limits WHERE tat <= $1"#, now)
+            .execute(self.pool.as_ref())
+            .instrument(query_span("DELETE", "rate_limits"))
+            .await
+            .map(|r| r.rows_affected())
+            .map_err(|e| e.into())
+    }
+}
+
+#[test]
+fn test_gcra() {
+    // 3 requests per 3 seconds
+    let quota = Quota { limit: 3, period: 3000 };
+
+    // A burst of the whole quota is allowed
+    let (first, tat) = gcra(None, 0, quota);
+    assert!(first.allowed);
+    assert_eq!((2, 1000), (first.remaining, first.reset));
+    let (second, tat) = gcra(tat, 0, quota);
+    assert_eq!(1, second.remaining);
+    let (third, tat) = gcra(tat, 0, quota);
+    assert!(third.allowed);
+    assert_eq!((0, 3000), (third.remaining, third.reset));
+
+    // Then requests are denied until a request is allowed again
+    let (denied, denied_tat) = gcra(tat, 400, quota);
+    assert!(!denied.allowed);
+    assert_eq!(None, denied_tat);
+    assert_eq!(600, denied.retry_after);
+
+    let (allowed, tat) = gcra(tat, 1000, quota);
+    assert!(allowed.allowed);
+    assert_eq!(0, allowed.remaining);
+
+    // The quota is fully available once the TAT is past
+    let (rested, _) = gcra(tat, 10_000, quota);
+    assert_eq!(2, rested.remaining);
+}
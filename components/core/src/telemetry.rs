@@ -0,0 +1,15 @@
+//! Telemetry helpers shared by the repositories
+
+use tracing::Span;
+
+/// Returns the span of a SQL query, named and annotated after the OpenTelemetry database conventions
+pub fn query_span(operation: &str, table: &str) -> Span {
+    tracing::info_span!(
+        "query",
+        otel.name = %format!("{} {}", operation, table),
+        otel.kind = "client",
+        db.system = "postgresql",
+        db.operation = operation,
+        db.sql.table = table,
+    )
+}
@@ -0,0 +1,50 @@
+//! Catalog models module
+
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Category {
+    pub category_id: i16,
+    pub category_name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Supplier {
+    pub supplier_id: i16,
+    pub company_name: String,
+    pub contact_name: Option<String>,
+    pub contact_title: Option<String>,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub region: Option<String>,
+    pub postal_code: Option<String>,
+    pub country: Option<String>,
+    pub phone: Option<String>,
+    pub fax: Option<String>,
+    pub homepage: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct Product {
+    pub product_id: i16,
+    #[validate(length(min = 1, max = 40))]
+    pub product_name: String,
+    pub supplier_id: Option<i16>,
+    pub category_id: Option<i16>,
+    #[validate(length(max = 20))]
+    pub quantity_per_unit: Option<String>,
+    #[validate(range(min = 0))]
+    pub unit_price: Option<f32>,
+    #[validate(range(min = 0))]
+    pub units_in_stock: Option<i16>,
+    #[validate(range(min = 0))]
+    pub units_on_order: Option<i16>,
+    #[validate(range(min = 0))]
+    pub reorder_level: Option<i16>,
+    /// 1 if the product is not sold anymore
+    #[serde(default)]
+    #[validate(range(min = 0, max = 1))]
+    pub discontinued: i32,
+}
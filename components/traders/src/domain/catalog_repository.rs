@@ -0,0 +1,20 @@
+use crate::domain::catalog::{Category, Product, Supplier};
+use crate::domain::Page;
+use async_trait::async_trait;
+use northwind_core::audit::AuditContext;
+use northwind_core::errors::AppError;
+use northwind_core::import::BatchError;
+
+#[async_trait]
+pub trait CatalogRepository: Send + Sync {
+    async fn get_categories(&self, page: Page) -> Result<Vec<Category>, AppError>;
+    async fn get_categories_by_ids(&self, ids: &[i16]) -> Result<Vec<Category>, AppError>;
+    async fn get_suppliers(&self, page: Page) -> Result<Vec<Supplier>, AppError>;
+    async fn get_suppliers_by_ids(&self, ids: &[i16]) -> Result<Vec<Supplier>, AppError>;
+    async fn get_products(&self, page: Page) -> Result<Vec<Product>, AppError>;
+    async fn get_products_by_ids(&self, ids: &[i16]) -> Result<Vec<Product>, AppError>;
+    /// Adds products in one transaction, none of them being added if one fails
+    async fn import_products(&self, products: &[Product], audit: &AuditContext) -> Result<u64, BatchError>;
+    async fn get_products_by_category_ids(&self, category_ids: &[i16]) -> Result<Vec<Product>, AppError>;
+    async fn get_products_by_supplier_ids(&self, supplier_ids: &[i16]) -> Result<Vec<Product>, AppError>;
+}
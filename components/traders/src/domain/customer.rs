@@ -0,0 +1,38 @@
+//! Customer models module
+
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct Customer {
+    /// Five letters, e.g. `ALFKI`
+    #[validate(length(min = 1, max = 5))]
+    pub customer_id: String,
+    #[validate(length(min = 1, max = 40))]
+    pub company_name: String,
+    #[validate(length(max = 30))]
+    pub contact_name: Option<String>,
+    #[validate(length(max = 30))]
+    pub contact_title: Option<String>,
+    #[validate(length(max = 60))]
+    pub address: Option<String>,
+    #[validate(length(max = 15))]
+    pub city: Option<String>,
+    #[validate(length(max = 15))]
+    pub region: Option<String>,
+    #[validate(length(max = 10))]
+    pub postal_code: Option<String>,
+    #[validate(length(max = 15))]
+    pub country: Option<String>,
+    #[validate(length(max = 24))]
+    pub phone: Option<String>,
+    #[validate(length(max = 24))]
+    pub fax: Option<String>,
+}
+
+/// Type of customer
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomerDemographic {
+    pub customer_type_id: String,
+    pub customer_desc: Option<String>,
+}
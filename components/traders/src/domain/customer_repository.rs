@@ -0,0 +1,20 @@
+use crate::domain::customer::{Customer, CustomerDemographic};
+use crate::domain::Page;
+use async_trait::async_trait;
+use northwind_core::audit::AuditContext;
+use northwind_core::errors::AppError;
+use northwind_core::import::BatchError;
+
+#[async_trait]
+pub trait CustomerRepository: Send + Sync {
+    async fn get_customers(&self, page: Page) -> Result<Vec<Customer>, AppError>;
+    async fn get_customers_by_ids(&self, ids: &[String]) -> Result<Vec<Customer>, AppError>;
+    /// Adds customers in one transaction, none of them being added if one fails
+    async fn import(&self, customers: &[Customer], audit: &AuditContext) -> Result<u64, BatchError>;
+    async fn get_demographics(&self, page: Page) -> Result<Vec<CustomerDemographic>, AppError>;
+    /// Returns the demographics of customers, with the ID of their customer
+    async fn get_demographics_by_customer_ids(
+        &self,
+        customer_ids: &[String],
+    ) -> Result<Vec<(String, CustomerDemographic)>, AppError>;
+}
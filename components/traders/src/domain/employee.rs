@@ -0,0 +1,48 @@
+//! Employee models module
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Employee {
+    pub employee_id: i16,
+    pub last_name: String,
+    pub first_name: String,
+    pub title: Option<String>,
+    pub title_of_courtesy: Option<String>,
+    pub birth_date: Option<NaiveDate>,
+    pub hire_date: Option<NaiveDate>,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub region: Option<String>,
+    pub postal_code: Option<String>,
+    pub country: Option<String>,
+    pub home_phone: Option<String>,
+    pub extension: Option<String>,
+    pub notes: Option<String>,
+    /// Manager of the employee
+    pub reports_to: Option<i16>,
+    pub photo_path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Region {
+    pub region_id: i16,
+    pub region_description: String,
+}
+
+/// Sales territory, in a region
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Territory {
+    pub territory_id: String,
+    pub territory_description: String,
+    pub region_id: i16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UsState {
+    pub state_id: i16,
+    pub state_name: Option<String>,
+    pub state_abbr: Option<String>,
+    pub state_region: Option<String>,
+}
@@ -0,0 +1,19 @@
+use crate::domain::employee::{Employee, Region, Territory, UsState};
+use crate::domain::Page;
+use async_trait::async_trait;
+use northwind_core::errors::AppError;
+
+#[async_trait]
+pub trait EmployeeRepository: Send + Sync {
+    async fn get_employees(&self, page: Page) -> Result<Vec<Employee>, AppError>;
+    async fn get_employees_by_ids(&self, ids: &[i16]) -> Result<Vec<Employee>, AppError>;
+    /// Returns the employees reporting to managers
+    async fn get_employees_by_manager_ids(&self, manager_ids: &[i16]) -> Result<Vec<Employee>, AppError>;
+    async fn get_territories(&self, page: Page) -> Result<Vec<Territory>, AppError>;
+    async fn get_territories_by_region_ids(&self, region_ids: &[i16]) -> Result<Vec<Territory>, AppError>;
+    /// Returns the territories of employees, with the ID of their employee
+    async fn get_territories_by_employee_ids(&self, employee_ids: &[i16]) -> Result<Vec<(i16, Territory)>, AppError>;
+    async fn get_regions(&self, page: Page) -> Result<Vec<Region>, AppError>;
+    async fn get_regions_by_ids(&self, ids: &[i16]) -> Result<Vec<Region>, AppError>;
+    async fn get_us_states(&self, page: Page) -> Result<Vec<UsState>, AppError>;
+}
@@ -0,0 +1,46 @@
+//! Models list module
+
+pub mod catalog;
+pub mod catalog_repository;
+pub mod customer;
+pub mod customer_repository;
+pub mod employee;
+pub mod employee_repository;
+pub mod order;
+pub mod order_repository;
+pub mod report;
+pub mod report_repository;
+
+/// Default and maximum number of rows of a page
+pub const PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 500;
+
+/// Page of a list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Page {
+    /// Builds a page from optional client values, bounded to `MAX_PAGE_SIZE` rows
+    pub fn new(limit: Option<i64>, offset: Option<i64>) -> Self {
+        Self {
+            limit: limit.unwrap_or(PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+            offset: offset.unwrap_or(0).max(0),
+        }
+    }
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+#[test]
+fn test_page_is_bounded() {
+    assert_eq!(Page { limit: 50, offset: 0 }, Page::default());
+    assert_eq!(Page { limit: 500, offset: 100 }, Page::new(Some(10_000), Some(100)));
+    assert_eq!(Page { limit: 1, offset: 0 }, Page::new(Some(0), Some(-5)));
+}
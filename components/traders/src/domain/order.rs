@@ -0,0 +1,40 @@
+//! Order models module
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Order {
+    pub order_id: i16,
+    pub customer_id: Option<String>,
+    pub employee_id: Option<i16>,
+    pub order_date: Option<NaiveDate>,
+    pub required_date: Option<NaiveDate>,
+    pub shipped_date: Option<NaiveDate>,
+    /// Shipper of the order
+    pub ship_via: Option<i16>,
+    pub freight: Option<f32>,
+    pub ship_name: Option<String>,
+    pub ship_address: Option<String>,
+    pub ship_city: Option<String>,
+    pub ship_region: Option<String>,
+    pub ship_postal_code: Option<String>,
+    pub ship_country: Option<String>,
+}
+
+/// Product line of an order
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OrderDetail {
+    pub order_id: i16,
+    pub product_id: i16,
+    pub unit_price: f32,
+    pub quantity: i16,
+    pub discount: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Shipper {
+    pub shipper_id: i16,
+    pub company_name: String,
+    pub phone: Option<String>,
+}
@@ -0,0 +1,17 @@
+use crate::domain::order::{Order, OrderDetail, Shipper};
+use crate::domain::Page;
+use async_trait::async_trait;
+use northwind_core::errors::AppError;
+
+#[async_trait]
+pub trait OrderRepository: Send + Sync {
+    async fn get_orders(&self, page: Page) -> Result<Vec<Order>, AppError>;
+    async fn get_orders_by_ids(&self, ids: &[i16]) -> Result<Vec<Order>, AppError>;
+    async fn get_orders_by_customer_ids(&self, customer_ids: &[String]) -> Result<Vec<Order>, AppError>;
+    async fn get_orders_by_employee_ids(&self, employee_ids: &[i16]) -> Result<Vec<Order>, AppError>;
+    async fn get_orders_by_shipper_ids(&self, shipper_ids: &[i16]) -> Result<Vec<Order>, AppError>;
+    async fn get_details_by_order_ids(&self, order_ids: &[i16]) -> Result<Vec<OrderDetail>, AppError>;
+    async fn get_details_by_product_ids(&self, product_ids: &[i16]) -> Result<Vec<OrderDetail>, AppError>;
+    async fn get_shippers(&self, page: Page) -> Result<Vec<Shipper>, AppError>;
+    async fn get_shippers_by_ids(&self, ids: &[i16]) -> Result<Vec<Shipper>, AppError>;
+}
@@ -0,0 +1,34 @@
+//! Report models module
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// Order with its customer, employee and totals
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OrderSummary {
+    pub order_id: i16,
+    pub order_date: Option<NaiveDate>,
+    pub required_date: Option<NaiveDate>,
+    pub shipped_date: Option<NaiveDate>,
+    pub customer_id: Option<String>,
+    pub company_name: Option<String>,
+    pub employee_name: Option<String>,
+    pub ship_country: Option<String>,
+    /// Number of product lines
+    pub lines: i64,
+    pub quantity: i64,
+    /// Total of the lines, discounts deducted
+    pub subtotal: f64,
+    pub freight: f64,
+    pub total: f64,
+}
+
+/// Report criteria, every criterion being optional
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ReportFilter {
+    /// Orders placed on or after this date
+    pub from: Option<NaiveDate>,
+    /// Orders placed before this date
+    pub to: Option<NaiveDate>,
+    pub customer_id: Option<String>,
+}
@@ -0,0 +1,8 @@
+use crate::domain::report::{OrderSummary, ReportFilter};
+use futures::stream::BoxStream;
+use northwind_core::errors::AppError;
+
+/// Reports are streamed, since they may cover every order
+pub trait ReportRepository: Send + Sync {
+    fn order_summaries(&self, filter: ReportFilter) -> BoxStream<'static, Result<OrderSummary, AppError>>;
+}
@@ -0,0 +1,4 @@
+//! Northwind Traders business data: catalog, customers, orders and employees
+
+pub mod domain;
+pub mod repositories;
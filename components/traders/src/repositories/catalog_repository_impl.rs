@@ -0,0 +1,227 @@
+use crate::domain::catalog::{Category, Product, Supplier};
+use crate::domain::catalog_repository::CatalogRepository;
+use crate::domain::Page;
+use async_trait::async_trait;
+use northwind_core::audit::{self, AuditContext, AuditRecord};
+use northwind_core::errors::AppError;
+use northwind_core::import::BatchError;
+use northwind_core::telemetry::query_span;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::Instrument;
+
+pub struct CatalogRepositoryImpl {
+    pub pool: Arc<PgPool>,
+}
+
+#[async_trait]
+impl CatalogRepository for CatalogRepositoryImpl {
+    #[tracing::instrument(name = "CatalogRepository::get_categories", skip(self))]
+    async fn get_categories(&self, page: Page) -> Result<Vec<Category>, AppError> {
+        Ok(sqlx::query_as!(
+            Category,
+            r#"
+                SELECT category_id, category_name, description
+                FROM categories
+                ORDER BY category_id
+                LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "categories"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "CatalogRepository::get_categories_by_ids", skip(self))]
+    async fn get_categories_by_ids(&self, ids: &[i16]) -> Result<Vec<Category>, AppError> {
+        Ok(sqlx::query_as!(
+            Category,
+            r#"
+                SELECT category_id, category_name, description
+                FROM categories
+                WHERE category_id = ANY($1)
+            "#,
+            ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "categories"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "CatalogRepository::get_suppliers", skip(self))]
+    async fn get_suppliers(&self, page: Page) -> Result<Vec<Supplier>, AppError> {
+        Ok(sqlx::query_as!(
+            Supplier,
+            r#"
+                SELECT supplier_id, company_name, contact_name, contact_title, address, city, region,
+                    postal_code, country, phone, fax, homepage
+                FROM suppliers
+                ORDER BY supplier_id
+                LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "suppliers"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "CatalogRepository::get_suppliers_by_ids", skip(self))]
+    async fn get_suppliers_by_ids(&self, ids: &[i16]) -> Result<Vec<Supplier>, AppError> {
+        Ok(sqlx::query_as!(
+            Supplier,
+            r#"
+                SELECT supplier_id, company_name, contact_name, contact_title, address, city, region,
+                    postal_code, country, phone, fax, homepage
+                FROM suppliers
+                WHERE supplier_id = ANY($1)
+            "#,
+            ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "suppliers"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "CatalogRepository::get_products", skip(self))]
+    async fn get_products(&self, page: Page) -> Result<Vec<Product>, AppError> {
+        Ok(sqlx::query_as!(
+            Product,
+            r#"
+                SELECT product_id, product_name, supplier_id, category_id, quantity_per_unit, unit_price,
+                    units_in_stock, units_on_order, reorder_level, discontinued
+                FROM products
+                ORDER BY product_id
+                LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "products"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "CatalogRepository::get_products_by_ids", skip(self))]
+    async fn get_products_by_ids(&self, ids: &[i16]) -> Result<Vec<Product>, AppError> {
+        Ok(sqlx::query_as!(
+            Product,
+            r#"
+                SELECT product_id, product_name, supplier_id, category_id, quantity_per_unit, unit_price,
+                    units_in_stock, units_on_order, reorder_level, discontinued
+                FROM products
+                WHERE product_id = ANY($1)
+            "#,
+            ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "products"))
+        .await?)
+    }
+
+    #[tracing::instrument(
+        name = "CatalogRepository::import_products",
+        skip(self, products, audit),
+        fields(products = products.len())
+    )]
+    async fn import_products(&self, products: &[Product], audit: &AuditContext) -> Result<u64, BatchError> {
+        let fail = |index, error| BatchError { index, error };
+        let mut tx = self.pool.begin().await.map_err(|e| fail(0, e.into()))?;
+        let mut affected_rows = 0;
+
+        for (index, product) in products.iter().enumerate() {
+            affected_rows += sqlx::query!(
+                r#"
+                    INSERT INTO products (product_id, product_name, supplier_id, category_id, quantity_per_unit,
+                        unit_price, units_in_stock, units_on_order, reorder_level, discontinued)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+                product.product_id,
+                product.product_name,
+                product.supplier_id,
+                product.category_id,
+                product.quantity_per_unit,
+                product.unit_price,
+                product.units_in_stock,
+                product.units_on_order,
+                product.reorder_level,
+                product.discontinued,
+            )
+            .execute(&mut tx)
+            .instrument(query_span("INSERT", "products"))
+            .await
+            .map(|r| r.rows_affected())
+            .map_err(|e| match &e {
+                sqlx::Error::Database(db) => match db.constraint() {
+                    Some("products_pkey") => AppError::Conflict {
+                        message: format!("Product {} already exists", product.product_id),
+                    },
+                    Some("products_category_id_fkey") => AppError::UnprocessableEntity {
+                        message: format!("Unknown category {}", product.category_id.unwrap_or_default()),
+                    },
+                    Some("products_supplier_id_fkey") => AppError::UnprocessableEntity {
+                        message: format!("Unknown supplier {}", product.supplier_id.unwrap_or_default()),
+                    },
+                    _ => e.into(),
+                },
+                _ => e.into(),
+            })
+            .map_err(|e| fail(index, e))?;
+
+            let record = AuditRecord {
+                action: "product.create",
+                target_type: "product",
+                target_id: Some(product.product_id.to_string()),
+                before: None,
+                after: Some(json!(product)),
+            };
+            audit::record(&mut tx, audit, record)
+                .instrument(query_span("INSERT", "audit_events"))
+                .await
+                .map_err(|e| fail(index, e))?;
+        }
+        tx.commit().await.map_err(|e| fail(0, e.into()))?;
+
+        Ok(affected_rows)
+    }
+
+    #[tracing::instrument(name = "CatalogRepository::get_products_by_category_ids", skip(self))]
+    async fn get_products_by_category_ids(&self, category_ids: &[i16]) -> Result<Vec<Product>, AppError> {
+        Ok(sqlx::query_as!(
+            Product,
+            r#"
+                SELECT product_id, product_name, supplier_id, category_id, quantity_per_unit, unit_price,
+                    units_in_stock, units_on_order, reorder_level, discontinued
+                FROM products
+                WHERE category_id = ANY($1)
+                ORDER BY product_id
+            "#,
+            category_ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "products"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "CatalogRepository::get_products_by_supplier_ids", skip(self))]
+    async fn get_products_by_supplier_ids(&self, supplier_ids: &[i16]) -> Result<Vec<Product>, AppError> {
+        Ok(sqlx::query_as!(
+            Product,
+            r#"
+                SELECT product_id, product_name, supplier_id, category_id, quantity_per_unit, unit_price,
+                    units_in_stock, units_on_order, reorder_level, discontinued
+                FROM products
+                WHERE supplier_id = ANY($1)
+                ORDER BY product_id
+            "#,
+            supplier_ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "products"))
+        .await?)
+    }
+}
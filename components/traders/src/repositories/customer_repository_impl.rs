@@ -0,0 +1,158 @@
+use crate::domain::customer::{Customer, CustomerDemographic};
+use crate::domain::customer_repository::CustomerRepository;
+use crate::domain::Page;
+use async_trait::async_trait;
+use northwind_core::audit::{self, AuditContext, AuditRecord};
+use northwind_core::errors::AppError;
+use northwind_core::import::BatchError;
+use northwind_core::telemetry::query_span;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::Instrument;
+
+pub struct CustomerRepositoryImpl {
+    pub pool: Arc<PgPool>,
+}
+
+#[async_trait]
+impl CustomerRepository for CustomerRepositoryImpl {
+    #[tracing::instrument(name = "CustomerRepository::get_customers", skip(self))]
+    async fn get_customers(&self, page: Page) -> Result<Vec<Customer>, AppError> {
+        Ok(sqlx::query_as!(
+            Customer,
+            r#"
+                SELECT customer_id, company_name, contact_name, contact_title, address, city, region,
+                    postal_code, country, phone, fax
+                FROM customers
+                ORDER BY customer_id
+                LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "customers"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "CustomerRepository::get_customers_by_ids", skip(self))]
+    async fn get_customers_by_ids(&self, ids: &[String]) -> Result<Vec<Customer>, AppError> {
+        Ok(sqlx::query_as!(
+            Customer,
+            r#"
+                SELECT customer_id, company_name, contact_name, contact_title, address, city, region,
+                    postal_code, country, phone, fax
+                FROM customers
+                WHERE customer_id = ANY($1::text[])
+            "#,
+            ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "customers"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "CustomerRepository::import", skip(self, customers, audit), fields(customers = customers.len()))]
+    async fn import(&self, customers: &[Customer], audit: &AuditContext) -> Result<u64, BatchError> {
+        let fail = |index, error| BatchError { index, error };
+        let mut tx = self.pool.begin().await.map_err(|e| fail(0, e.into()))?;
+        let mut affected_rows = 0;
+
+        for (index, customer) in customers.iter().enumerate() {
+            affected_rows += sqlx::query!(
+                r#"
+                    INSERT INTO customers (customer_id, company_name, contact_name, contact_title, address, city, region,
+                        postal_code, country, phone, fax)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                "#,
+                customer.customer_id,
+                customer.company_name,
+                customer.contact_name,
+                customer.contact_title,
+                customer.address,
+                customer.city,
+                customer.region,
+                customer.postal_code,
+                customer.country,
+                customer.phone,
+                customer.fax,
+            )
+            .execute(&mut tx)
+            .instrument(query_span("INSERT", "customers"))
+            .await
+            .map(|r| r.rows_affected())
+            .map_err(|e| match &e {
+                sqlx::Error::Database(db) if db.constraint() == Some("customers_pkey") => AppError::Conflict {
+                    message: format!("Customer {} already exists", customer.customer_id),
+                },
+                _ => e.into(),
+            })
+            .map_err(|e| fail(index, e))?;
+
+            let record = AuditRecord {
+                action: "customer.create",
+                target_type: "customer",
+                target_id: Some(customer.customer_id.clone()),
+                before: None,
+                after: Some(json!(customer)),
+            };
+            audit::record(&mut tx, audit, record)
+                .instrument(query_span("INSERT", "audit_events"))
+                .await
+                .map_err(|e| fail(index, e))?;
+        }
+        tx.commit().await.map_err(|e| fail(0, e.into()))?;
+
+        Ok(affected_rows)
+    }
+
+    #[tracing::instrument(name = "CustomerRepository::get_demographics", skip(self))]
+    async fn get_demographics(&self, page: Page) -> Result<Vec<CustomerDemographic>, AppError> {
+        Ok(sqlx::query_as!(
+            CustomerDemographic,
+            r#"
+                SELECT customer_type_id, customer_desc
+                FROM customer_demographics
+                ORDER BY customer_type_id
+                LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "customer_demographics"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "CustomerRepository::get_demographics_by_customer_ids", skip(self))]
+    async fn get_demographics_by_customer_ids(
+        &self,
+        customer_ids: &[String],
+    ) -> Result<Vec<(String, CustomerDemographic)>, AppError> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT ccd.customer_id, cd.customer_type_id, cd.customer_desc
+                FROM customer_customer_demo ccd
+                INNER JOIN customer_demographics cd ON cd.customer_type_id = ccd.customer_type_id
+                WHERE ccd.customer_id = ANY($1::text[])
+                ORDER BY cd.customer_type_id
+            "#,
+            customer_ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "customer_demographics"))
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let demographic = CustomerDemographic {
+                    customer_type_id: row.customer_type_id,
+                    customer_desc: row.customer_desc,
+                };
+                (row.customer_id, demographic)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,186 @@
+use crate::domain::employee::{Employee, Region, Territory, UsState};
+use crate::domain::employee_repository::EmployeeRepository;
+use crate::domain::Page;
+use async_trait::async_trait;
+use northwind_core::errors::AppError;
+use northwind_core::telemetry::query_span;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::Instrument;
+
+pub struct EmployeeRepositoryImpl {
+    pub pool: Arc<PgPool>,
+}
+
+#[async_trait]
+impl EmployeeRepository for EmployeeRepositoryImpl {
+    #[tracing::instrument(name = "EmployeeRepository::get_employees", skip(self))]
+    async fn get_employees(&self, page: Page) -> Result<Vec<Employee>, AppError> {
+        Ok(sqlx::query_as!(
+            Employee,
+            r#"
+                SELECT employee_id, last_name, first_name, title, title_of_courtesy, birth_date, hire_date,
+                    address, city, region, postal_code, country, home_phone, extension, notes, reports_to, photo_path
+                FROM employees
+                ORDER BY employee_id
+                LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "employees"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "EmployeeRepository::get_employees_by_ids", skip(self))]
+    async fn get_employees_by_ids(&self, ids: &[i16]) -> Result<Vec<Employee>, AppError> {
+        Ok(sqlx::query_as!(
+            Employee,
+            r#"
+                SELECT employee_id, last_name, first_name, title, title_of_courtesy, birth_date, hire_date,
+                    address, city, region, postal_code, country, home_phone, extension, notes, reports_to, photo_path
+                FROM employees
+                WHERE employee_id = ANY($1)
+            "#,
+            ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "employees"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "EmployeeRepository::get_employees_by_manager_ids", skip(self))]
+    async fn get_employees_by_manager_ids(&self, manager_ids: &[i16]) -> Result<Vec<Employee>, AppError> {
+        Ok(sqlx::query_as!(
+            Employee,
+            r#"
+                SELECT employee_id, last_name, first_name, title, title_of_courtesy, birth_date, hire_date,
+                    address, city, region, postal_code, country, home_phone, extension, notes, reports_to, photo_path
+                FROM employees
+                WHERE reports_to = ANY($1)
+                ORDER BY employee_id
+            "#,
+            manager_ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "employees"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "EmployeeRepository::get_territories", skip(self))]
+    async fn get_territories(&self, page: Page) -> Result<Vec<Territory>, AppError> {
+        Ok(sqlx::query_as!(
+            Territory,
+            r#"
+                SELECT territory_id, territory_description, region_id
+                FROM territories
+                ORDER BY territory_id
+                LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "territories"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "EmployeeRepository::get_territories_by_region_ids", skip(self))]
+    async fn get_territories_by_region_ids(&self, region_ids: &[i16]) -> Result<Vec<Territory>, AppError> {
+        Ok(sqlx::query_as!(
+            Territory,
+            r#"
+                SELECT territory_id, territory_description, region_id
+                FROM territories
+                WHERE region_id = ANY($1)
+                ORDER BY territory_id
+            "#,
+            region_ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "territories"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "EmployeeRepository::get_territories_by_employee_ids", skip(self))]
+    async fn get_territories_by_employee_ids(&self, employee_ids: &[i16]) -> Result<Vec<(i16, Territory)>, AppError> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT et.employee_id, t.territory_id, t.territory_description, t.region_id
+                FROM employee_territories et
+                INNER JOIN territories t ON t.territory_id = et.territory_id
+                WHERE et.employee_id = ANY($1)
+                ORDER BY t.territory_id
+            "#,
+            employee_ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "territories"))
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let territory = Territory {
+                    territory_id: row.territory_id,
+                    territory_description: row.territory_description,
+                    region_id: row.region_id,
+                };
+                (row.employee_id, territory)
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(name = "EmployeeRepository::get_regions", skip(self))]
+    async fn get_regions(&self, page: Page) -> Result<Vec<Region>, AppError> {
+        Ok(sqlx::query_as!(
+            Region,
+            r#"
+                SELECT region_id, region_description
+                FROM region
+                ORDER BY region_id
+                LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "region"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "EmployeeRepository::get_regions_by_ids", skip(self))]
+    async fn get_regions_by_ids(&self, ids: &[i16]) -> Result<Vec<Region>, AppError> {
+        Ok(sqlx::query_as!(
+            Region,
+            r#"
+                SELECT region_id, region_description
+                FROM region
+                WHERE region_id = ANY($1)
+            "#,
+            ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "region"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "EmployeeRepository::get_us_states", skip(self))]
+    async fn get_us_states(&self, page: Page) -> Result<Vec<UsState>, AppError> {
+        Ok(sqlx::query_as!(
+            UsState,
+            r#"
+                SELECT state_id, state_name, state_abbr, state_region
+                FROM us_states
+                ORDER BY state_id
+                LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "us_states"))
+        .await?)
+    }
+}
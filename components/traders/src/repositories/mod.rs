@@ -0,0 +1,5 @@
+pub mod catalog_repository_impl;
+pub mod customer_repository_impl;
+pub mod employee_repository_impl;
+pub mod order_repository_impl;
+pub mod report_repository_impl;
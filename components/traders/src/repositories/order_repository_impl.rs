@@ -0,0 +1,174 @@
+use crate::domain::order::{Order, OrderDetail, Shipper};
+use crate::domain::order_repository::OrderRepository;
+use crate::domain::Page;
+use async_trait::async_trait;
+use northwind_core::errors::AppError;
+use northwind_core::telemetry::query_span;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::Instrument;
+
+pub struct OrderRepositoryImpl {
+    pub pool: Arc<PgPool>,
+}
+
+#[async_trait]
+impl OrderRepository for OrderRepositoryImpl {
+    #[tracing::instrument(name = "OrderRepository::get_orders", skip(self))]
+    async fn get_orders(&self, page: Page) -> Result<Vec<Order>, AppError> {
+        Ok(sqlx::query_as!(
+            Order,
+            r#"
+                SELECT order_id, customer_id, employee_id, order_date, required_date, shipped_date, ship_via,
+                    freight, ship_name, ship_address, ship_city, ship_region, ship_postal_code, ship_country
+                FROM orders
+                ORDER BY order_id
+                LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "orders"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "OrderRepository::get_orders_by_ids", skip(self))]
+    async fn get_orders_by_ids(&self, ids: &[i16]) -> Result<Vec<Order>, AppError> {
+        Ok(sqlx::query_as!(
+            Order,
+            r#"
+                SELECT order_id, customer_id, employee_id, order_date, required_date, shipped_date, ship_via,
+                    freight, ship_name, ship_address, ship_city, ship_region, ship_postal_code, ship_country
+                FROM orders
+                WHERE order_id = ANY($1)
+            "#,
+            ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "orders"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "OrderRepository::get_orders_by_customer_ids", skip(self))]
+    async fn get_orders_by_customer_ids(&self, customer_ids: &[String]) -> Result<Vec<Order>, AppError> {
+        Ok(sqlx::query_as!(
+            Order,
+            r#"
+                SELECT order_id, customer_id, employee_id, order_date, required_date, shipped_date, ship_via,
+                    freight, ship_name, ship_address, ship_city, ship_region, ship_postal_code, ship_country
+                FROM orders
+                WHERE customer_id = ANY($1::text[])
+                ORDER BY order_id
+            "#,
+            customer_ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "orders"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "OrderRepository::get_orders_by_employee_ids", skip(self))]
+    async fn get_orders_by_employee_ids(&self, employee_ids: &[i16]) -> Result<Vec<Order>, AppError> {
+        Ok(sqlx::query_as!(
+            Order,
+            r#"
+                SELECT order_id, customer_id, employee_id, order_date, required_date, shipped_date, ship_via,
+                    freight, ship_name, ship_address, ship_city, ship_region, ship_postal_code, ship_country
+                FROM orders
+                WHERE employee_id = ANY($1)
+                ORDER BY order_id
+            "#,
+            employee_ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "orders"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "OrderRepository::get_orders_by_shipper_ids", skip(self))]
+    async fn get_orders_by_shipper_ids(&self, shipper_ids: &[i16]) -> Result<Vec<Order>, AppError> {
+        Ok(sqlx::query_as!(
+            Order,
+            r#"
+                SELECT order_id, customer_id, employee_id, order_date, required_date, shipped_date, ship_via,
+                    freight, ship_name, ship_address, ship_city, ship_region, ship_postal_code, ship_country
+                FROM orders
+                WHERE ship_via = ANY($1)
+                ORDER BY order_id
+            "#,
+            shipper_ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "orders"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "OrderRepository::get_details_by_order_ids", skip(self))]
+    async fn get_details_by_order_ids(&self, order_ids: &[i16]) -> Result<Vec<OrderDetail>, AppError> {
+        Ok(sqlx::query_as!(
+            OrderDetail,
+            r#"
+                SELECT order_id, product_id, unit_price, quantity, discount
+                FROM order_details
+                WHERE order_id = ANY($1)
+                ORDER BY order_id, product_id
+            "#,
+            order_ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "order_details"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "OrderRepository::get_details_by_product_ids", skip(self))]
+    async fn get_details_by_product_ids(&self, product_ids: &[i16]) -> Result<Vec<OrderDetail>, AppError> {
+        Ok(sqlx::query_as!(
+            OrderDetail,
+            r#"
+                SELECT order_id, product_id, unit_price, quantity, discount
+                FROM order_details
+                WHERE product_id = ANY($1)
+                ORDER BY order_id, product_id
+            "#,
+            product_ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "order_details"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "OrderRepository::get_shippers", skip(self))]
+    async fn get_shippers(&self, page: Page) -> Result<Vec<Shipper>, AppError> {
+        Ok(sqlx::query_as!(
+            Shipper,
+            r#"
+                SELECT shipper_id, company_name, phone
+                FROM shippers
+                ORDER BY shipper_id
+                LIMIT $1 OFFSET $2
+            "#,
+            page.limit,
+            page.offset
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "shippers"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "OrderRepository::get_shippers_by_ids", skip(self))]
+    async fn get_shippers_by_ids(&self, ids: &[i16]) -> Result<Vec<Shipper>, AppError> {
+        Ok(sqlx::query_as!(
+            Shipper,
+            r#"
+                SELECT shipper_id, company_name, phone
+                FROM shippers
+                WHERE shipper_id = ANY($1)
+            "#,
+            ids
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "shippers"))
+        .await?)
+    }
+}
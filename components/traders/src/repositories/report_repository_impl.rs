@@ -0,0 +1,57 @@
+use crate::domain::report::{OrderSummary, ReportFilter};
+use crate::domain::report_repository::ReportRepository;
+use async_stream::try_stream;
+use futures::stream::{BoxStream, TryStreamExt};
+use northwind_core::errors::AppError;
+use northwind_core::telemetry::query_span;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::Instrument;
+
+pub struct ReportRepositoryImpl {
+    pub pool: Arc<PgPool>,
+}
+
+impl ReportRepository for ReportRepositoryImpl {
+    /// Streams the orders matching the filter with their totals, by order ID
+    fn order_summaries(&self, filter: ReportFilter) -> BoxStream<'static, Result<OrderSummary, AppError>> {
+        let pool = self.pool.clone();
+
+        Box::pin(try_stream! {
+            let span = query_span("SELECT", "orders");
+            let mut orders = sqlx::query_as!(
+                OrderSummary,
+                r#"
+                    SELECT o.order_id, o.order_date, o.required_date, o.shipped_date, o.customer_id,
+                        c.company_name AS "company_name?",
+                        e.first_name || ' ' || e.last_name AS "employee_name?",
+                        o.ship_country,
+                        COUNT(d.product_id) AS "lines!",
+                        COALESCE(SUM(d.quantity), 0)::bigint AS "quantity!",
+                        ROUND(COALESCE(SUM(d.unit_price * d.quantity * (1 - d.discount)), 0)::numeric, 2)::float8
+                            AS "subtotal!",
+                        ROUND(COALESCE(o.freight, 0)::numeric, 2)::float8 AS "freight!",
+                        ROUND((COALESCE(SUM(d.unit_price * d.quantity * (1 - d.discount)), 0)
+                            + COALESCE(o.freight, 0))::numeric, 2)::float8 AS "total!"
+                    FROM orders o
+                        LEFT JOIN customers c ON c.customer_id = o.customer_id
+                        LEFT JOIN employees e ON e.employee_id = o.employee_id
+                        LEFT JOIN order_details d ON d.order_id = o.order_id
+                    WHERE ($1::date IS NULL OR o.order_date >= $1)
+                        AND ($2::date IS NULL OR o.order_date < $2)
+                        AND ($3::text IS NULL OR o.customer_id = $3)
+                    GROUP BY o.order_id, c.company_name, e.first_name, e.last_name
+                    ORDER BY o.order_id
+                "#,
+                filter.from,
+                filter.to,
+                filter.customer_id,
+            )
+            .fetch(pool.as_ref());
+
+            while let Some(order) = orders.try_next().instrument(span.clone()).await? {
+                yield order;
+            }
+        })
+    }
+}
@@ -1,6 +1,7 @@
 use uuid::Uuid;
 use async_trait::async_trait;
 use crate::domain::auth::Claims;
+use jsonwebtoken::errors::{Error, ErrorKind};
 
 #[async_trait]
 pub trait JwtProcessor: Send + Sync {
@@ -15,4 +16,26 @@ pub trait JwtProcessor: Send + Sync {
     ) -> Result<(String, i64), Box<dyn std::error::Error>>;
 
     fn parse(&self, token: String, secret_key: String) -> Result<Claims, Box<dyn std::error::Error>>;
+
+    /// Parses a token signed with any of the keys, trying the next key only when the
+    /// signature does not match
+    fn parse_any(&self, token: String, secret_keys: &[&str]) -> Result<Claims, Box<dyn std::error::Error>> {
+        let mut result = Err(Box::new(Error::from(ErrorKind::InvalidSignature)) as Box<dyn std::error::Error>);
+        for secret_key in secret_keys {
+            result = self.parse(token.clone(), (*secret_key).to_owned());
+            match &result {
+                Err(e) if is_invalid_signature(e.as_ref()) => continue,
+                _ => break,
+            }
+        }
+
+        result
+    }
+}
+
+fn is_invalid_signature(error: &(dyn std::error::Error + 'static)) -> bool {
+    matches!(
+        error.downcast_ref::<Error>().map(|e| e.kind()),
+        Some(ErrorKind::InvalidSignature)
+    )
 }
@@ -2,5 +2,6 @@
 
 pub mod auth;
 pub mod user;
+pub mod user_event;
 pub mod user_repository;
 pub mod jwt_processor;
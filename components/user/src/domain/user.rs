@@ -2,8 +2,15 @@
 
 use validator::{Validate};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
 use uuid::Uuid;
 
+/// Role of regular users
+pub const ROLE_USER: &str = "user";
+
+/// Role of administrators, allowed to read the audit log
+pub const ROLE_ADMIN: &str = "admin";
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct User {
     pub id: Uuid,
@@ -12,22 +19,29 @@ pub struct User {
     pub email: String,
     #[serde(skip_serializing)]
     pub password: String,
+    pub role: String,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
-    #[serde(skip_serializing)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub deleted_at: Option<chrono::NaiveDateTime>,
+    /// Incremented by every change, exposed as the ETag of the user
+    #[serde(skip_serializing)]
+    pub version: i32,
 }
 
 impl User {
+    #[allow(clippy::too_many_arguments)]
     pub fn init(
         id: Uuid,
         lastname: String,
         firstname: String,
         email: String,
         password: String,
+        role: String,
         created_at: chrono::NaiveDateTime,
         updated_at: chrono::NaiveDateTime,
         deleted_at: Option<chrono::NaiveDateTime>,
+        version: i32,
     ) -> Self {
         Self {
             id,
@@ -35,9 +49,11 @@ impl User {
             firstname,
             email,
             password,
+            role,
             created_at,
             updated_at,
             deleted_at,
+            version,
         }
     }
 
@@ -46,14 +62,20 @@ impl User {
             id: Uuid::new_v4(),
             lastname: user.lastname,
             firstname: user.firstname,
-            email: user.email,
+            email: normalize_email(&user.email),
             password: user.password,
+            role: String::from(ROLE_USER),
             created_at: chrono::Utc::now().naive_utc(),
             updated_at: chrono::Utc::now().naive_utc(),
             deleted_at: None,
+            version: 1,
         }
     }
 
+    pub fn is_admin(&self) -> bool {
+        self.role == ROLE_ADMIN
+    }
+
     pub fn _fullname(&self) -> String {
         let mut fullname = String::new();
 
@@ -67,6 +89,16 @@ impl User {
     }
 }
 
+/// Normalizes an email for storage and comparison, emails being unique regardless of case
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Hashes a password as stored in the `users` table
+pub fn hash_password(password: &str) -> String {
+    format!("{:x}", Sha512::digest(password.as_bytes()))
+}
+
 #[derive(Deserialize, Debug, Validate)]
 pub struct Login {
     #[validate(email)]
@@ -96,12 +128,24 @@ pub struct UserCreation {
     pub password: String,
 }
 
+/// Editable fields of a user, for `PUT` and `PATCH`
 #[derive(Serialize, Deserialize, Debug, Validate)]
 pub struct UpdateUserModel {
+    #[validate(length(max = 63))]
     pub lastname: String,
+    #[validate(length(max = 63))]
     pub firstname: String,
 }
 
+impl From<&User> for UpdateUserModel {
+    fn from(user: &User) -> Self {
+        Self {
+            lastname: user.lastname.clone(),
+            firstname: user.firstname.clone(),
+        }
+    }
+}
+
 #[test]
 fn test_fullname() {
     let mut user = User {
@@ -110,14 +154,16 @@ fn test_fullname() {
         firstname: String::from("Thang"),
         email: String::from(""),
         password: String::from(""),
+        role: String::from(ROLE_USER),
         created_at: chrono::Utc::now().naive_utc(),
         updated_at: chrono::Utc::now().naive_utc(),
         deleted_at: None,
+        version: 1,
     };
     assert_eq!("Thang Chung", user._fullname());
 
     user.firstname = String::from("");
-    assert_eq!("Thang", user._fullname());
+    assert_eq!("Chung", user._fullname());
 
     user.firstname = String::from("Thang");
     user.lastname = String::from("");
@@ -127,3 +173,15 @@ fn test_fullname() {
     user.lastname = String::from("");
     assert_eq!("", user._fullname());
 }
+
+#[test]
+fn test_new_user_email_is_normalized() {
+    let user = User::new(UserCreation {
+        lastname: String::from("Chung"),
+        firstname: String::from("Thang"),
+        email: String::from(" Thang.Chung@Example.COM "),
+        password: String::from("00000000"),
+    });
+
+    assert_eq!("thang.chung@example.com", user.email);
+}
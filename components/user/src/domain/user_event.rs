@@ -0,0 +1,62 @@
+//! User domain events module
+
+use chrono::NaiveDateTime;
+use northwind_core::outbox::DomainEvent;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Domain events of the users, published through the outbox
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum UserEvent {
+    Registered {
+        id: Uuid,
+        email: String,
+        firstname: String,
+        lastname: String,
+        role: String,
+    },
+    Updated {
+        id: Uuid,
+        firstname: String,
+        lastname: String,
+        version: i32,
+    },
+    Deleted {
+        id: Uuid,
+        deleted_at: NaiveDateTime,
+    },
+    Restored {
+        id: Uuid,
+    },
+    /// The personal data of the user has to be erased
+    Purged {
+        id: Uuid,
+    },
+}
+
+impl DomainEvent for UserEvent {
+    fn event_type(&self) -> &'static str {
+        match self {
+            Self::Registered { .. } => "user.registered",
+            Self::Updated { .. } => "user.updated",
+            Self::Deleted { .. } => "user.deleted",
+            Self::Restored { .. } => "user.restored",
+            Self::Purged { .. } => "user.purged",
+        }
+    }
+
+    fn aggregate_type(&self) -> &'static str {
+        "user"
+    }
+
+    fn aggregate_id(&self) -> String {
+        match self {
+            Self::Registered { id, .. }
+            | Self::Updated { id, .. }
+            | Self::Deleted { id, .. }
+            | Self::Restored { id }
+            | Self::Purged { id } => id.to_string(),
+        }
+    }
+}
@@ -1,14 +1,37 @@
 use async_trait::async_trait;
 use crate::domain::user::User;
+use northwind_core::audit::AuditContext;
+use northwind_core::concurrency::ExpectedVersion;
 use northwind_core::errors::AppError;
+use northwind_core::import::BatchError;
+use chrono::NaiveDateTime;
+use futures::stream::BoxStream;
 use uuid::Uuid;
 
 #[async_trait]
 pub trait UserRepository: Send + Sync  {
-    async fn login(&self, email: String, password: String) -> Result<Option<User>, AppError>;
-    async fn create(&self, user: &mut User) -> Result<Option<u64>, AppError>;
+    async fn login(&self, email: String, password: String, audit: &AuditContext) -> Result<Option<User>, AppError>;
+    async fn create(&self, user: &mut User, audit: &AuditContext) -> Result<Option<u64>, AppError>;
+    async fn import(&self, users: &mut [User], audit: &AuditContext) -> Result<u64, BatchError>;
     async fn get_all(&self) -> Result<Vec<User>, AppError>;
+    /// Streams the users, e.g. to export them without loading them all
+    fn stream_all(&self) -> BoxStream<'static, Result<User, AppError>>;
     async fn get_by_id(&self, id: Uuid) -> Result<Option<User>, AppError>;
-    async fn delete(&self, id: Uuid) -> Result<Option<u64>, AppError>;
-    async fn update(&self, id: Uuid, firstname: String, lastname: String) -> Result<Option<u64>, AppError>;
-}
\ No newline at end of file
+    async fn get_by_email(&self, email: &str) -> Result<Option<User>, AppError>;
+    /// Returns the users not deleted having one of the normalized emails
+    async fn get_by_emails(&self, emails: &[String]) -> Result<Vec<User>, AppError>;
+    async fn delete(&self, id: Uuid, expected: &ExpectedVersion, audit: &AuditContext) -> Result<Option<u64>, AppError>;
+    async fn update(
+        &self,
+        id: Uuid,
+        firstname: String,
+        lastname: String,
+        expected: &ExpectedVersion,
+        audit: &AuditContext,
+    ) -> Result<Option<u64>, AppError>;
+    async fn get_deleted(&self) -> Result<Vec<User>, AppError>;
+    fn stream_deleted(&self) -> BoxStream<'static, Result<User, AppError>>;
+    async fn restore(&self, id: Uuid, audit: &AuditContext) -> Result<Option<u64>, AppError>;
+    async fn reset_password(&self, id: Uuid, password: String, audit: &AuditContext) -> Result<Option<u64>, AppError>;
+    async fn purge(&self, deleted_before: NaiveDateTime, audit: &AuditContext) -> Result<u64, AppError>;
+}
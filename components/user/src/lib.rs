@@ -7,5 +7,16 @@ pub mod domain;
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub jwt_secret_key: String,
+    /// Keys replaced by `jwt_secret_key`, still accepted to verify tokens
+    pub jwt_previous_keys: Vec<String>,
     pub jwt_lifetime: i64,
 }
+
+impl AppState {
+    /// Keys verifying the tokens, the signing key first
+    pub fn jwt_keys(&self) -> Vec<&str> {
+        std::iter::once(self.jwt_secret_key.as_str())
+            .chain(self.jwt_previous_keys.iter().map(String::as_str))
+            .collect()
+    }
+}
@@ -1,12 +1,84 @@
-use chrono::Utc;
-use sha2::{Digest, Sha512};
-use sqlx::{PgPool};
+use async_stream::try_stream;
+use chrono::{NaiveDateTime, Utc};
+use futures::stream::{BoxStream, TryStreamExt};
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 use async_trait::async_trait;
-use crate::domain::user::{User};
+use crate::domain::user::{hash_password, normalize_email, User};
+use crate::domain::user_event::UserEvent;
+use northwind_core::audit::{self, AuditContext, AuditRecord};
+use northwind_core::concurrency::ExpectedVersion;
 use northwind_core::errors::AppError;
+use northwind_core::import::BatchError;
+use northwind_core::outbox;
+use northwind_core::telemetry::query_span;
 use crate::domain::user_repository::UserRepository;
 use std::sync::Arc;
+use tracing::Instrument;
+
+/// Unique index of the emails of users not deleted
+const EMAIL_UNIQUE_INDEX: &str = "users_email_unique_idx";
+
+/// Fields of the users erased from the audit diffs and event payloads when they are purged
+const PERSONAL_DATA_KEYS: [&str; 3] = ["email", "firstname", "lastname"];
+
+/// Maps the violation of the unique email index to a `Conflict` error
+fn email_conflict(error: sqlx::Error) -> AppError {
+    match &error {
+        sqlx::Error::Database(e) if e.constraint() == Some(EMAIL_UNIQUE_INDEX) => AppError::Conflict {
+            message: String::from("A user with this email already exists"),
+        },
+        _ => error.into(),
+    }
+}
+
+/// Inserts a user with its audit record and event, hashing its password
+async fn insert(tx: &mut Transaction<'_, Postgres>, user: &mut User, audit: &AuditContext) -> Result<u64, AppError> {
+    user.password = hash_password(&user.password);
+
+    let affected_rows = sqlx::query!(
+        r#"
+            INSERT INTO users (id, lastname, firstname, email, password, role, created_at, updated_at, deleted_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+        user.id,
+        user.lastname,
+        user.firstname,
+        user.email,
+        user.password,
+        user.role,
+        user.created_at,
+        user.updated_at,
+        user.deleted_at,
+    )
+    .execute(&mut *tx)
+    .instrument(query_span("INSERT", "users"))
+    .await
+    .map(|r| r.rows_affected())
+    .map_err(email_conflict)?;
+
+    let record = AuditRecord {
+        action: "user.create",
+        target_type: "user",
+        target_id: Some(user.id.to_string()),
+        before: None,
+        after: Some(json!(user)),
+    };
+    audit::record(&mut *tx, audit, record)
+        .instrument(query_span("INSERT", "audit_events"))
+        .await?;
+    let event = UserEvent::Registered {
+        id: user.id,
+        email: user.email.clone(),
+        firstname: user.firstname.clone(),
+        lastname: user.lastname.clone(),
+        role: user.role.clone(),
+    };
+    outbox::enqueue(&mut *tx, &event, audit).await?;
+
+    Ok(affected_rows)
+}
 
 pub struct UserRepositoryImpl {
     pub pool: Arc<PgPool>,
@@ -23,76 +95,128 @@ impl UserRepositoryImpl {
 #[async_trait]
 impl UserRepository for UserRepositoryImpl {
     /// Returns a User if credentials are right
-    async fn login(&self, email: String, password: String) -> Result<Option<User>, AppError> {
-        let hashed_password = format!("{:x}", Sha512::digest(&password.as_bytes()));
+    #[tracing::instrument(name = "UserRepository::login", skip(self, email, password, audit))]
+    async fn login(&self, email: String, password: String, audit: &AuditContext) -> Result<Option<User>, AppError> {
+        let hashed_password = hash_password(&password);
         let result = sqlx::query!(
             r#"
                 SELECT * 
                 FROM users 
-                WHERE email = $1 AND
+                WHERE lower(email) = $1 AND
                     password = $2 AND
                     deleted_at IS NULL
             "#,
-            email,
+            normalize_email(&email),
             hashed_password
         )
         .fetch_optional(self.pool.as_ref())
+        .instrument(query_span("SELECT", "users"))
         .await
         .map_err(|e| -> AppError { e.into() })?;
 
-        match result {
-            Some(result) => Ok(Some(User::init(
+        let user = result.map(|result| {
+            User::init(
                 result.id,
-                result.password,
                 result.lastname,
                 result.firstname,
                 result.email,
+                result.password,
+                result.role,
                 result.created_at,
                 result.updated_at,
-                result.deleted_at.into(),
-            ))),
-            None => Ok(None),
+                result.deleted_at,
+                result.version,
+            )
+        });
+
+        match &user {
+            Some(user) => {
+                let context = AuditContext {
+                    actor_id: Some(user.id),
+                    ..audit.clone()
+                };
+                let record = AuditRecord {
+                    action: "user.login",
+                    target_type: "user",
+                    target_id: Some(user.id.to_string()),
+                    before: None,
+                    after: None,
+                };
+                audit::record(self.pool.as_ref(), &context, record)
+                    .instrument(query_span("INSERT", "audit_events"))
+                    .await?;
+            }
+            None => {
+                let record = AuditRecord {
+                    action: "user.login_failed",
+                    target_type: "user",
+                    target_id: None,
+                    before: None,
+                    after: Some(json!({ "email": email })),
+                };
+                audit::record(self.pool.as_ref(), audit, record)
+                    .instrument(query_span("INSERT", "audit_events"))
+                    .await?;
+            }
         }
+
+        Ok(user)
     }
 
     /// Add a new user
-    async fn create(&self, user: &mut User) -> Result<Option<u64>, AppError> {
-        user.password = format!("{:x}", Sha512::digest(&user.password.as_bytes()));
+    #[tracing::instrument(name = "UserRepository::create", skip(self, user, audit), fields(user_id = %user.id))]
+    async fn create(&self, user: &mut User, audit: &AuditContext) -> Result<Option<u64>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let affected_rows = insert(&mut tx, user, audit).await?;
+        tx.commit().await?;
 
-        let affected_rows = sqlx::query!(
-            r#"
-                INSERT INTO users (id, lastname, firstname, email, password, created_at, updated_at, deleted_at)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-            "#,
-            user.id,
-            user.lastname,
-            user.firstname,
-            user.email,
-            user.password,
-            user.created_at,
-            user.updated_at,
-            user.deleted_at,
-        )
-        .execute(self.pool.as_ref())
-        .await
-        .map(|r| r.rows_affected())
-        .map_err(|e| -> AppError { e.into() });
+        Ok(Some(affected_rows))
+    }
 
-        match affected_rows {
-            Ok(r) => Ok(Some(r)),
-            Err(e) => Err(AppError::from(e))
+    /// Adds users in one transaction, none of them being added if one fails
+    #[tracing::instrument(name = "UserRepository::import", skip(self, users, audit), fields(users = users.len()))]
+    async fn import(&self, users: &mut [User], audit: &AuditContext) -> Result<u64, BatchError> {
+        let fail = |index, error| BatchError { index, error };
+        let mut tx = self.pool.begin().await.map_err(|e| fail(0, e.into()))?;
+        let mut affected_rows = 0;
+        for (index, user) in users.iter_mut().enumerate() {
+            affected_rows += insert(&mut tx, user, audit).await.map_err(|e| fail(index, e))?;
         }
+        tx.commit().await.map_err(|e| fail(0, e.into()))?;
+
+        Ok(affected_rows)
     }
 
     /// Returns all users not deleted
+    #[tracing::instrument(name = "UserRepository::get_all", skip(self))]
     async fn get_all(&self) -> Result<Vec<User>, AppError> {
         sqlx::query_as!(User, r#"SELECT * FROM users WHERE deleted_at IS NULL"#)
             .fetch_all(self.pool.as_ref())
+            .instrument(query_span("SELECT", "users"))
             .await
             .map_err(|e| -> AppError { e.into() })
     }
 
+    /// Streams the users which are not deleted, oldest first
+    fn stream_all(&self) -> BoxStream<'static, Result<User, AppError>> {
+        let pool = self.pool.clone();
+
+        Box::pin(try_stream! {
+            let span = query_span("SELECT", "users");
+            let mut users = sqlx::query_as!(
+                User,
+                r#"SELECT * FROM users WHERE deleted_at IS NULL ORDER BY created_at, id"#
+            )
+            .fetch(pool.as_ref());
+
+            while let Some(user) = users.try_next().instrument(span.clone()).await? {
+                yield user;
+            }
+        })
+    }
+
     /// Returns a user by its ID
+    #[tracing::instrument(name = "UserRepository::get_by_id", skip(self))]
     async fn get_by_id(&self, id: Uuid) -> Result<Option<User>, AppError> {
         let result = sqlx::query!(
             r#"
@@ -104,6 +228,7 @@ impl UserRepository for UserRepositoryImpl {
             id
         )
         .fetch_optional(self.pool.as_ref())
+        .instrument(query_span("SELECT", "users"))
         .await?;
 
         match result {
@@ -113,42 +238,119 @@ impl UserRepository for UserRepositoryImpl {
                 result.firstname,
                 result.email,
                 result.password,
+                result.role,
                 result.created_at,
                 result.updated_at,
-                result.deleted_at.into(),
+                result.deleted_at,
+                result.version,
             ))),
             None => Ok(None),
         }
     }
 
-    /// Delete a user
-    async fn delete(&self, id: Uuid) -> Result<Option<u64>, AppError> {
+    /// Returns a user not deleted by its email
+    #[tracing::instrument(name = "UserRepository::get_by_email", skip(self))]
+    async fn get_by_email(&self, email: &str) -> Result<Option<User>, AppError> {
+        sqlx::query_as!(
+            User,
+            r#"SELECT * FROM users WHERE email = $1 AND deleted_at IS NULL"#,
+            normalize_email(email)
+        )
+        .fetch_optional(self.pool.as_ref())
+        .instrument(query_span("SELECT", "users"))
+        .await
+        .map_err(|e| -> AppError { e.into() })
+    }
+
+    /// Returns the users not deleted having one of the normalized emails
+    #[tracing::instrument(name = "UserRepository::get_by_emails", skip(self, emails), fields(emails = emails.len()))]
+    async fn get_by_emails(&self, emails: &[String]) -> Result<Vec<User>, AppError> {
+        sqlx::query_as!(
+            User,
+            r#"SELECT * FROM users WHERE email = ANY($1::text[]) AND deleted_at IS NULL"#,
+            emails
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "users"))
+        .await
+        .map_err(|e| -> AppError { e.into() })
+    }
+
+    /// Delete a user, if it is at an expected version
+    #[tracing::instrument(name = "UserRepository::delete", skip(self, expected, audit))]
+    async fn delete(&self, id: Uuid, expected: &ExpectedVersion, audit: &AuditContext) -> Result<Option<u64>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let before = sqlx::query!(
+            r#"SELECT version FROM users WHERE id = $1 AND deleted_at IS NULL FOR UPDATE"#,
+            id
+        )
+        .fetch_optional(&mut tx)
+        .instrument(query_span("SELECT", "users"))
+        .await?;
+        match before {
+            Some(before) => expected.check(before.version)?,
+            None => return Ok(Some(0)),
+        }
+
+        let deleted_at = Utc::now().naive_utc();
         let affected_rows = sqlx::query!(
             r#"
                 UPDATE users
-                SET deleted_at = $1
+                SET deleted_at = $1, version = version + 1
                 WHERE id = $2
             "#,
-            Utc::now().naive_utc(),
+            deleted_at,
             id
         )
-        .execute(self.pool.as_ref())
+        .execute(&mut tx)
+        .instrument(query_span("UPDATE", "users"))
         .await
-        .map(|r| r.rows_affected())
-        .map_err(|e| -> AppError { e.into() });
+        .map(|r| r.rows_affected())?;
 
-        match affected_rows {
-            Ok(r) => Ok(Some(r)),
-            Err(e) => Err(AppError::from(e))
-        }
+        let record = AuditRecord::change(
+            "user.delete",
+            "user",
+            id.to_string(),
+            json!({ "deleted_at": null }),
+            json!({ "deleted_at": deleted_at }),
+        );
+        audit::record(&mut tx, audit, record)
+            .instrument(query_span("INSERT", "audit_events"))
+            .await?;
+        outbox::enqueue(&mut tx, &UserEvent::Deleted { id, deleted_at }, audit).await?;
+        tx.commit().await?;
+
+        Ok(Some(affected_rows))
     }
 
-    /// Update a user
-    async fn update(&self, id: Uuid, firstname: String, lastname: String) -> Result<Option<u64>, AppError> {
+    /// Update a user, if it is at an expected version
+    #[tracing::instrument(name = "UserRepository::update", skip(self, firstname, lastname, expected, audit))]
+    async fn update(
+        &self,
+        id: Uuid,
+        firstname: String,
+        lastname: String,
+        expected: &ExpectedVersion,
+        audit: &AuditContext,
+    ) -> Result<Option<u64>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let before = sqlx::query!(
+            r#"SELECT lastname, firstname, version FROM users WHERE id = $1 AND deleted_at IS NULL FOR UPDATE"#,
+            id
+        )
+        .fetch_optional(&mut tx)
+        .instrument(query_span("SELECT", "users"))
+        .await?;
+        let before = match before {
+            Some(before) => before,
+            None => return Ok(Some(0)),
+        };
+        expected.check(before.version)?;
+
         let affected_rows = sqlx::query!(
             r#"
                 UPDATE users
-                SET lastname = $1, firstname = $2, updated_at = $3
+                SET lastname = $1, firstname = $2, updated_at = $3, version = version + 1
                 WHERE id = $4
             "#,
             lastname,
@@ -156,14 +358,317 @@ impl UserRepository for UserRepositoryImpl {
             Utc::now().naive_utc(),
             id
         )
-        .execute(self.pool.as_ref())
+        .execute(&mut tx)
+        .instrument(query_span("UPDATE", "users"))
+        .await
+        .map(|r| r.rows_affected())?;
+
+        let record = AuditRecord::change(
+            "user.update",
+            "user",
+            id.to_string(),
+            json!({ "lastname": before.lastname, "firstname": before.firstname }),
+            json!({ "lastname": lastname, "firstname": firstname }),
+        );
+        audit::record(&mut tx, audit, record)
+            .instrument(query_span("INSERT", "audit_events"))
+            .await?;
+        let event = UserEvent::Updated {
+            id,
+            firstname,
+            lastname,
+            version: before.version + 1,
+        };
+        outbox::enqueue(&mut tx, &event, audit).await?;
+        tx.commit().await?;
+
+        Ok(Some(affected_rows))
+    }
+
+    /// Returns deleted users, most recently deleted first
+    #[tracing::instrument(name = "UserRepository::get_deleted", skip(self))]
+    async fn get_deleted(&self) -> Result<Vec<User>, AppError> {
+        sqlx::query_as!(
+            User,
+            r#"SELECT * FROM users WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"#
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "users"))
+        .await
+        .map_err(|e| -> AppError { e.into() })
+    }
+
+    /// Streams the deleted users, most recently deleted first
+    fn stream_deleted(&self) -> BoxStream<'static, Result<User, AppError>> {
+        let pool = self.pool.clone();
+
+        Box::pin(try_stream! {
+            let span = query_span("SELECT", "users");
+            let mut users = sqlx::query_as!(
+                User,
+                r#"SELECT * FROM users WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"#
+            )
+            .fetch(pool.as_ref());
+
+            while let Some(user) = users.try_next().instrument(span.clone()).await? {
+                yield user;
+            }
+        })
+    }
+
+    /// Restore a deleted user
+    #[tracing::instrument(name = "UserRepository::restore", skip(self, audit))]
+    async fn restore(&self, id: Uuid, audit: &AuditContext) -> Result<Option<u64>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let before = sqlx::query!(
+            r#"SELECT deleted_at FROM users WHERE id = $1 AND deleted_at IS NOT NULL FOR UPDATE"#,
+            id
+        )
+        .fetch_optional(&mut tx)
+        .instrument(query_span("SELECT", "users"))
+        .await?;
+        let before = match before {
+            Some(before) => before,
+            None => return Ok(Some(0)),
+        };
+
+        let affected_rows = sqlx::query!(
+            r#"
+                UPDATE users
+                SET deleted_at = NULL, updated_at = $1, version = version + 1
+                WHERE id = $2
+            "#,
+            Utc::now().naive_utc(),
+            id
+        )
+        .execute(&mut tx)
+        .instrument(query_span("UPDATE", "users"))
         .await
         .map(|r| r.rows_affected())
-        .map_err(|e| -> AppError { e.into() });
+        .map_err(email_conflict)?;
+
+        let record = AuditRecord::change(
+            "user.restore",
+            "user",
+            id.to_string(),
+            json!({ "deleted_at": before.deleted_at }),
+            json!({ "deleted_at": null }),
+        );
+        audit::record(&mut tx, audit, record)
+            .instrument(query_span("INSERT", "audit_events"))
+            .await?;
+        outbox::enqueue(&mut tx, &UserEvent::Restored { id }, audit).await?;
+        tx.commit().await?;
+
+        Ok(Some(affected_rows))
+    }
 
-        match affected_rows {
-            Ok(r) => Ok(Some(r)),
-            Err(e) => Err(AppError::from(e))
+    /// Replace the password of a user not deleted
+    #[tracing::instrument(name = "UserRepository::reset_password", skip(self, password, audit))]
+    async fn reset_password(&self, id: Uuid, password: String, audit: &AuditContext) -> Result<Option<u64>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let affected_rows = sqlx::query!(
+            r#"
+                UPDATE users
+                SET password = $1, updated_at = $2, version = version + 1
+                WHERE id = $3 AND deleted_at IS NULL
+            "#,
+            hash_password(&password),
+            Utc::now().naive_utc(),
+            id
+        )
+        .execute(&mut tx)
+        .instrument(query_span("UPDATE", "users"))
+        .await
+        .map(|r| r.rows_affected())?;
+        if affected_rows == 0 {
+            return Ok(Some(0));
         }
+
+        // Passwords, even hashed, are not kept in the audit log
+        let record = AuditRecord {
+            action: "user.reset_password",
+            target_type: "user",
+            target_id: Some(id.to_string()),
+            before: None,
+            after: None,
+        };
+        audit::record(&mut tx, audit, record)
+            .instrument(query_span("INSERT", "audit_events"))
+            .await?;
+        tx.commit().await?;
+
+        Ok(Some(affected_rows))
     }
+
+    /// Permanently erase the users deleted before a date, returns the number of erased users
+    #[tracing::instrument(name = "UserRepository::purge", skip(self, audit))]
+    async fn purge(&self, deleted_before: NaiveDateTime, audit: &AuditContext) -> Result<u64, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let purged = sqlx::query!(
+            r#"
+                DELETE FROM users
+                WHERE deleted_at < $1
+                RETURNING id, email, deleted_at
+            "#,
+            deleted_before
+        )
+        .fetch_all(&mut tx)
+        .instrument(query_span("DELETE", "users"))
+        .await?;
+        if purged.is_empty() {
+            return Ok(0);
+        }
+
+        let ids: Vec<String> = purged.iter().map(|user| user.id.to_string()).collect();
+        let emails: Vec<String> = purged.iter().map(|user| normalize_email(&user.email)).collect();
+        let keys: Vec<String> = PERSONAL_DATA_KEYS.iter().map(|key| key.to_string()).collect();
+
+        // The diffs of the audit log are redacted through the only update allowed by its trigger
+        sqlx::query!(r#"SELECT set_config('northwind.audit_redaction', 'on', true)"#)
+            .fetch_one(&mut tx)
+            .await?;
+        sqlx::query!(
+            r#"
+                UPDATE audit_events
+                SET before = before - $3::text[], after = after - $3::text[]
+                WHERE (target_type = 'user' AND target_id = ANY($1::text[]))
+                    OR (action = 'user.login_failed' AND lower(after->>'email') = ANY($2::text[]))
+            "#,
+            &ids,
+            &emails,
+            &keys,
+        )
+        .execute(&mut tx)
+        .instrument(query_span("UPDATE", "audit_events"))
+        .await?;
+
+        // Published events are kept for a while and pending ones are still to be delivered
+        sqlx::query!(
+            r#"
+                UPDATE outbox
+                SET payload = payload - $2::text[]
+                WHERE aggregate_type = 'user' AND aggregate_id = ANY($1::text[])
+            "#,
+            &ids,
+            &keys,
+        )
+        .execute(&mut tx)
+        .instrument(query_span("UPDATE", "outbox"))
+        .await?;
+        sqlx::query!(
+            r#"
+                UPDATE webhook_deliveries
+                SET payload = payload - $2::text[]
+                WHERE event_type LIKE 'user.%' AND payload->>'id' = ANY($1::text[])
+            "#,
+            &ids,
+            &keys,
+        )
+        .execute(&mut tx)
+        .instrument(query_span("UPDATE", "webhook_deliveries"))
+        .await?;
+
+        // Personal data is not kept in the audit log of an erasure
+        for user in &purged {
+            let record = AuditRecord {
+                action: "user.purge",
+                target_type: "user",
+                target_id: Some(user.id.to_string()),
+                before: Some(json!({ "deleted_at": user.deleted_at })),
+                after: None,
+            };
+            audit::record(&mut tx, audit, record)
+                .instrument(query_span("INSERT", "audit_events"))
+                .await?;
+            outbox::enqueue(&mut tx, &UserEvent::Purged { id: user.id }, audit).await?;
+        }
+        tx.commit().await?;
+
+        Ok(purged.len() as u64)
+    }
+}
+
+#[test]
+#[ignore = "needs a migrated database at DATABASE_URL"]
+fn test_purge_erases_personal_data() {
+    use crate::domain::user::UserCreation;
+    use chrono::NaiveDate;
+    use northwind_core::concurrency::ExpectedVersion;
+
+    futures::executor::block_on(async {
+        let pool = PgPool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
+        let repository = UserRepositoryImpl { pool: Arc::new(pool.clone()) };
+        let audit = AuditContext::default();
+        let email = format!("purge.{}@example.com", Uuid::new_v4());
+
+        let mut user = User::new(UserCreation {
+            lastname: String::from("Purge"),
+            firstname: String::from("Test"),
+            email: email.clone(),
+            password: String::from("password"),
+        });
+        repository.create(&mut user, &audit).await.unwrap();
+        repository.login(email.to_uppercase(), String::from("wrong"), &audit).await.unwrap();
+        repository
+            .update(user.id, String::from("Test 2"), String::from("Purge"), &ExpectedVersion::Any, &audit)
+            .await
+            .unwrap();
+        repository.delete(user.id, &ExpectedVersion::Any, &audit).await.unwrap();
+
+        // Events delivered to a webhook, then deleted long enough ago to be the only user purged
+        let subscription = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO webhook_subscriptions (id, url, secret, created_at, updated_at) \
+             VALUES ($1, 'http://localhost/', 'secret', now(), now())",
+        )
+        .bind(subscription)
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO webhook_deliveries (id, subscription_id, event_id, event_type, payload, next_attempt_at, created_at) \
+             SELECT uuid_generate_v4(), $1, id, event_type, payload, now(), now() FROM outbox WHERE aggregate_id = $2",
+        )
+        .bind(subscription)
+        .bind(user.id.to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+        let deleted_at = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        sqlx::query("UPDATE users SET deleted_at = $1 WHERE id = $2")
+            .bind(deleted_at)
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(1, repository.purge(deleted_at + chrono::Duration::seconds(1), &audit).await.unwrap());
+
+        let pattern = format!("%{}%", email);
+        let remaining: i64 = sqlx::query_scalar(
+            "SELECT (SELECT COUNT(*) FROM users WHERE email ILIKE $1) \
+             + (SELECT COUNT(*) FROM audit_events WHERE before::text ILIKE $1 OR after::text ILIKE $1) \
+             + (SELECT COUNT(*) FROM outbox WHERE payload::text ILIKE $1) \
+             + (SELECT COUNT(*) FROM webhook_deliveries WHERE payload::text ILIKE $1)",
+        )
+        .bind(pattern)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(0, remaining);
+
+        // Anything but the redaction of the diffs is still rejected
+        let update = sqlx::query("UPDATE audit_events SET action = 'user.purge' WHERE target_id = $1")
+            .bind(user.id.to_string())
+            .execute(&pool)
+            .await;
+        assert!(update.is_err());
+
+        sqlx::query("DELETE FROM webhook_subscriptions WHERE id = $1")
+            .bind(subscription)
+            .execute(&pool)
+            .await
+            .unwrap();
+    });
 }
@@ -20,7 +20,7 @@ impl JwtProcessor for JwtProcessorImpl {
         jwt_lifetime: i64,
     ) -> Result<(String, i64), Box<dyn std::error::Error>> {
         let header = Header::new(Algorithm::HS512);
-        let now = Utc::now().timestamp_nanos() / 1_000_000_000; // nanosecond -> second
+        let now = Utc::now().timestamp();
         let expired_at = now + (jwt_lifetime * 3600);
 
         let payload = Claims {
@@ -46,3 +46,35 @@ impl JwtProcessor for JwtProcessorImpl {
         Ok(token.claims)
     }
 }
+
+#[test]
+fn test_parse_with_previous_keys() {
+    let processor = JwtProcessorImpl {};
+    let user_id = uuid::Uuid::new_v4();
+    let token = |secret_key: &str, jwt_lifetime| {
+        processor
+            .generate(
+                user_id,
+                String::from("Doe"),
+                String::from("John"),
+                String::from("john@example.com"),
+                secret_key.to_owned(),
+                jwt_lifetime,
+            )
+            .unwrap()
+            .0
+    };
+    let reason = |e: Box<dyn std::error::Error>| e.downcast::<jsonwebtoken::errors::Error>().unwrap().into_kind();
+
+    // Tokens signed with a replaced key stay valid
+    let claims = processor.parse_any(token("old", 1), &["new", "old"]).unwrap();
+    assert_eq!(user_id, claims.user_id);
+    assert!(matches!(
+        processor.parse_any(token("older", 1), &["new", "old"]).map_err(reason),
+        Err(jsonwebtoken::errors::ErrorKind::InvalidSignature)
+    ));
+    assert!(matches!(
+        processor.parse_any(token("old", -1), &["new", "old"]).map_err(reason),
+        Err(jsonwebtoken::errors::ErrorKind::ExpiredSignature)
+    ));
+}
@@ -0,0 +1,86 @@
+//! Webhook delivery model module
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Status of a delivery to be attempted, again or for the first time
+pub const DELIVERY_PENDING: &str = "pending";
+
+/// Status of a delivery accepted by its endpoint
+pub const DELIVERY_DELIVERED: &str = "delivered";
+
+/// Status of a delivery given up after its last attempt, until redelivered
+pub const DELIVERY_FAILED: &str = "failed";
+
+/// Event sent to a subscription
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Delivery {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub payload: Value,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_response_status: Option<i16>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+}
+
+/// Delivery with its attempts, most recent first
+#[derive(Serialize, Debug)]
+pub struct DeliveryLog {
+    #[serde(flatten)]
+    pub delivery: Delivery,
+    pub log: Vec<DeliveryAttempt>,
+}
+
+/// Request sent for a delivery
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeliveryAttempt {
+    pub attempted_at: NaiveDateTime,
+    /// Status of the response, none if no response was received
+    pub response_status: Option<i16>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+}
+
+impl DeliveryAttempt {
+    pub fn succeeded(&self) -> bool {
+        self.response_status.is_some_and(|status| (200..300).contains(&status))
+    }
+}
+
+/// Delivery claimed to be attempted, with what is needed to send it
+#[derive(Debug, Clone)]
+pub struct PendingDelivery {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub payload: Value,
+    /// Attempts, including the current one
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+    /// W3C `traceparent` of the request which caused the event
+    pub traceparent: Option<String>,
+    /// ID of the request which caused the event
+    pub request_id: Option<String>,
+}
+
+/// Deliveries search criteria
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DeliveryFilter {
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Default and maximum number of deliveries returned by a search
+pub const DELIVERY_PAGE_SIZE: i64 = 50;
+pub const DELIVERY_MAX_PAGE_SIZE: i64 = 500;
@@ -0,0 +1,17 @@
+//! Webhooks domain module
+
+pub mod delivery;
+pub mod signature;
+pub mod subscription;
+pub mod webhook_repository;
+
+/// Types of the events partners can subscribe to, emitted by the user and order components
+pub const EVENT_TYPES: [&str; 7] = [
+    "user.registered",
+    "user.updated",
+    "user.deleted",
+    "user.restored",
+    "user.purged",
+    "order.placed",
+    "order.shipped",
+];
@@ -0,0 +1,94 @@
+//! Webhook subscription model module
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+use crate::domain::EVENT_TYPES;
+
+/// Endpoint of a partner receiving domain events
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Subscription {
+    pub id: Uuid,
+    pub url: String,
+    /// Types of the events sent, all when empty
+    pub event_types: Vec<String>,
+    /// Key of the payload signatures, only returned on creation
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub enabled: bool,
+    /// Failed attempts since the last successful one
+    pub consecutive_failures: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl Subscription {
+    pub fn new(request: SubscriptionRequest) -> Self {
+        let now = chrono::Utc::now().naive_utc();
+        let enabled = request.enabled.unwrap_or(true);
+
+        Self {
+            id: Uuid::new_v4(),
+            url: request.url,
+            event_types: request.event_types,
+            secret: request.secret.unwrap_or_else(generate_secret),
+            enabled,
+            consecutive_failures: 0,
+            disabled_at: if enabled { None } else { Some(now) },
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn matches(&self, event_type: &str) -> bool {
+        self.event_types.is_empty() || self.event_types.iter().any(|t| t == event_type)
+    }
+}
+
+/// Random signing secret, e.g. `whsec_3f0c...`
+pub fn generate_secret() -> String {
+    format!("whsec_{}{}", Uuid::new_v4().to_simple(), Uuid::new_v4().to_simple())
+}
+
+/// Subscription returned on creation, the only time its secret is shown
+#[derive(Serialize, Debug)]
+pub struct SubscriptionCreated {
+    #[serde(flatten)]
+    pub subscription: Subscription,
+    pub secret: String,
+}
+
+/// Editable fields of a subscription, for `POST` and `PUT`
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct SubscriptionRequest {
+    #[validate(length(max = 2048), custom = "validate_url")]
+    pub url: String,
+    #[serde(default)]
+    #[validate(custom = "validate_event_types")]
+    pub event_types: Vec<String>,
+    /// Generated on creation, and kept on update, when not set
+    #[validate(length(min = 16, max = 255))]
+    pub secret: Option<String>,
+    /// Enabled when not set; enabling a subscription clears its failures
+    pub enabled: Option<bool>,
+}
+
+fn validate_url(url: &str) -> Result<(), ValidationError> {
+    match url::Url::parse(url) {
+        Ok(url) if (url.scheme() == "http" || url.scheme() == "https") && url.has_host() => Ok(()),
+        _ => Err(ValidationError::new("url")),
+    }
+}
+
+fn validate_event_types(event_types: &[String]) -> Result<(), ValidationError> {
+    if event_types.iter().all(|t| EVENT_TYPES.contains(&t.as_str())) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("event_types"))
+    }
+}
@@ -0,0 +1,47 @@
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use northwind_core::audit::AuditContext;
+use northwind_core::errors::AppError;
+use northwind_core::outbox::OutboxEvent;
+use uuid::Uuid;
+
+use crate::domain::delivery::{Delivery, DeliveryAttempt, DeliveryFilter, DeliveryLog, PendingDelivery};
+use crate::domain::subscription::{Subscription, SubscriptionRequest};
+
+#[async_trait]
+pub trait WebhookRepository: Send + Sync {
+    async fn create(&self, subscription: &Subscription, audit: &AuditContext) -> Result<(), AppError>;
+    async fn get_all(&self) -> Result<Vec<Subscription>, AppError>;
+    async fn get_by_id(&self, id: Uuid) -> Result<Option<Subscription>, AppError>;
+    /// Updates a subscription, returns it unless not found
+    async fn update(
+        &self,
+        id: Uuid,
+        request: &SubscriptionRequest,
+        audit: &AuditContext,
+    ) -> Result<Option<Subscription>, AppError>;
+    /// Deletes a subscription and its deliveries, returns the number of deleted subscriptions
+    async fn delete(&self, id: Uuid, audit: &AuditContext) -> Result<u64, AppError>;
+
+    /// Deliveries of a subscription, most recent first
+    async fn get_deliveries(&self, subscription_id: Uuid, filter: &DeliveryFilter) -> Result<Vec<Delivery>, AppError>;
+    async fn get_delivery(&self, subscription_id: Uuid, id: Uuid) -> Result<Option<DeliveryLog>, AppError>;
+    /// Schedules a delivery to be attempted again right away, returns the number of updated deliveries
+    async fn redeliver(&self, subscription_id: Uuid, id: Uuid, audit: &AuditContext) -> Result<u64, AppError>;
+
+    /// Creates the deliveries of an event to the enabled subscriptions to its type, once per
+    /// subscription whatever the number of calls, returns the number of created deliveries
+    async fn enqueue(&self, event: &OutboxEvent) -> Result<u64, AppError>;
+    /// Claims up to `limit` pending deliveries of enabled subscriptions until `lease_until`
+    async fn claim(&self, limit: i64, lease_until: NaiveDateTime) -> Result<Vec<PendingDelivery>, AppError>;
+    /// Logs an attempt and updates its delivery, to be retried at `retry_at` if failed, or given
+    /// up when not set. Past `disable_after` consecutive failures, the subscription is disabled,
+    /// in which case `true` is returned.
+    async fn record_attempt(
+        &self,
+        delivery: &PendingDelivery,
+        attempt: &DeliveryAttempt,
+        retry_at: Option<NaiveDateTime>,
+        disable_after: i32,
+    ) -> Result<bool, AppError>;
+}
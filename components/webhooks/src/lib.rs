@@ -0,0 +1,5 @@
+//! Outgoing webhooks: subscriptions of partners to domain events, and their deliveries
+
+pub mod domain;
+pub mod repositories;
+pub mod services;
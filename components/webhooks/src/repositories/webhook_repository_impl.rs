@@ -0,0 +1,455 @@
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use northwind_core::audit::{self, AuditContext, AuditRecord};
+use northwind_core::errors::AppError;
+use northwind_core::outbox::OutboxEvent;
+use northwind_core::telemetry::query_span;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::domain::delivery::{
+    Delivery, DeliveryAttempt, DeliveryFilter, DeliveryLog, PendingDelivery, DELIVERY_MAX_PAGE_SIZE, DELIVERY_PAGE_SIZE,
+};
+use crate::domain::subscription::{Subscription, SubscriptionRequest};
+use crate::domain::webhook_repository::WebhookRepository;
+
+pub struct WebhookRepositoryImpl {
+    pub pool: Arc<PgPool>,
+}
+
+#[async_trait]
+impl WebhookRepository for WebhookRepositoryImpl {
+    #[tracing::instrument(name = "WebhookRepository::create", skip(self, subscription, audit), fields(subscription_id = %subscription.id))]
+    async fn create(&self, subscription: &Subscription, audit: &AuditContext) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+                INSERT INTO webhook_subscriptions
+                    (id, url, event_types, secret, enabled, consecutive_failures, disabled_at, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            subscription.id,
+            subscription.url,
+            &subscription.event_types,
+            subscription.secret,
+            subscription.enabled,
+            subscription.consecutive_failures,
+            subscription.disabled_at,
+            subscription.created_at,
+            subscription.updated_at,
+        )
+        .execute(&mut tx)
+        .instrument(query_span("INSERT", "webhook_subscriptions"))
+        .await?;
+
+        // Secrets are not kept in the audit log
+        let record = AuditRecord {
+            action: "webhook.create",
+            target_type: "webhook",
+            target_id: Some(subscription.id.to_string()),
+            before: None,
+            after: Some(json!(subscription)),
+        };
+        audit::record(&mut tx, audit, record)
+            .instrument(query_span("INSERT", "audit_events"))
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "WebhookRepository::get_all", skip(self))]
+    async fn get_all(&self) -> Result<Vec<Subscription>, AppError> {
+        Ok(sqlx::query_as!(
+            Subscription,
+            r#"
+                SELECT id, url, event_types, secret, enabled, consecutive_failures, disabled_at, created_at, updated_at
+                FROM webhook_subscriptions
+                ORDER BY created_at
+            "#
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "webhook_subscriptions"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "WebhookRepository::get_by_id", skip(self))]
+    async fn get_by_id(&self, id: Uuid) -> Result<Option<Subscription>, AppError> {
+        Ok(sqlx::query_as!(
+            Subscription,
+            r#"
+                SELECT id, url, event_types, secret, enabled, consecutive_failures, disabled_at, created_at, updated_at
+                FROM webhook_subscriptions
+                WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self.pool.as_ref())
+        .instrument(query_span("SELECT", "webhook_subscriptions"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "WebhookRepository::update", skip(self, request, audit))]
+    async fn update(
+        &self,
+        id: Uuid,
+        request: &SubscriptionRequest,
+        audit: &AuditContext,
+    ) -> Result<Option<Subscription>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let before = sqlx::query_as!(
+            Subscription,
+            r#"
+                SELECT id, url, event_types, secret, enabled, consecutive_failures, disabled_at, created_at, updated_at
+                FROM webhook_subscriptions
+                WHERE id = $1
+                FOR UPDATE
+            "#,
+            id
+        )
+        .fetch_optional(&mut tx)
+        .instrument(query_span("SELECT", "webhook_subscriptions"))
+        .await?;
+        let before = match before {
+            Some(before) => before,
+            None => return Ok(None),
+        };
+
+        // Enabling a subscription clears its failures
+        let enabled = request.enabled.unwrap_or(true);
+        let now = Utc::now().naive_utc();
+        let after = sqlx::query_as!(
+            Subscription,
+            r#"
+                UPDATE webhook_subscriptions
+                SET url = $2,
+                    event_types = $3,
+                    secret = COALESCE($4, secret),
+                    enabled = $5,
+                    consecutive_failures = CASE WHEN $5 AND NOT enabled THEN 0 ELSE consecutive_failures END,
+                    disabled_at = CASE WHEN $5 THEN NULL ELSE COALESCE(disabled_at, $6) END,
+                    updated_at = $6
+                WHERE id = $1
+                RETURNING id, url, event_types, secret, enabled, consecutive_failures, disabled_at, created_at, updated_at
+            "#,
+            id,
+            request.url,
+            &request.event_types,
+            request.secret,
+            enabled,
+            now,
+        )
+        .fetch_one(&mut tx)
+        .instrument(query_span("UPDATE", "webhook_subscriptions"))
+        .await?;
+
+        let mut record = AuditRecord::change("webhook.update", "webhook", id.to_string(), json!(before), json!(after));
+        if before.secret != after.secret {
+            record.after = record.after.map(|mut after| {
+                after["secret"] = json!("changed");
+                after
+            });
+        }
+        audit::record(&mut tx, audit, record)
+            .instrument(query_span("INSERT", "audit_events"))
+            .await?;
+        tx.commit().await?;
+
+        Ok(Some(after))
+    }
+
+    #[tracing::instrument(name = "WebhookRepository::delete", skip(self, audit))]
+    async fn delete(&self, id: Uuid, audit: &AuditContext) -> Result<u64, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let deleted = sqlx::query!(r#"DELETE FROM webhook_subscriptions WHERE id = $1 RETURNING url"#, id)
+            .fetch_optional(&mut tx)
+            .instrument(query_span("DELETE", "webhook_subscriptions"))
+            .await?;
+        let deleted = match deleted {
+            Some(deleted) => deleted,
+            None => return Ok(0),
+        };
+
+        let record = AuditRecord {
+            action: "webhook.delete",
+            target_type: "webhook",
+            target_id: Some(id.to_string()),
+            before: Some(json!({ "url": deleted.url })),
+            after: None,
+        };
+        audit::record(&mut tx, audit, record)
+            .instrument(query_span("INSERT", "audit_events"))
+            .await?;
+        tx.commit().await?;
+
+        Ok(1)
+    }
+
+    #[tracing::instrument(name = "WebhookRepository::get_deliveries", skip(self))]
+    async fn get_deliveries(&self, subscription_id: Uuid, filter: &DeliveryFilter) -> Result<Vec<Delivery>, AppError> {
+        Ok(sqlx::query_as!(
+            Delivery,
+            r#"
+                SELECT id, subscription_id, event_id, event_type, payload, status, attempts, next_attempt_at,
+                    last_response_status, last_error, created_at, delivered_at
+                FROM webhook_deliveries
+                WHERE subscription_id = $1
+                    AND ($2::text IS NULL OR status = $2)
+                ORDER BY created_at DESC, id
+                LIMIT $3 OFFSET $4
+            "#,
+            subscription_id,
+            filter.status,
+            filter
+                .limit
+                .unwrap_or(DELIVERY_PAGE_SIZE)
+                .clamp(1, DELIVERY_MAX_PAGE_SIZE),
+            filter.offset.unwrap_or(0).max(0),
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "webhook_deliveries"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "WebhookRepository::get_delivery", skip(self))]
+    async fn get_delivery(&self, subscription_id: Uuid, id: Uuid) -> Result<Option<DeliveryLog>, AppError> {
+        let delivery = sqlx::query_as!(
+            Delivery,
+            r#"
+                SELECT id, subscription_id, event_id, event_type, payload, status, attempts, next_attempt_at,
+                    last_response_status, last_error, created_at, delivered_at
+                FROM webhook_deliveries
+                WHERE id = $1 AND subscription_id = $2
+            "#,
+            id,
+            subscription_id
+        )
+        .fetch_optional(self.pool.as_ref())
+        .instrument(query_span("SELECT", "webhook_deliveries"))
+        .await?;
+        let delivery = match delivery {
+            Some(delivery) => delivery,
+            None => return Ok(None),
+        };
+
+        let log = sqlx::query_as!(
+            DeliveryAttempt,
+            r#"
+                SELECT attempted_at, response_status, error, duration_ms
+                FROM webhook_attempts
+                WHERE delivery_id = $1
+                ORDER BY attempted_at DESC, id DESC
+            "#,
+            id
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("SELECT", "webhook_attempts"))
+        .await?;
+
+        Ok(Some(DeliveryLog { delivery, log }))
+    }
+
+    #[tracing::instrument(name = "WebhookRepository::redeliver", skip(self, audit))]
+    async fn redeliver(&self, subscription_id: Uuid, id: Uuid, audit: &AuditContext) -> Result<u64, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let updated = sqlx::query!(
+            r#"
+                UPDATE webhook_deliveries
+                SET status = 'pending', attempts = 0, next_attempt_at = $3
+                WHERE id = $1 AND subscription_id = $2
+                RETURNING status
+            "#,
+            id,
+            subscription_id,
+            Utc::now().naive_utc(),
+        )
+        .fetch_optional(&mut tx)
+        .instrument(query_span("UPDATE", "webhook_deliveries"))
+        .await?;
+        if updated.is_none() {
+            return Ok(0);
+        }
+
+        let record = AuditRecord {
+            action: "webhook.redeliver",
+            target_type: "webhook_delivery",
+            target_id: Some(id.to_string()),
+            before: None,
+            after: None,
+        };
+        audit::record(&mut tx, audit, record)
+            .instrument(query_span("INSERT", "audit_events"))
+            .await?;
+        tx.commit().await?;
+
+        Ok(1)
+    }
+
+    #[tracing::instrument(name = "WebhookRepository::enqueue", skip(self, event), fields(event_id = %event.id))]
+    async fn enqueue(&self, event: &OutboxEvent) -> Result<u64, AppError> {
+        let payload = serde_json::to_value(event).map_err(|e| AppError::InternalError { message: e.to_string() })?;
+
+        sqlx::query!(
+            r#"
+                INSERT INTO webhook_deliveries (id, subscription_id, event_id, event_type, payload, next_attempt_at, created_at,
+                    traceparent, request_id)
+                SELECT uuid_generate_v4(), id, $1, $2::text, $3, $4, $4, $5, $6
+                FROM webhook_subscriptions
+                WHERE enabled AND (cardinality(event_types) = 0 OR $2::text = ANY(event_types))
+                ON CONFLICT (subscription_id, event_id) DO NOTHING
+            "#,
+            event.id,
+            event.event_type,
+            payload,
+            Utc::now().naive_utc(),
+            event.traceparent,
+            event.request_id,
+        )
+        .execute(self.pool.as_ref())
+        .instrument(query_span("INSERT", "webhook_deliveries"))
+        .await
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.into())
+    }
+
+    #[tracing::instrument(name = "WebhookRepository::claim", skip(self))]
+    async fn claim(&self, limit: i64, lease_until: NaiveDateTime) -> Result<Vec<PendingDelivery>, AppError> {
+        // Skipping locked rows lets several instances send deliveries at the same time
+        Ok(sqlx::query_as!(
+            PendingDelivery,
+            r#"
+                UPDATE webhook_deliveries AS d
+                SET attempts = d.attempts + 1, next_attempt_at = $3
+                FROM webhook_subscriptions AS s
+                WHERE s.id = d.subscription_id
+                    AND d.id IN (
+                        SELECT wd.id
+                        FROM webhook_deliveries AS wd
+                        JOIN webhook_subscriptions AS ws ON ws.id = wd.subscription_id
+                        WHERE wd.status = 'pending' AND wd.next_attempt_at <= $1 AND ws.enabled
+                        ORDER BY wd.next_attempt_at
+                        LIMIT $2
+                        FOR UPDATE OF wd SKIP LOCKED
+                    )
+                RETURNING d.id, d.subscription_id, d.event_id, d.event_type, d.payload, d.attempts,
+                    s.url AS "url!", s.secret AS "secret!", d.traceparent, d.request_id
+            "#,
+            Utc::now().naive_utc(),
+            limit,
+            lease_until,
+        )
+        .fetch_all(self.pool.as_ref())
+        .instrument(query_span("UPDATE", "webhook_deliveries"))
+        .await?)
+    }
+
+    #[tracing::instrument(name = "WebhookRepository::record_attempt", skip(self, delivery, attempt), fields(delivery_id = %delivery.id))]
+    async fn record_attempt(
+        &self,
+        delivery: &PendingDelivery,
+        attempt: &DeliveryAttempt,
+        retry_at: Option<NaiveDateTime>,
+        disable_after: i32,
+    ) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+                INSERT INTO webhook_attempts (delivery_id, attempted_at, response_status, error, duration_ms)
+                VALUES ($1, $2, $3, $4, $5)
+            "#,
+            delivery.id,
+            attempt.attempted_at,
+            attempt.response_status,
+            attempt.error,
+            attempt.duration_ms,
+        )
+        .execute(&mut tx)
+        .instrument(query_span("INSERT", "webhook_attempts"))
+        .await?;
+
+        if attempt.succeeded() {
+            sqlx::query!(
+                r#"
+                    UPDATE webhook_deliveries
+                    SET status = 'delivered', delivered_at = $2, last_response_status = $3, last_error = NULL
+                    WHERE id = $1
+                "#,
+                delivery.id,
+                attempt.attempted_at,
+                attempt.response_status,
+            )
+            .execute(&mut tx)
+            .instrument(query_span("UPDATE", "webhook_deliveries"))
+            .await?;
+            sqlx::query!(
+                r#"UPDATE webhook_subscriptions SET consecutive_failures = 0 WHERE id = $1"#,
+                delivery.subscription_id
+            )
+            .execute(&mut tx)
+            .instrument(query_span("UPDATE", "webhook_subscriptions"))
+            .await?;
+            tx.commit().await?;
+
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+                UPDATE webhook_deliveries
+                SET status = CASE WHEN $2::timestamp IS NULL THEN 'failed' ELSE 'pending' END,
+                    next_attempt_at = COALESCE($2, next_attempt_at),
+                    last_response_status = $3,
+                    last_error = $4
+                WHERE id = $1
+            "#,
+            delivery.id,
+            retry_at,
+            attempt.response_status,
+            attempt.error,
+        )
+        .execute(&mut tx)
+        .instrument(query_span("UPDATE", "webhook_deliveries"))
+        .await?;
+        let subscription = sqlx::query!(
+            r#"
+                UPDATE webhook_subscriptions
+                SET consecutive_failures = consecutive_failures + 1
+                WHERE id = $1
+                RETURNING consecutive_failures, enabled
+            "#,
+            delivery.subscription_id
+        )
+        .fetch_optional(&mut tx)
+        .instrument(query_span("UPDATE", "webhook_subscriptions"))
+        .await?;
+
+        let disabled = subscription.is_some_and(|s| s.enabled && s.consecutive_failures >= disable_after);
+        if disabled {
+            sqlx::query!(
+                r#"UPDATE webhook_subscriptions SET enabled = FALSE, disabled_at = $2 WHERE id = $1"#,
+                delivery.subscription_id,
+                attempt.attempted_at,
+            )
+            .execute(&mut tx)
+            .instrument(query_span("UPDATE", "webhook_subscriptions"))
+            .await?;
+
+            let record = AuditRecord::change(
+                "webhook.disable",
+                "webhook",
+                delivery.subscription_id.to_string(),
+                json!({ "enabled": true }),
+                json!({ "enabled": false }),
+            );
+            audit::record(&mut tx, &AuditContext::default(), record)
+                .instrument(query_span("INSERT", "audit_events"))
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(disabled)
+    }
+}
@@ -0,0 +1,20 @@
+use async_trait::async_trait;
+use northwind_core::errors::AppError;
+use northwind_core::outbox::{EventSink, OutboxEvent};
+use std::sync::Arc;
+
+use crate::domain::webhook_repository::WebhookRepository;
+
+/// Outbox sink turning each event into deliveries to the subscriptions to its type
+pub struct SubscriptionsSink {
+    pub repo: Arc<dyn WebhookRepository>,
+}
+
+#[async_trait(?Send)]
+impl EventSink for SubscriptionsSink {
+    async fn publish(&self, event: &OutboxEvent) -> Result<(), AppError> {
+        self.repo.enqueue(event).await?;
+
+        Ok(())
+    }
+}
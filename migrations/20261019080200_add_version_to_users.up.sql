@@ -0,0 +1,3 @@
+-- Add up migration script here
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
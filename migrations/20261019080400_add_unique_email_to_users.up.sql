@@ -0,0 +1,24 @@
+-- Add up migration script here
+
+-- Only the oldest active user of each email is kept, the others are soft deleted
+WITH duplicates AS (
+    UPDATE users
+    SET deleted_at = now() AT TIME ZONE 'utc', version = version + 1
+    WHERE deleted_at IS NULL
+        AND EXISTS (
+            SELECT 1
+            FROM users AS original
+            WHERE lower(trim(original.email)) = lower(trim(users.email))
+                AND original.deleted_at IS NULL
+                AND (original.created_at, original.id) < (users.created_at, users.id)
+        )
+    RETURNING id, deleted_at
+)
+INSERT INTO audit_events (occurred_at, action, target_type, target_id, before, after)
+SELECT deleted_at, 'user.deduplicate', 'user', id::text, '{"deleted_at": null}', jsonb_build_object('deleted_at', deleted_at)
+FROM duplicates;
+
+-- Emails are stored as normalized by the API
+UPDATE users SET email = lower(trim(email)) WHERE email <> lower(trim(email));
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_unique_idx ON users (lower(email)) WHERE deleted_at IS NULL;
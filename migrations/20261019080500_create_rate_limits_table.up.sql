@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+-- Rate limiting counters do not need to survive a crash
+CREATE UNLOGGED TABLE IF NOT EXISTS rate_limits (
+    key VARCHAR(255) PRIMARY KEY,
+    -- Theoretical arrival time, in milliseconds since the epoch
+    tat BIGINT NOT NULL
+);
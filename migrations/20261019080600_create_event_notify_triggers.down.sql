@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+DROP TRIGGER IF EXISTS products_notify_event ON products;
+DROP TRIGGER IF EXISTS orders_notify_event ON orders;
+DROP TRIGGER IF EXISTS users_notify_event ON users;
+DROP FUNCTION IF EXISTS notify_product_event();
+DROP FUNCTION IF EXISTS notify_order_event();
+DROP FUNCTION IF EXISTS notify_user_event();
+DROP FUNCTION IF EXISTS notify_event(TEXT, JSONB);
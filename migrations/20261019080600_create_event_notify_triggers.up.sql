@@ -0,0 +1,90 @@
+-- Add up migration script here
+
+-- Domain events are sent on the `northwind_events` channel, as
+-- `{"type": ..., "data": ..., "occurred_at": ...}`, once their transaction commits
+CREATE OR REPLACE FUNCTION notify_event(event_type TEXT, data JSONB) RETURNS VOID AS $$
+BEGIN
+    PERFORM pg_notify(
+        'northwind_events',
+        jsonb_build_object('type', event_type, 'data', data, 'occurred_at', now())::text
+    );
+END;
+$$ LANGUAGE plpgsql;
+
+-- Passwords are never sent
+CREATE OR REPLACE FUNCTION notify_user_event() RETURNS TRIGGER AS $$
+DECLARE
+    data JSONB := jsonb_build_object(
+        'id', NEW.id,
+        'email', NEW.email,
+        'firstname', NEW.firstname,
+        'lastname', NEW.lastname,
+        'role', NEW.role,
+        'version', NEW.version
+    );
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        PERFORM notify_event('user.registered', data);
+    ELSIF OLD.deleted_at IS NULL AND NEW.deleted_at IS NOT NULL THEN
+        PERFORM notify_event('user.deleted', data);
+    ELSIF OLD.deleted_at IS NOT NULL AND NEW.deleted_at IS NULL THEN
+        PERFORM notify_event('user.restored', data);
+    ELSIF NEW.deleted_at IS NULL
+        AND (OLD.email, OLD.firstname, OLD.lastname, OLD.role)
+            IS DISTINCT FROM (NEW.email, NEW.firstname, NEW.lastname, NEW.role) THEN
+        PERFORM notify_event('user.updated', data);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_notify_event
+    AFTER INSERT OR UPDATE ON users
+    FOR EACH ROW EXECUTE FUNCTION notify_user_event();
+
+CREATE OR REPLACE FUNCTION notify_order_event() RETURNS TRIGGER AS $$
+DECLARE
+    data JSONB := jsonb_build_object(
+        'order_id', NEW.order_id,
+        'customer_id', NEW.customer_id,
+        'employee_id', NEW.employee_id,
+        'order_date', NEW.order_date,
+        'required_date', NEW.required_date,
+        'shipped_date', NEW.shipped_date,
+        'ship_via', NEW.ship_via
+    );
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        PERFORM notify_event('order.placed', data);
+    ELSIF OLD.shipped_date IS NULL AND NEW.shipped_date IS NOT NULL THEN
+        PERFORM notify_event('order.shipped', data);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER orders_notify_event
+    AFTER INSERT OR UPDATE ON orders
+    FOR EACH ROW EXECUTE FUNCTION notify_order_event();
+
+-- Sent when the stock of a product still sold reaches its reorder level
+CREATE OR REPLACE FUNCTION notify_product_event() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.discontinued = 0
+        AND NEW.units_in_stock <= NEW.reorder_level
+        AND (TG_OP = 'INSERT' OR NOT COALESCE(OLD.units_in_stock <= OLD.reorder_level, FALSE)) THEN
+        PERFORM notify_event('product.stock_low', jsonb_build_object(
+            'product_id', NEW.product_id,
+            'product_name', NEW.product_name,
+            'units_in_stock', NEW.units_in_stock,
+            'units_on_order', NEW.units_on_order,
+            'reorder_level', NEW.reorder_level
+        ));
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER products_notify_event
+    AFTER INSERT OR UPDATE ON products
+    FOR EACH ROW EXECUTE FUNCTION notify_product_event();
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TRIGGER IF EXISTS orders_outbox_event ON orders;
+DROP FUNCTION IF EXISTS outbox_order_event();
@@ -0,0 +1,41 @@
+-- Add up migration script here
+
+-- Orders are not written by the API, so their domain events are appended to the outbox by a
+-- trigger, in the transaction of the change
+CREATE OR REPLACE FUNCTION outbox_order_event() RETURNS TRIGGER AS $$
+DECLARE
+    event_type TEXT;
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        event_type := 'order.placed';
+    ELSIF OLD.shipped_date IS NULL AND NEW.shipped_date IS NOT NULL THEN
+        event_type := 'order.shipped';
+    ELSE
+        RETURN NULL;
+    END IF;
+
+    INSERT INTO outbox (id, event_type, aggregate_type, aggregate_id, payload, occurred_at, next_attempt_at)
+    VALUES (
+        uuid_generate_v4(),
+        event_type,
+        'order',
+        NEW.order_id::text,
+        jsonb_build_object(
+            'order_id', NEW.order_id,
+            'customer_id', NEW.customer_id,
+            'employee_id', NEW.employee_id,
+            'order_date', NEW.order_date,
+            'required_date', NEW.required_date,
+            'shipped_date', NEW.shipped_date,
+            'ship_via', NEW.ship_via
+        ),
+        now() AT TIME ZONE 'utc',
+        now() AT TIME ZONE 'utc'
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER orders_outbox_event
+    AFTER INSERT OR UPDATE ON orders
+    FOR EACH ROW EXECUTE FUNCTION outbox_order_event();
@@ -0,0 +1,19 @@
+-- Add up migration script here
+
+CREATE TABLE IF NOT EXISTS import_jobs (
+    id UUID PRIMARY KEY,
+    -- customers, products or users
+    entity VARCHAR(32) NOT NULL,
+    -- pending, running, completed or failed
+    status VARCHAR(15) NOT NULL DEFAULT 'pending',
+    total_rows INTEGER NOT NULL,
+    processed_rows INTEGER NOT NULL DEFAULT 0,
+    imported_rows INTEGER NOT NULL DEFAULT 0,
+    -- Row of the file which failed the job, counted from 1 without the CSV header
+    failed_row INTEGER,
+    error TEXT,
+    created_by UUID,
+    created_at TIMESTAMP NOT NULL,
+    started_at TIMESTAMP,
+    finished_at TIMESTAMP
+);
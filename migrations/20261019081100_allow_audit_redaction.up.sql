@@ -0,0 +1,21 @@
+-- Add up migration script here
+
+-- Events are never deleted, and only updated to redact the personal data of their diffs when
+-- a user is erased: the erasing transaction runs `SET LOCAL northwind.audit_redaction = 'on'`
+-- and may then change nothing but `before` and `after`
+CREATE OR REPLACE FUNCTION audit_events_append_only() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE'
+        AND current_setting('northwind.audit_redaction', true) = 'on'
+        AND (NEW.id, NEW.occurred_at, NEW.actor_id, NEW.action, NEW.target_type, NEW.target_id, NEW.ip,
+             NEW.user_agent, NEW.request_id)
+            IS NOT DISTINCT FROM
+            (OLD.id, OLD.occurred_at, OLD.actor_id, OLD.action, OLD.target_type, OLD.target_id, OLD.ip,
+             OLD.user_agent, OLD.request_id)
+    THEN
+        RETURN NEW;
+    END IF;
+
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
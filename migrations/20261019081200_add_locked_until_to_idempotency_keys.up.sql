@@ -0,0 +1,7 @@
+-- Add up migration script here
+
+-- End of the lease of the request handling a key, after which the key can be claimed again
+-- by a retry: a request whose server crashed never stores its response nor frees its key
+ALTER TABLE idempotency_keys ADD COLUMN IF NOT EXISTS locked_until TIMESTAMP NULL;
+
+UPDATE idempotency_keys SET locked_until = created_at WHERE status IS NULL;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+ALTER TABLE webhook_deliveries DROP COLUMN IF EXISTS traceparent;
+ALTER TABLE outbox DROP COLUMN IF EXISTS traceparent;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+-- W3C trace context of the request which caused an event, sent along with its deliveries so
+-- that they continue its trace although they are made in the background
+ALTER TABLE outbox ADD COLUMN IF NOT EXISTS traceparent TEXT NULL;
+ALTER TABLE webhook_deliveries ADD COLUMN IF NOT EXISTS traceparent TEXT NULL;
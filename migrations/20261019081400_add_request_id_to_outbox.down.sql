@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+ALTER TABLE webhook_deliveries DROP COLUMN IF EXISTS request_id;
+ALTER TABLE outbox DROP COLUMN IF EXISTS request_id;
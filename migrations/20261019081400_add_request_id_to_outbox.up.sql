@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+-- ID of the request which caused an event, sent along with its deliveries so that the receivers
+-- can correlate them with the request
+ALTER TABLE outbox ADD COLUMN IF NOT EXISTS request_id TEXT NULL;
+ALTER TABLE webhook_deliveries ADD COLUMN IF NOT EXISTS request_id TEXT NULL;